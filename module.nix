@@ -54,13 +54,29 @@ in
         #   '';
         # };
 
-        # archivesDirectory = mkOption {
-        #   type = types.path;
-        #   default = "/var/webtarot/archives";
-        #   description = ''
-        #     Webtarot directory path where game archives are stored
-        #   '';
-        # };
+        storeDirectory = mkOption {
+          type = types.path;
+          default = "/var/webtarot/games";
+          description = ''
+            Webtarot directory path where running games are saved
+          '';
+        };
+
+        archivesDirectory = mkOption {
+          type = types.path;
+          default = "/var/webtarot/archives";
+          description = ''
+            Webtarot directory path where game archives are stored
+          '';
+        };
+
+        archivesPort = mkOption {
+          type = types.port;
+          default = 8003;
+          description = ''
+            Port of the Webtarot archives API.
+          '';
+        };
 
         archivageCheck = mkOption {
           type = types.int;
@@ -91,6 +107,9 @@ in
           upstream webtarot-api {
           server localhost:${toString cfg.apiPort};
           }
+          upstream webtarot-archives {
+          server localhost:${toString cfg.archivesPort};
+          }
         '';
         virtualHosts = 
         let proxyConfig = ''
@@ -121,15 +140,18 @@ in
                 extraConfig = proxyConfig;
                 proxyPass = "http://webtarot-api/";
               };
+              "/archives" = {
+                proxyPass = "http://webtarot-archives";
+              };
             };
           };
         };
       };
 
-      # systemd.tmpfiles.rules = [
-      #   "d ${cfg.archivesDirectory} 0755 ${cfg.user} ${cfg.group} - -"
-      #   "d ${cfg.dbUri} 0755 ${cfg.user} ${cfg.group} - -"
-      # ];
+      systemd.tmpfiles.rules = [
+        "d ${cfg.archivesDirectory} 0755 ${cfg.user} ${cfg.group} - -"
+        "d ${cfg.storeDirectory} 0755 ${cfg.user} ${cfg.group} - -"
+      ];
 
       systemd.targets.webtarot = {
         description = "Webtarot";
@@ -146,6 +168,11 @@ in
           partOf = [ "webtarot.target" ];
 
           serviceConfig = serviceConfig // { 
+            Environment = [
+              "WEBTAROT_STORE=${cfg.storeDirectory}"
+              "WEBTAROT_ARCHIVES=${cfg.archivesDirectory}"
+              "WEBTAROT_ARCHIVES_PORT=${toString cfg.archivesPort}"
+            ];
             ExecStart = ''${pkgs.webtarot}/bin/webtarot_server -d ${pkgs.webtarot-front}/ \
               -p ${toString cfg.apiPort} \
               '';
@@ -13,6 +13,7 @@ use super::pos;
 ///
 /// Determines the winning conditions and the score on success.
 #[derive(EnumIter, PartialEq, PartialOrd, Clone, Copy, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum Target {
     Prise,
     Garde,
@@ -64,6 +65,7 @@ impl ToString for Target {
 
 /// Contract taken
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Contract {
     /// Initial author of the contract.
     pub author: pos::PlayerPos,
@@ -93,6 +95,7 @@ impl ToString for Contract {
 
 /// Current state of an auction
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum AuctionState {
     /// Players are still bidding for the highest contract
     Bidding,
@@ -104,6 +107,7 @@ pub enum AuctionState {
 
 /// Bidding status for a player
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum BidStatus {
     Todo,
     Passed,
@@ -113,6 +117,7 @@ pub enum BidStatus {
 
 /// Represents the entire auction process.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Auction {
     contract: Option<Contract>,
     players_status: Vec<BidStatus>, 
@@ -152,8 +157,23 @@ impl fmt::Display for BidError {
 impl Auction {
     /// Starts a new auction, starting with the player `first`.
     pub fn new(first: pos::PlayerPos) -> Self {
+        let (hands, dog) = super::deal_hands(first.count as usize);
+        Auction::with_hands(first, hands, dog)
+    }
+
+    /// Starts a new auction with the cards dealt from `seed`.
+    pub fn new_seeded(first: pos::PlayerPos, seed: [u8; 32]) -> Self {
+        let (hands, dog) = super::deal_seeded_hands(seed, first.count as usize);
+        Auction::with_hands(first, hands, dog)
+    }
+
+    /// Starts a new auction with the given cards.
+    pub fn new_dealt(first: pos::PlayerPos, hands: Vec<cards::Hand>, dog: cards::Hand) -> Self {
+        Auction::with_hands(first, hands, dog)
+    }
+
+    fn with_hands(first: pos::PlayerPos, hands: Vec<cards::Hand>, dog: cards::Hand) -> Self {
         let count = first.count as usize;
-        let (hands, dog) = super::deal_hands(count);
         Auction {
             contract: None,
             players_status: vec![BidStatus::Todo; count],
@@ -170,6 +190,11 @@ impl Auction {
         self.dog = dog;
     }
 
+    /// Returns the dog
+    pub fn dog(&self) -> cards::Hand {
+        self.dog
+    }
+
     /// Returns the current state of the auctions.
     pub fn get_state(&self) -> AuctionState {
         self.state
@@ -12,6 +12,7 @@ use std::string::ToString;
 
 /// One of the four Suits: Heart, Spade, Diamond, Club.
 #[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[repr(u64)]
 pub enum Suit {
     /// The suit of hearts.
@@ -299,7 +300,7 @@ impl FromStr for Rank {
             "19" => Ok(Rank::Rank19),
             "20" => Ok(Rank::Rank20),
             "21" => Ok(Rank::Rank21),
-            "22" => Ok(Rank::Rank22),
+            "22" | "E" => Ok(Rank::Rank22),
             _ => Err(format!("invalid rank: {}", s)),
         }
     }
@@ -308,6 +309,7 @@ impl FromStr for Rank {
 
 /// Represents a single card.
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Card(u64, u32); // (pips & courts , trumps)
 
 
@@ -444,6 +446,7 @@ impl Card {
 
 /// Represents an unordered set of cards.
 #[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Hand(u64, u32);
 
 impl From<Vec<Card>> for Hand {
@@ -788,6 +791,9 @@ mod tests {
         let card = Card::from_str("11T");
         assert!(card.is_ok());
         assert!(card.unwrap().to_locale_string("fr") == "11A");
+
+        let excuse = Card::new(Suit::Trump, Rank::Rank22);
+        assert_eq!(Card::from_str(&excuse.to_string()), Ok(excuse));
     }
 
     #[test]
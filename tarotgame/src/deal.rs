@@ -13,6 +13,7 @@ use super::AnnounceType;
 
 /// Describes the state of a coinche deal, ready to play a card.
 #[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DealState {
     players: Vec<cards::Hand>,
     partner: pos::PlayerPos,
@@ -44,6 +45,7 @@ impl fmt::Display for DealState {
 
 /// Result of a deal.
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum DealResult {
     /// The deal is still playing
     Nothing,
@@ -199,6 +201,11 @@ impl DealState {
         self.partner
     }
 
+    /// Returns the points won by each player so far
+    pub fn points(&self) -> &[f32] {
+        &self.points
+    }
+
     //TODO return Result instead of bool
     pub fn call_king(&mut self, pos: pos::PlayerPos, card: cards::Card) -> bool {
         if pos != self.contract.author {
@@ -586,6 +593,20 @@ impl DealState {
         }
     }
 
+    /// Returns the tricks of the deal, the last one being the current trick
+    pub fn tricks(&self) -> &Vec<trick::Trick> {
+        &self.tricks
+    }
+
+    /// Returns the finished tricks, in the order they were played
+    pub fn completed_tricks(&self) -> &[trick::Trick] {
+        if self.is_over() {
+            &self.tricks
+        } else {
+            &self.tricks[..self.tricks.len() - 1]
+        }
+    }
+
     /// Returns the current trick.
     pub fn current_trick(&self) -> &trick::Trick {
         let i = self.tricks.len() - 1;
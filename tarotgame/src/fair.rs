@@ -0,0 +1,231 @@
+//! Provably fair deals.
+//!
+//! The server draws a secret seed and publishes its hash, the commitment, before the deal. Each
+//! player then adds some entropy of his own, and the cards are shuffled with a seed combining
+//! them all. Once the deal is over the server reveals its seed : anyone can check that it is the
+//! one committed to, that his entropy was used, and deal the cards again from the combined seed.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+use crate::cards::Hand;
+use crate::{check_deal_ok, deal_seeded_hands};
+
+pub type Seed = [u8; 32];
+
+fn sha256(parts: &[&[u8]]) -> Seed {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// Hash of the server seed, published before the deal
+pub fn commitment(server_seed: &Seed) -> Seed {
+    sha256(&[server_seed])
+}
+
+/// Seed the cards are shuffled with : the hash of the server seed followed by the position and
+/// the entropy of each player who gave one. It is hashed again as long as a player would get the
+/// petit sec, so that the deal doesn't have to be cancelled.
+pub fn combined_seed(server_seed: &Seed, entropies: &[Option<Seed>]) -> Seed {
+    let positions: Vec<[u8; 1]> = (0..entropies.len()).map(|pos| [pos as u8]).collect();
+    let mut parts: Vec<&[u8]> = vec![server_seed];
+    for (pos, entropy) in positions.iter().zip(entropies) {
+        if let Some(entropy) = entropy {
+            parts.push(pos);
+            parts.push(entropy);
+        }
+    }
+    let mut seed = sha256(&parts);
+    while !check_deal_ok(&deal_seeded_hands(seed, entropies.len()).0) {
+        seed = sha256(&[&seed]);
+    }
+    seed
+}
+
+/// Lowercase hexadecimal notation, to show a seed or a commitment
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Secret of the server for a deal to come, and the entropy given so far by the players
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FairDeal {
+    server_seed: Seed,
+    /// By position
+    entropies: Vec<Option<Seed>>,
+}
+
+impl FairDeal {
+    /// Draws the secret seed of a deal between `count` players
+    pub fn new(count: usize) -> Self {
+        FairDeal {
+            server_seed: rand::random(),
+            entropies: vec![None; count],
+        }
+    }
+
+    pub fn commitment(&self) -> Seed {
+        commitment(&self.server_seed)
+    }
+
+    /// Adds the entropy of the player at `pos`, only the first one he gives is kept.
+    pub fn contribute(&mut self, pos: usize, entropy: Seed) {
+        if let Some(given @ None) = self.entropies.get_mut(pos) {
+            *given = Some(entropy);
+        }
+    }
+
+    /// Whether the player at each position gave his entropy
+    pub fn contributors(&self) -> Vec<bool> {
+        self.entropies.iter().map(Option::is_some).collect()
+    }
+
+    pub fn seed(&self) -> Seed {
+        combined_seed(&self.server_seed, &self.entropies)
+    }
+
+    /// Reveals the secret, once the deal is over
+    pub fn proof(&self) -> FairProof {
+        FairProof {
+            server_seed: self.server_seed,
+            entropies: self.entropies.clone(),
+        }
+    }
+}
+
+/// What a deal was shuffled with, revealed at its end
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FairProof {
+    pub server_seed: Seed,
+    /// Entropy of the players, by position, none for those who didn't give one
+    pub entropies: Vec<Option<Seed>>,
+}
+
+/// Reason why a deal could not be verified
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum FairError {
+    /// The revealed seed is not the one the server committed to.
+    Commitment,
+    /// The entropy given by the player was not used.
+    Entropy,
+    /// The cards were not dealt from the combined seed.
+    Cards,
+}
+
+impl fmt::Display for FairError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            FairError::Commitment => write!(f, "the revealed seed doesn't match the commitment"),
+            FairError::Entropy => write!(f, "the entropy of the player was not used"),
+            FairError::Cards => write!(f, "the cards were not dealt from the combined seed"),
+        }
+    }
+}
+
+impl FairProof {
+    pub fn seed(&self) -> Seed {
+        combined_seed(&self.server_seed, &self.entropies)
+    }
+
+    /// Checks the revealed seed against the `commitment` published before the deal, and the
+    /// entropy `own` given by the player at its position, if any. Returns the hands and the dog
+    /// dealt from the combined seed.
+    pub fn dealt(
+        &self,
+        commitment: &Seed,
+        own: Option<(usize, Seed)>,
+    ) -> Result<(Vec<Hand>, Hand), FairError> {
+        if self::commitment(&self.server_seed) != *commitment {
+            return Err(FairError::Commitment);
+        }
+        if let Some((pos, entropy)) = own {
+            if self.entropies.get(pos) != Some(&Some(entropy)) {
+                return Err(FairError::Entropy);
+            }
+        }
+        Ok(deal_seeded_hands(self.seed(), self.entropies.len()))
+    }
+
+    /// Checks the deal of `hands` and `dog` against the `commitment` published before it, and
+    /// the entropy `own` given by the player at its position, if any.
+    pub fn verify(
+        &self,
+        commitment: &Seed,
+        own: Option<(usize, Seed)>,
+        hands: &[Hand],
+        dog: Hand,
+    ) -> Result<(), FairError> {
+        let (dealt, dealt_dog) = self.dealt(commitment, own)?;
+        if dealt != hands || dealt_dog != dog {
+            return Err(FairError::Cards);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contributed(count: usize) -> FairDeal {
+        let mut fair = FairDeal::new(count);
+        for pos in 0..count {
+            fair.contribute(pos, [pos as u8 + 1; 32]);
+        }
+        fair
+    }
+
+    #[test]
+    fn test_verify() {
+        let fair = contributed(5);
+        let commitment = fair.commitment();
+        let (hands, dog) = deal_seeded_hands(fair.seed(), 5);
+        assert!(check_deal_ok(&hands));
+
+        let proof = fair.proof();
+        assert_eq!(Ok(()), proof.verify(&commitment, Some((2, [3; 32])), &hands, dog));
+        assert_eq!(Ok(()), proof.verify(&commitment, None, &hands, dog));
+
+        let other = FairDeal::new(5).commitment();
+        assert_eq!(Err(FairError::Commitment), proof.verify(&other, None, &hands, dog));
+        assert_eq!(
+            Err(FairError::Entropy),
+            proof.verify(&commitment, Some((2, [9; 32])), &hands, dog)
+        );
+        let mut swapped = hands.clone();
+        swapped.swap(0, 1);
+        assert_eq!(Err(FairError::Cards), proof.verify(&commitment, None, &swapped, dog));
+
+        // a player checks the hand he was dealt
+        assert_eq!(Ok((hands, dog)), proof.dealt(&commitment, Some((2, [3; 32]))));
+        assert_eq!(Err(FairError::Commitment), proof.dealt(&other, None).map(|_| ()));
+    }
+
+    #[test]
+    fn test_contribute() {
+        let mut fair = FairDeal::new(4);
+        let alone = fair.seed();
+        fair.contribute(1, [7; 32]);
+        fair.contribute(1, [8; 32]);
+        fair.contribute(6, [8; 32]);
+        assert_eq!(vec![false, true, false, false], fair.contributors());
+        assert_eq!(Some([7; 32]), fair.proof().entropies[1]);
+        assert_ne!(alone, fair.seed());
+
+        // the position of the entropy counts
+        let mut moved = fair.clone();
+        moved.entropies.swap(1, 2);
+        assert_ne!(fair.seed(), moved.seed());
+    }
+
+    #[test]
+    fn test_to_hex() {
+        assert_eq!("00ff1a", to_hex(&[0, 255, 26]));
+        assert_eq!(64, to_hex(&commitment(&[0; 32])).len());
+    }
+}
@@ -0,0 +1,507 @@
+//! Generates deals matching constraints on the hands and the dog.
+//!
+//! Constraints are read from JSON (see `DealConstraints`) or from a small expression syntax,
+//! one clause per seat separated by `;` :
+//!
+//! ```text
+//! 0: trumps>=10 oudlers>=2 has:21T,ET void:H; dog: not:1T; any: S>=6
+//! ```
+//!
+//! A seat is a position at the table, `dog`, or `any` for some hand whichever its position.
+//! Its terms are:
+//!
+//! * `trumps`, `oudlers`, `H`, `S`, `D` or `C` followed by `>=`, `<=`, `=`, `>` or `<` and a
+//!   number of cards,
+//! * `has:` and `not:` followed by cards, written as read by `Card::from_str`,
+//! * `void:` followed by suits.
+
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::cards::{Card, Hand, Suit};
+use crate::{check_deal_ok, deal_seeded_hands, deal_size, dog_size};
+
+/// Number of cards between two bounds, both included
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Bounds {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Bounds { min: 0, max: 78 }
+    }
+}
+
+impl Bounds {
+    pub fn contains(self, count: usize) -> bool {
+        self.min <= count && count <= self.max
+    }
+
+    /// Restricts the bounds with a comparison such as `>=3`
+    fn restrict(&mut self, comparison: &str) -> Result<(), String> {
+        let (op, number) = match comparison.find(|c: char| c.is_ascii_digit()) {
+            Some(idx) => comparison.split_at(idx),
+            None => return Err(format!("missing number: {}", comparison)),
+        };
+        let n: usize = number
+            .parse()
+            .map_err(|_| format!("invalid number: {}", number))?;
+        match op {
+            ">=" => self.min = self.min.max(n),
+            "<=" => self.max = self.max.min(n),
+            "=" | "==" => {
+                self.min = self.min.max(n);
+                self.max = self.max.min(n);
+            }
+            ">" => self.min = self.min.max(n + 1),
+            "<" if n > 0 => self.max = self.max.min(n - 1),
+            _ => return Err(format!("invalid comparison: {}", comparison)),
+        }
+        Ok(())
+    }
+}
+
+/// Constraints on the cards of one seat
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SeatConstraints {
+    /// Number of trumps, the excuse included
+    pub trumps: Bounds,
+    pub oudlers: Bounds,
+    /// Number of cards of the plain suits
+    pub suits: HashMap<Suit, Bounds>,
+    /// Cards the seat must have
+    #[serde(with = "card_names")]
+    pub has: Vec<Card>,
+    /// Cards the seat must not have
+    #[serde(with = "card_names")]
+    pub not: Vec<Card>,
+    /// Suits the seat has no card of
+    pub void: Vec<Suit>,
+}
+
+impl SeatConstraints {
+    pub fn matches(&self, hand: Hand) -> bool {
+        self.trumps.contains(hand.trumps_count())
+            && self.oudlers.contains(hand.count_oudlers() as usize)
+            && self
+                .suits
+                .iter()
+                .all(|(suit, bounds)| bounds.contains(hand.get_suit_cards(suit).len()))
+            && self.has.iter().all(|card| hand.has(*card))
+            && !self.not.iter().any(|card| hand.has(*card))
+            && !self.void.iter().any(|suit| hand.has_any(*suit))
+    }
+
+    /// The seat can't receive `card` on top of the cards of `hand` without breaking a
+    /// constraint
+    fn excludes(&self, hand: Hand, card: Card) -> bool {
+        let suit = card.suit();
+        let full = if suit == Suit::Trump {
+            hand.trumps_count() >= self.trumps.max
+        } else {
+            let held = hand.get_suit_cards(&suit).len();
+            matches!(self.suits.get(&suit), Some(bounds) if held >= bounds.max)
+        };
+        full || self.not.contains(&card)
+            || self.void.contains(&suit)
+            || (card.is_oudler() && hand.count_oudlers() as usize >= self.oudlers.max)
+    }
+
+    fn parse_term(&mut self, term: &str) -> Result<(), String> {
+        if let Some(cards) = term.strip_prefix("has:") {
+            self.has.extend(parse_list::<Card>(cards)?);
+        } else if let Some(cards) = term.strip_prefix("not:") {
+            self.not.extend(parse_list::<Card>(cards)?);
+        } else if let Some(suits) = term.strip_prefix("void:") {
+            self.void.extend(parse_list::<Suit>(suits)?);
+        } else {
+            let idx = term
+                .find(['<', '>', '='])
+                .ok_or(format!("invalid term: {}", term))?;
+            let (key, comparison) = term.split_at(idx);
+            let bounds = match key {
+                "trumps" | "T" => &mut self.trumps,
+                "oudlers" => &mut self.oudlers,
+                _ => match Suit::from_str(key)? {
+                    Suit::Trump => &mut self.trumps,
+                    suit => self.suits.entry(suit).or_default(),
+                },
+            };
+            bounds.restrict(comparison)?;
+        }
+        Ok(())
+    }
+}
+
+/// Constraints on a whole deal, as read from JSON:
+///
+/// ```json
+/// {
+///   "players": [{"trumps": {"min": 10}, "has": ["21T"], "void": ["Heart"]}],
+///   "dog": {"not": ["1T"]}
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct DealConstraints {
+    /// Constraints on the hands, by position
+    pub players: Vec<SeatConstraints>,
+    pub dog: SeatConstraints,
+    /// Constraints on some hand, whichever its position
+    pub any: SeatConstraints,
+}
+
+impl FromStr for DealConstraints {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut constraints = DealConstraints::default();
+        for clause in s.split(';').map(str::trim).filter(|c| !c.is_empty()) {
+            let (seat, terms) = clause
+                .split_once(':')
+                .ok_or(format!("missing seat: {}", clause))?;
+            let seat_constraints = match seat.trim() {
+                "dog" => &mut constraints.dog,
+                "any" => &mut constraints.any,
+                pos => {
+                    let n: usize = pos.parse().map_err(|_| format!("invalid seat: {}", pos))?;
+                    if constraints.players.len() <= n {
+                        constraints
+                            .players
+                            .resize(n + 1, SeatConstraints::default());
+                    }
+                    &mut constraints.players[n]
+                }
+            };
+            for term in terms.split_whitespace() {
+                seat_constraints.parse_term(term)?;
+            }
+        }
+        Ok(constraints)
+    }
+}
+
+impl DealConstraints {
+    /// Checks that the constraints fit a table of `count` players
+    pub fn check(&self, count: usize) -> Result<(), String> {
+        if self.players.len() > count {
+            return Err(format!(
+                "no seat {} at a table of {}",
+                self.players.len() - 1,
+                count
+            ));
+        }
+        let mut required = Hand::new();
+        for seat in self.players.iter().chain(Some(&self.dog)) {
+            for card in &seat.has {
+                if required.has(*card) {
+                    return Err(format!("{} is required twice", card.to_string()));
+                }
+                required.add(*card);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, hands: &[Hand], dog: Hand) -> bool {
+        self.players
+            .iter()
+            .zip(hands.iter())
+            .all(|(seat, hand)| seat.matches(*hand))
+            && self.dog.matches(dog)
+            && hands.iter().any(|hand| self.any.matches(*hand))
+    }
+
+    /// Walks the seeds from `from` until one gives a matching deal without petit sec, at most
+    /// `tries` of them
+    pub fn find_seed(&self, count: usize, from: [u8; 32], tries: usize) -> Option<[u8; 32]> {
+        std::iter::successors(Some(from), |seed| next_seed(*seed))
+            .take(tries)
+            .find(|seed| {
+                let (hands, dog) = deal_seeded_hands(*seed, count);
+                check_deal_ok(&hands) && self.matches(&hands, dog)
+            })
+    }
+
+    /// Builds a matching deal without petit sec : the required cards are given first, then the
+    /// minimum numbers of oudlers, trumps and cards of each suit, the others are dealt at random
+    /// among the seats which can take them. Gives up after `tries` deals.
+    pub fn deal(&self, count: usize, tries: usize) -> Option<(Vec<Hand>, Hand)> {
+        let mut rng = thread_rng();
+        (0..tries)
+            .filter_map(|_| self.try_deal(count, &mut rng))
+            .find(|(hands, dog)| check_deal_ok(hands) && self.matches(hands, *dog))
+    }
+
+    fn try_deal<R: Rng>(&self, count: usize, rng: &mut R) -> Option<(Vec<Hand>, Hand)> {
+        let free = SeatConstraints::default();
+        // the dog is the last seat
+        let seats: Vec<&SeatConstraints> = (0..count)
+            .map(|n| self.players.get(n).unwrap_or(&free))
+            .chain(Some(&self.dog))
+            .collect();
+        let mut sizes = vec![deal_size(count); count];
+        sizes.push(dog_size(count));
+        let mut dealing = Dealing {
+            seats,
+            sizes,
+            hands: vec![Hand::new(); count + 1],
+            dealt: Hand::new(),
+        };
+
+        for n in 0..=count {
+            let seat = dealing.seats[n];
+            for card in &seat.has {
+                if !dealing.give(n, *card) {
+                    return None;
+                }
+            }
+        }
+        for n in 0..=count {
+            let seat = dealing.seats[n];
+            let filled = dealing.fill(n, seat.oudlers.min, |card| card.is_oudler(), rng)
+                && dealing.fill(n, seat.trumps.min, |card| card.suit() == Suit::Trump, rng)
+                && seat.suits.iter().all(|(suit, bounds)| {
+                    dealing.fill(n, bounds.min, |card| card.suit() == *suit, rng)
+                });
+            if !filled {
+                return None;
+            }
+        }
+
+        let mut cards = dealing.undealt(|_| true);
+        cards.shuffle(rng);
+        for card in cards {
+            let rooms: Vec<(usize, usize)> = (0..=count)
+                .map(|n| (n, dealing.room(n)))
+                .filter(|(n, room)| {
+                    *room > 0 && !dealing.seats[*n].excludes(dealing.hands[*n], card)
+                })
+                .collect();
+            let total: usize = rooms.iter().map(|(_, room)| room).sum();
+            if total == 0 {
+                return None;
+            }
+            // each free place has the same chance to get the card
+            let mut place = rng.gen_range(0, total);
+            for (n, room) in rooms {
+                if place < room {
+                    dealing.give(n, card);
+                    break;
+                }
+                place -= room;
+            }
+        }
+        let mut hands = dealing.hands;
+        let dog = hands.pop().unwrap_or_default();
+        Some((hands, dog))
+    }
+}
+
+/// Deal in progress, the dog being the last seat
+struct Dealing<'a> {
+    seats: Vec<&'a SeatConstraints>,
+    sizes: Vec<usize>,
+    hands: Vec<Hand>,
+    dealt: Hand,
+}
+
+impl<'a> Dealing<'a> {
+    fn room(&self, n: usize) -> usize {
+        self.sizes[n] - self.hands[n].size()
+    }
+
+    fn give(&mut self, n: usize, card: Card) -> bool {
+        if self.dealt.has(card) || self.room(n) == 0 {
+            return false;
+        }
+        self.hands[n].add(card);
+        self.dealt.add(card);
+        true
+    }
+
+    fn undealt<F: Fn(Card) -> bool>(&self, kind: F) -> Vec<Card> {
+        (0..66)
+            .chain(70..82)
+            .map(Card::from_id)
+            .filter(|card| !self.dealt.has(*card) && kind(*card))
+            .collect()
+    }
+
+    /// Gives to the seat `n` random cards of a kind until it has `min` of them
+    fn fill<F: Fn(Card) -> bool, R: Rng>(
+        &mut self,
+        n: usize,
+        min: usize,
+        kind: F,
+        rng: &mut R,
+    ) -> bool {
+        let held = self.hands[n]
+            .list()
+            .into_iter()
+            .filter(|card| kind(*card))
+            .count();
+        let mut cards = self.undealt(kind);
+        cards.shuffle(rng);
+        let mut cards = cards.into_iter();
+        for _ in held..min {
+            let seat = self.seats[n];
+            let hand = self.hands[n];
+            let card = match cards.find(|card| !seat.excludes(hand, *card)) {
+                Some(card) => card,
+                None => return false,
+            };
+            if !self.give(n, card) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Seed following `seed`, counting from the first byte, none after the last one
+pub fn next_seed(mut seed: [u8; 32]) -> Option<[u8; 32]> {
+    for byte in seed.iter_mut() {
+        if *byte < 255 {
+            *byte += 1;
+            return Some(seed);
+        }
+        *byte = 0;
+    }
+    None
+}
+
+fn parse_list<T: FromStr<Err = String>>(list: &str) -> Result<Vec<T>, String> {
+    list.split(',').map(T::from_str).collect()
+}
+
+/// Cards written as read by `Card::from_str` : 7H, KS, 21T, ET for the excuse
+mod card_names {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::str::FromStr;
+
+    use crate::cards::Card;
+
+    pub fn serialize<S: Serializer>(cards: &[Card], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            cards
+                .iter()
+                .map(|card| card.rank().to_string() + &card.suit().to_safe_string()),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Card>, D::Error> {
+        let names: Vec<String> = Vec::deserialize(deserializer)?;
+        names
+            .iter()
+            .map(|name| Card::from_str(name).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let constraints: DealConstraints =
+            "0: trumps>=10 oudlers=2 has:21T,ET; 2: H<3 void:S; dog: not:1T"
+                .parse()
+                .unwrap();
+        assert_eq!(3, constraints.players.len());
+        assert_eq!(Bounds { min: 10, max: 78 }, constraints.players[0].trumps);
+        assert_eq!(Bounds { min: 2, max: 2 }, constraints.players[0].oudlers);
+        assert_eq!(
+            vec![Card::from_str("21T").unwrap(), Card::excuse()],
+            constraints.players[0].has
+        );
+        assert_eq!(SeatConstraints::default(), constraints.players[1]);
+        assert_eq!(
+            Some(&Bounds { min: 0, max: 2 }),
+            constraints.players[2].suits.get(&Suit::Heart)
+        );
+        assert_eq!(vec![Suit::Spade], constraints.players[2].void);
+        assert_eq!(vec![Card::from_str("1T").unwrap()], constraints.dog.not);
+
+        assert!("trumps>=10".parse::<DealConstraints>().is_err());
+        assert!("0: trumps>>10".parse::<DealConstraints>().is_err());
+        assert!("0: has:ZH".parse::<DealConstraints>().is_err());
+        assert!("north: void:H".parse::<DealConstraints>().is_err());
+    }
+
+    #[test]
+    fn test_json() {
+        let json = r#"{"players": [{"trumps": {"min": 10}, "has": ["21T"], "suits": {"Heart": {"max": 1}}}], "dog": {"void": ["Club"]}}"#;
+        let constraints: DealConstraints = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            "0: trumps>=10 has:21T H<=1; dog: void:C"
+                .parse::<DealConstraints>()
+                .unwrap(),
+            constraints
+        );
+        let written = serde_json::to_string(&constraints).unwrap();
+        assert_eq!(constraints, serde_json::from_str(&written).unwrap());
+    }
+
+    #[test]
+    fn test_check() {
+        let constraints: DealConstraints = "4: trumps>5".parse().unwrap();
+        assert!(constraints.check(5).is_ok());
+        assert!(constraints.check(4).is_err());
+        let constraints: DealConstraints = "0: has:ET; dog: has:ET".parse().unwrap();
+        assert!(constraints.check(5).is_err());
+    }
+
+    #[test]
+    fn test_next_seed() {
+        let mut expected = [1; 32];
+        expected[0] = 2;
+        assert_eq!(Some(expected), next_seed([1; 32]));
+        let mut seed = [0; 32];
+        seed[0] = 255;
+        let mut expected = [0; 32];
+        expected[1] = 1;
+        assert_eq!(Some(expected), next_seed(seed));
+        assert_eq!(None, next_seed([255; 32]));
+    }
+
+    #[test]
+    fn test_find_seed() {
+        let constraints: DealConstraints = "any: trumps>9".parse().unwrap();
+        let seed = constraints.find_seed(5, [0; 32], 100_000).unwrap();
+        let (hands, _dog) = deal_seeded_hands(seed, 5);
+        assert!(hands.iter().any(|hand| hand.trumps_count() > 9));
+        assert_eq!(Some(seed), constraints.find_seed(5, seed, 1));
+    }
+
+    #[test]
+    fn test_deal() {
+        let constraints: DealConstraints =
+            "1: trumps>=12 has:21T,1T void:H,S; 3: oudlers=0 C>=6; dog: has:KH not:ET"
+                .parse()
+                .unwrap();
+        for _ in 0..20 {
+            let (hands, dog) = constraints.deal(5, 1000).unwrap();
+            assert!(constraints.matches(&hands, dog));
+            assert!(hands.iter().all(|hand| hand.size() == 15));
+            assert_eq!(3, dog.size());
+            let mut all = dog;
+            for hand in &hands {
+                all.merge(*hand);
+            }
+            assert_eq!(78, all.size());
+        }
+
+        let impossible: DealConstraints = "0: trumps>=20; 1: trumps>=5".parse().unwrap();
+        assert_eq!(None, impossible.deal(4, 10));
+    }
+}
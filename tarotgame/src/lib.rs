@@ -45,11 +45,14 @@ extern crate test;
 pub mod bid;
 pub mod cards;
 pub mod deal;
+pub mod fair;
+pub mod generator;
 pub mod points;
 pub mod pos;
 pub mod trick;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum AnnounceType {
     Poignee,
     DoublePoignee,
@@ -104,6 +107,7 @@ impl AnnounceType {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Announce {
     pub atype: AnnounceType, 
     pub proof: Option<cards::Hand> 
@@ -181,6 +185,42 @@ fn check_deal_ok(hands: &Vec<cards::Hand>) -> bool {
    !hands.iter().any(|hand| hand.has_petit_sec())
 }
 
+/// Picks a random seed whose deal has no petit sec, so that the deal can be replayed from it.
+pub fn random_deal_seed(count: usize) -> [u8; 32] {
+    loop {
+        let seed: [u8; 32] = rand::random();
+        if check_deal_ok(&deal_seeded_hands(seed, count).0) {
+            return seed;
+        }
+    }
+}
+
+/// Checks that `hands` and `dog` can be dealt to `count` players : each hand and the dog have
+/// the right number of cards, and no card is dealt twice, so that they share the 78 cards.
+pub fn check_hands(hands: &[cards::Hand], dog: cards::Hand, count: usize) -> Result<(), String> {
+    if hands.len() != count {
+        return Err(format!("{} hands for {} players", hands.len(), count));
+    }
+    for (n, hand) in hands.iter().enumerate() {
+        if hand.size() != deal_size(count) {
+            return Err(format!("hand {} has {} cards instead of {}", n, hand.size(), deal_size(count)));
+        }
+    }
+    if dog.size() != dog_size(count) {
+        return Err(format!("the dog has {} cards instead of {}", dog.size(), dog_size(count)));
+    }
+    let mut dealt = cards::Hand::new();
+    for hand in hands.iter().chain(Some(&dog)) {
+        for card in hand.list() {
+            if dealt.has(card) {
+                return Err(format!("{} is dealt twice", card.to_string()));
+            }
+            dealt.add(card);
+        }
+    }
+    Ok(())
+}
+
 /// Deal cards for players deterministically.
 pub fn deal_seeded_hands(seed: [u8; 32], count: usize) -> (Vec<cards::Hand>, cards::Hand) {
     let mut d = cards::Deck::new();
@@ -280,6 +320,25 @@ fn test_deals_tarot3() {
 
 }
 
+#[test]
+fn test_check_hands() {
+    let (hands, dog) = deal_seeded_hands([3; 32], 4);
+    assert_eq!(Ok(()), check_hands(&hands, dog, 4));
+    assert!(check_hands(&hands, dog, 5).is_err());
+    assert!(check_hands(&hands[..3], dog, 4).is_err());
+
+    // a card of the dog given again to the first player, instead of one of his
+    let mut twice = hands.clone();
+    let card = twice[0].list()[0];
+    twice[0].remove(card);
+    twice[0].add(dog.list()[0]);
+    assert_eq!(Err(format!("{} is dealt twice", dog.list()[0].to_string())), check_hands(&twice, dog, 4));
+
+    let mut small_dog = dog;
+    small_dog.remove(dog.list()[0]);
+    assert!(check_hands(&hands, small_dog, 4).is_err());
+}
+
 #[cfg(test)]
 fn idx_from_id(id: u32) -> u32 {
     if id < 66 {
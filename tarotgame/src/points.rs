@@ -3,12 +3,7 @@
 use super::cards;
 
 pub fn score(points: f32, oudlers_count: u8) -> (f32, f32) {
-    let raw_points = match oudlers_count {
-        3 => points - 36.0,
-        2 => points - 41.0,
-        1 => points - 51.0,
-        _ => points - 56.0,
-    };
+    let raw_points = points - needed_points(oudlers_count);
     let points = if raw_points < 0.0 {
         raw_points - 25.0
     } else {
@@ -17,6 +12,16 @@ pub fn score(points: f32, oudlers_count: u8) -> (f32, f32) {
     (raw_points, points)
 }
 
+/// Returns the number of points the taker must make with `oudlers_count` oudlers
+pub fn needed_points(oudlers_count: u8) -> f32 {
+    match oudlers_count {
+        3 => 36.0,
+        2 => 41.0,
+        1 => 51.0,
+        _ => 56.0,
+    }
+}
+
 /// Returns the number of points `card` is worth
 pub fn points(card: cards::Card) -> f32 {
     match card.rank() {
@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 
 /// A position in the table
 #[derive(PartialEq,Clone,Copy,Debug,Serialize,Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct PlayerPos {
     pub pos: AbsolutePos,
     pub count: u8,
@@ -11,6 +12,7 @@ pub struct PlayerPos {
 
 /// A position in the table
 #[derive(PartialEq,Clone,Copy,Debug,Serialize,Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum AbsolutePos {
     /// Player 0
     P0,
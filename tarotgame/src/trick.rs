@@ -11,6 +11,7 @@ const MAX_PLAYERS: usize = 5;
 
 /// The current cards on the table.
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Trick {
     /// Cards currently on the table (they are `None` until played).
     pub cards: [Option<cards::Card>; MAX_PLAYERS],
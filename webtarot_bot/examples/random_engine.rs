@@ -0,0 +1,60 @@
+//! Example of an external engine : it never bids and plays random legal cards.
+//!
+//! Run a bot using it with :
+//! `cargo build --example random_engine && cargo run -- --engine ../target/debug/examples/random_engine`
+
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use rand::seq::SliceRandom;
+
+use tarotgame::cards::{Card, Rank, Suit};
+use webtarot_bot::engine::{EngineRequest, EngineResponse, LegalMoves, PROTOCOL_VERSION};
+
+fn main() {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut rng = rand::thread_rng();
+
+    for line in stdin.lock().lines() {
+        let line = line.expect("Can't read request");
+        let request: EngineRequest = serde_json::from_str(&line).expect("Can't parse request");
+        let response = match request {
+            EngineRequest::Hello { protocol_version } => {
+                if protocol_version != PROTOCOL_VERSION {
+                    eprintln!("unsupported protocol version {}", protocol_version);
+                    break;
+                }
+                EngineResponse::Ready {
+                    name: String::from("random"),
+                }
+            }
+            EngineRequest::Decide { legal, .. } => match legal {
+                LegalMoves::Bid { .. } => EngineResponse::Pass,
+                LegalMoves::CallKing { cards } => EngineResponse::CallKing {
+                    card: cards.choose(&mut rng).unwrap().clone(),
+                },
+                LegalMoves::MakeDog { cards, size } => {
+                    // Kings, oudlers and trumps are not allowed in the dog
+                    let mut allowed: Vec<String> = cards
+                        .into_iter()
+                        .filter(|c| {
+                            let card = Card::from_str(c).unwrap();
+                            card.rank() != Rank::RankK && card.suit() != Suit::Trump
+                        })
+                        .collect();
+                    allowed.shuffle(&mut rng);
+                    allowed.truncate(size);
+                    EngineResponse::MakeDog { cards: allowed }
+                }
+                LegalMoves::Play { cards } => EngineResponse::Play {
+                    card: cards.choose(&mut rng).unwrap().clone(),
+                },
+            },
+            EngineRequest::Quit => break,
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        writeln!(stdout, "{}", json).unwrap();
+        stdout.flush().unwrap();
+    }
+}
@@ -0,0 +1,483 @@
+//! External engines : bots written in any language, running as a child process and speaking a
+//! line based JSON protocol on their standard input and output (see `doc/engine-protocol.md`).
+//!
+//! The bot keeps the connection to the game server and forwards a view of the game and the
+//! legal moves to the engine each time a decision is needed. If the engine does not answer in
+//! time or answers with an illegal move, the builtin strategy is used instead.
+
+use std::env;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command as ProcessCommand, Stdio};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize};
+
+use tarotgame::{
+    bid::Target,
+    cards::{Card, Hand, Rank, Suit},
+};
+use webtarot_protocol::{
+    BidCommand, CallKingCommand, GamePlayCommand, GameStateSnapshot, MakeDogCommand, PlayCommand,
+};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Environment variable giving the engine command line to bots started by the socket listener
+pub const ENGINE_ENV: &str = "WEBTAROT_BOT_ENGINE";
+/// Time given to the engine to answer a request
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// What the bot sees of the game. Cards are written as in `Card::to_string` ("7♥", "K♠",
+/// "21T"...), positions are player numbers from 0 to nb_players - 1.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EngineView {
+    pub nb_players: u8,
+    /// Position of the bot
+    pub pos: usize,
+    pub hand: Vec<String>,
+    pub taker: Option<usize>,
+    pub contract: Option<String>,
+    pub king: Option<String>,
+    /// Dog shown to all players (empty for garde sans and garde contre)
+    pub dog: Vec<String>,
+    pub trick_count: usize,
+    /// Player who started the current trick
+    pub trick_first: usize,
+    /// Cards of the current trick, indexed by player position
+    pub trick: Vec<Option<String>>,
+}
+
+impl EngineView {
+    pub fn new(state: &GameStateSnapshot, pos: usize) -> Self {
+        let deal = &state.deal;
+        let nb_players = state.nb_players as usize;
+        EngineView {
+            nb_players: state.nb_players,
+            pos,
+            hand: card_strings(&deal.hand.list()),
+            taker: deal.contract.as_ref().map(|c| c.author.to_n()),
+            contract: deal.contract.as_ref().map(|c| c.to_string()),
+            king: deal.king.map(|k| k.to_string()),
+            dog: card_strings(&deal.initial_dog.list()),
+            trick_count: deal.trick_count,
+            trick_first: deal.last_trick.first.to_n(),
+            trick: deal.last_trick.cards[..nb_players]
+                .iter()
+                .map(|c| c.map(|c| c.to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// Moves allowed for the current decision
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum LegalMoves {
+    /// Passing is always allowed
+    Bid {
+        targets: Vec<String>,
+    },
+    CallKing {
+        cards: Vec<String>,
+    },
+    /// `size` cards to choose among `cards` (the taker hand and the dog)
+    MakeDog {
+        cards: Vec<String>,
+        size: usize,
+    },
+    Play {
+        cards: Vec<String>,
+    },
+}
+
+impl LegalMoves {
+    /// Contracts higher than the current one
+    pub fn bid(current: Option<Target>) -> Self {
+        let targets = [
+            Target::Prise,
+            Target::Garde,
+            Target::GardeSans,
+            Target::GardeContre,
+        ]
+        .iter()
+        .filter(|target| current.lt(&Some(**target)))
+        .map(|target| target.to_string())
+        .collect();
+        LegalMoves::Bid { targets }
+    }
+
+    /// Kings, or queens if the taker has all the kings, or jacks if he also has all the queens
+    pub fn call_king(hand: Hand) -> Self {
+        let mut ranks = vec![Rank::RankK];
+        if hand.has_all_rank(Rank::RankK) {
+            ranks.push(Rank::RankQ);
+            if hand.has_all_rank(Rank::RankQ) {
+                ranks.push(Rank::RankJ);
+            }
+        }
+        let cards = ranks
+            .iter()
+            .flat_map(|rank| {
+                [Suit::Club, Suit::Diamond, Suit::Spade, Suit::Heart]
+                    .iter()
+                    .map(move |suit| Card::new(*suit, *rank).to_string())
+            })
+            .collect();
+        LegalMoves::CallKing { cards }
+    }
+
+    pub fn make_dog(hand: Hand, dog: Hand) -> Self {
+        let mut cards = hand;
+        cards.merge(dog);
+        LegalMoves::MakeDog {
+            cards: card_strings(&cards.list()),
+            size: dog.size(),
+        }
+    }
+
+    pub fn play(playable: &[Card]) -> Self {
+        LegalMoves::Play {
+            cards: card_strings(playable),
+        }
+    }
+
+    pub fn candidates(&self) -> Vec<String> {
+        match self {
+            LegalMoves::Bid { targets } => targets.clone(),
+            LegalMoves::CallKing { cards }
+            | LegalMoves::MakeDog { cards, .. }
+            | LegalMoves::Play { cards } => cards.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EngineRequest {
+    Hello { protocol_version: u32 },
+    Decide { view: EngineView, legal: LegalMoves },
+    Quit,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EngineResponse {
+    Ready { name: String },
+    Bid { target: String },
+    Pass,
+    CallKing { card: String },
+    MakeDog { cards: Vec<String> },
+    Play { card: String },
+}
+
+impl EngineResponse {
+    /// Checks the answer against the legal moves and converts it to a game command
+    pub fn into_command(self, legal: &LegalMoves) -> Result<GamePlayCommand, EngineError> {
+        match (self, legal) {
+            (EngineResponse::Pass, LegalMoves::Bid { .. }) => Ok(GamePlayCommand::Pass),
+            (EngineResponse::Bid { target }, LegalMoves::Bid { targets }) => {
+                let target = Target::from_str(&target).map_err(EngineError::Illegal)?;
+                if !targets.contains(&target.to_string()) {
+                    return Err(EngineError::Illegal(format!("bid {}", target.to_str())));
+                }
+                Ok(GamePlayCommand::Bid(BidCommand {
+                    target,
+                    slam: false,
+                }))
+            }
+            (EngineResponse::CallKing { card }, LegalMoves::CallKing { cards }) => {
+                let card = legal_card(&card, cards)?;
+                Ok(GamePlayCommand::CallKing(CallKingCommand { card }))
+            }
+            (EngineResponse::MakeDog { cards }, LegalMoves::MakeDog { cards: pool, size }) => {
+                let dog = check_dog(&cards, pool, *size)?;
+                Ok(GamePlayCommand::MakeDog(MakeDogCommand {
+                    cards: dog,
+                    slam: false,
+                }))
+            }
+            (EngineResponse::Play { card }, LegalMoves::Play { cards }) => {
+                let card = legal_card(&card, cards)?;
+                Ok(GamePlayCommand::Play(PlayCommand { card }))
+            }
+            (response, _) => Err(EngineError::Unexpected(format!("{:?}", response))),
+        }
+    }
+}
+
+/// Short description of a command, used in decision traces
+pub fn describe_command(command: &GamePlayCommand) -> String {
+    match command {
+        GamePlayCommand::Bid(cmd) => cmd.target.to_string(),
+        GamePlayCommand::Pass => String::from("pass"),
+        GamePlayCommand::CallKing(cmd) => cmd.card.to_string(),
+        GamePlayCommand::MakeDog(cmd) => cmd.cards.to_string(),
+        GamePlayCommand::Play(cmd) => cmd.card.to_string(),
+        GamePlayCommand::Announce(cmd) => format!("{:?}", cmd.announce),
+        GamePlayCommand::ReplaceAbsent(cmd) => format!("replace {:?}", cmd.pos),
+        GamePlayCommand::TakeSeat(_) => String::from("take seat"),
+        GamePlayCommand::SetBotDelay(cmd) => format!("delay {}", cmd.delay),
+        GamePlayCommand::SetHands(_) => String::from("set hands"),
+        GamePlayCommand::Entropy(_) => String::from("entropy"),
+        GamePlayCommand::Resync => String::from("resync"),
+        GamePlayCommand::Replay(cmd) => format!("replay {}", cmd.since),
+        GamePlayCommand::Hello(cmd) => format!("hello {}", cmd.version),
+        GamePlayCommand::Timeout => String::from("timeout"),
+    }
+}
+
+fn card_strings(cards: &[Card]) -> Vec<String> {
+    cards.iter().map(|c| c.to_string()).collect()
+}
+
+fn parse_card(card: &str) -> Result<Card, EngineError> {
+    Card::from_str(card).map_err(EngineError::Illegal)
+}
+
+fn legal_card(card: &str, legal: &[String]) -> Result<Card, EngineError> {
+    let card = parse_card(card)?;
+    if !legal.contains(&card.to_string()) {
+        return Err(EngineError::Illegal(card.to_string()));
+    }
+    Ok(card)
+}
+
+/// Same rules as `DealState::make_dog`
+fn check_dog(cards: &[String], pool: &[String], size: usize) -> Result<Hand, EngineError> {
+    if cards.len() != size {
+        return Err(EngineError::Illegal(format!(
+            "dog of {} cards instead of {}",
+            cards.len(),
+            size
+        )));
+    }
+    let pool: Vec<Card> = pool
+        .iter()
+        .map(|c| parse_card(c))
+        .collect::<Result<_, _>>()?;
+    let cards: Vec<Card> = cards
+        .iter()
+        .map(|c| parse_card(c))
+        .collect::<Result<_, _>>()?;
+    // trumps are allowed only when all the other cards (but kings) are in the dog
+    let trump_allowed = !pool
+        .iter()
+        .any(|c| c.rank() != Rank::RankK && c.suit() != Suit::Trump && !cards.contains(c));
+    let mut dog = Hand::new();
+    for card in cards {
+        if dog.has(card)
+            || !pool.contains(&card)
+            || card.is_oudler()
+            || card.rank() == Rank::RankK
+            || (card.suit() == Suit::Trump && !trump_allowed)
+        {
+            return Err(EngineError::Illegal(format!("{} in dog", card.to_string())));
+        }
+        dog.add(card);
+    }
+    Ok(dog)
+}
+
+#[derive(Debug)]
+pub enum EngineError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// The engine did not answer in time
+    Timeout,
+    /// The engine closed its output
+    Closed,
+    /// Answer which does not match the request
+    Unexpected(String),
+    /// Move not allowed
+    Illegal(String),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Io(e) => write!(f, "engine io error: {}", e),
+            EngineError::Json(e) => write!(f, "invalid engine message: {}", e),
+            EngineError::Timeout => write!(f, "engine timeout"),
+            EngineError::Closed => write!(f, "engine closed"),
+            EngineError::Unexpected(answer) => write!(f, "unexpected engine answer: {}", answer),
+            EngineError::Illegal(play) => write!(f, "illegal engine move: {}", play),
+        }
+    }
+}
+
+impl From<io::Error> for EngineError {
+    fn from(error: io::Error) -> Self {
+        EngineError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for EngineError {
+    fn from(error: serde_json::Error) -> Self {
+        EngineError::Json(error)
+    }
+}
+
+/// A running engine process
+pub struct Engine {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    timeout: Duration,
+}
+
+impl Engine {
+    /// Starts the engine and waits for its `ready` answer
+    pub fn spawn(command_line: &str, timeout: Duration) -> Result<Self, EngineError> {
+        let mut args = command_line.split_whitespace();
+        let program = args.next().ok_or_else(|| {
+            EngineError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "empty engine command",
+            ))
+        })?;
+        let mut child = ProcessCommand::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        // stdin and stdout are piped above
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // Read the engine output in a thread so that we can wait for an answer with a timeout
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let sent = line.map(|line| sender.send(line).is_ok());
+                if sent.ok() != Some(true) {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Engine {
+            name: program.to_string(),
+            child,
+            stdin,
+            lines,
+            timeout,
+        };
+        match engine.request(&EngineRequest::Hello {
+            protocol_version: PROTOCOL_VERSION,
+        })? {
+            EngineResponse::Ready { name } => {
+                engine.name = name;
+                Ok(engine)
+            }
+            other => Err(EngineError::Unexpected(format!("{:?}", other))),
+        }
+    }
+
+    /// Starts the engine configured by the `WEBTAROT_BOT_ENGINE` environment variable, if any
+    pub fn from_env() -> Option<Self> {
+        let command_line = env::var(ENGINE_ENV).ok()?;
+        Engine::spawn(&command_line, DEFAULT_TIMEOUT)
+            .map_err(|e| error!("Can't start engine {}: {}", command_line, e))
+            .ok()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn decide(
+        &mut self,
+        view: EngineView,
+        legal: LegalMoves,
+    ) -> Result<GamePlayCommand, EngineError> {
+        let request = EngineRequest::Decide {
+            view,
+            legal: legal.clone(),
+        };
+        self.request(&request)?.into_command(&legal)
+    }
+
+    fn send(&mut self, request: &EngineRequest) -> Result<(), EngineError> {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        self.stdin.write_all(line.as_bytes())?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn request(&mut self, request: &EngineRequest) -> Result<EngineResponse, EngineError> {
+        // Discard late answers to previous requests which timed out
+        while let Ok(late) = self.lines.try_recv() {
+            warn!("engine {}: ignoring late answer {}", self.name, late);
+        }
+        self.send(request)?;
+        let answer = match self.lines.recv_timeout(self.timeout) {
+            Ok(answer) => answer,
+            Err(RecvTimeoutError::Timeout) => return Err(EngineError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => return Err(EngineError::Closed),
+        };
+        debug!("engine {}: {}", self.name, answer);
+        Ok(serde_json::from_str(&answer)?)
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send(&EngineRequest::Quit);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn test_engine_illegal_moves() {
+    let legal = LegalMoves::Play {
+        cards: vec![String::from("K♥"), String::from("2♥")],
+    };
+    let play = |card: &str| {
+        EngineResponse::Play {
+            card: String::from(card),
+        }
+        .into_command(&legal)
+    };
+    assert!(play("KH").is_ok());
+    assert!(play("3♥").is_err());
+    assert!(play("not a card").is_err());
+    assert!(EngineResponse::Pass.into_command(&legal).is_err());
+
+    let mut hand = Hand::new();
+    for card in &["K♥", "2♥", "3♥", "5T"] {
+        hand.add(Card::from_str(card).unwrap());
+    }
+    let mut dog = Hand::new();
+    dog.add(Card::from_str("4♥").unwrap());
+    dog.add(Card::from_str("1T").unwrap());
+    let legal = LegalMoves::make_dog(hand, dog);
+    let make_dog = |cards: &[&str]| {
+        EngineResponse::MakeDog {
+            cards: cards.iter().map(|c| c.to_string()).collect(),
+        }
+        .into_command(&legal)
+    };
+    assert!(make_dog(&["2♥", "4♥"]).is_ok());
+    assert!(make_dog(&["2♥"]).is_err()); // wrong size
+    assert!(make_dog(&["2♥", "2♥"]).is_err()); // same card twice
+    assert!(make_dog(&["2♥", "K♥"]).is_err()); // king
+    assert!(make_dog(&["2♥", "1T"]).is_err()); // oudler
+    assert!(make_dog(&["2♥", "5T"]).is_err()); // trump while there are other cards
+}
+
+#[test]
+fn test_engine_timeout() {
+    let spawned = Engine::spawn("sleep 5", Duration::from_millis(100));
+    assert!(matches!(spawned, Err(EngineError::Timeout)));
+
+    // `cat` echoes the hello request, which is not a valid answer
+    let spawned = Engine::spawn("cat", Duration::from_millis(1000));
+    assert!(matches!(spawned, Err(EngineError::Json(_))));
+}
@@ -0,0 +1,294 @@
+//! Headless executor : plays games between bots without a server, to simulate games or
+//! generate training data.
+//!
+//! The game state is the one used by the server (`TarotGameState`), bots receive the same
+//! messages as when they are connected to a server. Hands are dealt and bots are seeded from
+//! the game seed, and bots don't wait, so that a game can be replayed identically.
+
+use std::sync::{Arc, Mutex};
+use std::time;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use uuid::Uuid;
+
+use tarotgame::{cards::Hand, deal::DealResult, deal_seeded_hands};
+use webgame_protocol::{GameEventsListener, GameManager, GameState, PlayerInfo};
+use webtarot_protocol::{
+    check_protocol_version, Command, GamePlayCommand, Message, PlayEvent, TarotGameManager,
+    TarotGameState, TarotVariant, VariantSettings,
+};
+
+use crate::player::{InOut, InOutError, ManualClock, Player};
+use crate::recorder::Recorder;
+
+/// Commands sent by a bot, applied by the executor
+struct QueueInOut {
+    commands: Arc<Mutex<Vec<Command>>>,
+}
+
+impl InOut for QueueInOut {
+    fn read(&mut self) -> Result<Message, InOutError> {
+        unreachable!("messages are pushed to the player by the executor")
+    }
+
+    fn send(&mut self, command: &Command) -> Result<(), InOutError> {
+        self.commands.lock().unwrap().push(command.clone());
+        Ok(())
+    }
+
+    fn close(&mut self) {}
+}
+
+struct EventsCollector {
+    events: Vec<(PlayEvent, TarotGameState)>,
+}
+
+impl PartialEq for EventsCollector {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl GameEventsListener<(PlayEvent, TarotGameState)> for EventsCollector {
+    fn notify(&mut self, event: &(PlayEvent, TarotGameState)) {
+        self.events.push(event.clone());
+    }
+}
+
+struct Bot {
+    id: Uuid,
+    player: Player,
+    commands: Arc<Mutex<Vec<Command>>>,
+}
+
+pub struct Executor {
+    state: TarotGameState,
+    bots: Vec<Bot>,
+    /// Gives the seed of each deal
+    rng: StdRng,
+    /// Deals started, including the ones where everybody passed
+    deal: u32,
+    dealt_hands: Vec<Hand>,
+    /// Scores of the finished deals
+    scores: Vec<Vec<f32>>,
+}
+
+impl Executor {
+    pub fn new(nb_players: u8, seed: u64) -> Self {
+        let mut state = TarotGameState::default();
+        state.set_variant(TarotVariant {
+            parameters: VariantSettings::new(nb_players),
+        });
+        let bots = (0..nb_players)
+            .map(|n| {
+                let info = PlayerInfo {
+                    id: Uuid::from_u128(n as u128 + 1),
+                    nickname: format!("bot{}", n + 1),
+                };
+                state.add_player(info.clone());
+                let commands = Arc::new(Mutex::new(vec![]));
+                let in_out = Box::new(QueueInOut {
+                    commands: commands.clone(),
+                });
+                let mut player = Player::new(
+                    in_out,
+                    String::from("headless"),
+                    info.nickname.clone(),
+                    time::Duration::from_millis(1000),
+                );
+                player.set_seed(seed.wrapping_add(n as u64));
+                player.set_clock(Box::new(ManualClock::default()));
+                player.handle_server_message(Message::Authenticated(info.clone()));
+                // no need to join
+                commands.lock().unwrap().clear();
+                Bot {
+                    id: info.id,
+                    player,
+                    commands,
+                }
+            })
+            .collect();
+
+        Executor {
+            state,
+            bots,
+            rng: StdRng::seed_from_u64(seed),
+            deal: 0,
+            dealt_hands: vec![],
+            scores: vec![],
+        }
+    }
+
+    /// Bot at position `pos`, to set its recorder or engine before the game starts
+    pub fn player_mut(&mut self, pos: usize) -> &mut Player {
+        &mut self.bots[pos].player
+    }
+
+    /// Plays until `deals` deals are finished, returns the scores of each deal
+    pub fn run(&mut self, deals: usize) -> Result<Vec<Vec<f32>>, String> {
+        for bot in self.bots.iter() {
+            self.state.set_player_ready(bot.id);
+        }
+        self.start_deal();
+        self.broadcast_state(&self.state.clone());
+        while self.scores.len() < deals {
+            let commands: Vec<(Uuid, GamePlayCommand)> = self
+                .bots
+                .iter()
+                .flat_map(|bot| {
+                    let commands: Vec<Command> = bot.commands.lock().unwrap().drain(..).collect();
+                    commands
+                        .into_iter()
+                        .filter_map(move |command| match command {
+                            Command::GamePlay(command) => Some((bot.id, command)),
+                            _ => None,
+                        })
+                })
+                .collect();
+            if commands.is_empty() {
+                return Err(format!("no bot plays, turn: {}", self.state.get_turn()));
+            }
+            for (id, command) in commands {
+                self.apply(id, command)?;
+            }
+            let new_deal = self
+                .state
+                .get_deal()
+                .deal_auction()
+                .map_or(false, |auction| *auction.hands() != self.dealt_hands);
+            if new_deal {
+                self.deal += 1;
+                self.start_deal();
+            }
+            self.broadcast_state(&self.state.clone());
+        }
+        Ok(self.scores.clone())
+    }
+
+    fn start_deal(&mut self) {
+        let mut seed = [0u8; 32];
+        self.rng.fill(&mut seed);
+        let (hands, dog) = deal_seeded_hands(seed, self.bots.len());
+        if let Some(auction) = self.state.get_deal_mut().deal_auction_mut() {
+            auction.set_hands(hands.clone(), dog);
+        }
+        self.dealt_hands = hands;
+        let deal = self.deal;
+        for bot in self.bots.iter_mut() {
+            if let Some(recorder) = bot.player.recorder_mut() {
+                recorder.start_deal(deal);
+            }
+        }
+    }
+
+    fn apply(&mut self, id: Uuid, command: GamePlayCommand) -> Result<(), String> {
+        let result = match command {
+            GamePlayCommand::Bid(cmd) => self.state.set_bid(id, cmd.target, cmd.slam),
+            GamePlayCommand::Pass => self.state.set_pass(id).map(|_| ()),
+            GamePlayCommand::CallKing(cmd) => {
+                self.state.call_king(id, cmd.card);
+                Ok(())
+            }
+            GamePlayCommand::MakeDog(cmd) => self.state.make_dog(id, cmd.cards, cmd.slam),
+            GamePlayCommand::Announce(cmd) => self.state.set_announce(id, cmd.announce),
+            GamePlayCommand::ReplaceAbsent(cmd) => {
+                self.state.replace_absent(id, cmd.pos).map(|_| ())
+            }
+            GamePlayCommand::TakeSeat(cmd) => self.state.take_seat(id, cmd.token),
+            GamePlayCommand::SetBotDelay(cmd) => self.state.set_bot_delay(id, cmd.delay),
+            GamePlayCommand::SetHands(cmd) => self.state.set_hands(id, cmd.hands, cmd.dog),
+            GamePlayCommand::Entropy(cmd) => self.state.add_entropy(id, cmd.entropy),
+            GamePlayCommand::Resync => {
+                let update = self.state.resync(id);
+                if let Some(bot) = self.bots.iter_mut().find(|bot| bot.id == id) {
+                    bot.player
+                        .handle_server_message(Message::GameStateSnapshot(update));
+                }
+                Ok(())
+            }
+            GamePlayCommand::Hello(cmd) => check_protocol_version(cmd.version),
+            // the bots play without time controls
+            GamePlayCommand::Timeout => Ok(()),
+            GamePlayCommand::Replay(cmd) => {
+                let events = self.state.events_since(cmd.since);
+                if let Some(bot) = self.bots.iter_mut().find(|bot| bot.id == id) {
+                    for event in events {
+                        bot.player.handle_server_message(Message::PlayEvent(event));
+                    }
+                }
+                Ok(())
+            }
+            GamePlayCommand::Play(cmd) => {
+                let mut collector = EventsCollector { events: vec![] };
+                let mut manager = TarotGameManager::new(&mut self.state);
+                manager.register_listener(&mut collector);
+                let result = manager.set_play(id, cmd.card);
+                drop(manager);
+                for (event, state) in collector.events {
+                    if let PlayEvent::EndDeal(DealResult::GameOver { scores, .. }) = &event {
+                        self.scores.push(scores.clone());
+                    }
+                    self.broadcast_state(&state);
+                    let event = self.state.log_event(event);
+                    self.broadcast(Message::PlayEvent(event));
+                }
+                result
+            }
+        };
+        result.map_err(|e| e.message().to_string())
+    }
+
+    fn broadcast_state(&mut self, state: &TarotGameState) {
+        for bot in self.bots.iter_mut() {
+            let update = self.state.next_update(bot.id, state.view(bot.id));
+            bot.player
+                .handle_server_message(Message::GameStateSnapshot(update));
+        }
+    }
+
+    fn broadcast(&mut self, message: Message) {
+        for bot in self.bots.iter_mut() {
+            bot.player.handle_server_message(message.clone());
+        }
+    }
+}
+
+/// Plays `deals` deals between five bots and records their decisions in the file `path`
+pub fn record(seed: u64, deals: usize, path: &str) {
+    let recorder = match Recorder::create(path, Some(seed)) {
+        Ok(recorder) => recorder,
+        Err(e) => {
+            println!("Can't open {}: {}", path, e);
+            return;
+        }
+    };
+    let mut executor = Executor::new(5, seed);
+    for pos in 0..5 {
+        executor.player_mut(pos).set_recorder(recorder.share());
+    }
+    if let Err(e) = executor.run(deals) {
+        println!("Game stopped: {}", e);
+    }
+}
+
+#[test]
+fn test_executor_is_reproducible() {
+    // the deals and the choices of the bots only depend on the seed
+    let scores = Executor::new(5, 42).run(3).unwrap();
+    assert_eq!(scores.len(), 3);
+    assert_eq!(Executor::new(5, 42).run(3).unwrap(), scores);
+}
+
+#[test]
+fn test_seeded_game() {
+    // Update the expected scores only when the bots strategy changes on purpose
+    let scores = Executor::new(5, 2021).run(2).unwrap();
+    assert_eq!(
+        scores,
+        vec![
+            vec![30.5, -30.5, 30.5, -61.0, 30.5],
+            vec![-42.0, 42.0, -42.0, -42.0, 84.0]
+        ]
+    );
+}
@@ -1,8 +1,12 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Read;
 use std::path::Path;
+use std::process;
+use std::str::FromStr;
 
-use tarotgame::{cards::Hand, deal_seeded_hands};
+use tarotgame::cards::Hand;
+use tarotgame::deal_seeded_hands;
+use tarotgame::generator::{next_seed, DealConstraints};
 use webtarot_protocol::TarotGameState;
 
 pub fn read_json(json_path: &str) {
@@ -17,42 +21,77 @@ pub fn read_json(json_path: &str) {
     println!("{}", game);
 }
 
-pub fn find_decks() {
-    let mut found = false;
+/// Seeds tried by `find_decks` before giving up
+const SEED_TRIES: usize = 1_000_000;
+/// Deals tried by `make_deals` before giving up
+const DEAL_TRIES: usize = 10_000;
 
-    let mut seed = [0; 32];
-
-    let mut deal = deal_seeded_hands(seed, 5);
-    let mut optseed = Some(seed);
-    while !found && optseed.is_some() {
-        seed = optseed.unwrap();
-        deal = deal_seeded_hands(seed, 5);
-        found = check_deal(&deal);
-        optseed = incr_seed(seed);
+/// Reads deal constraints from a JSON file, or from an expression
+fn read_constraints(constraints: &str) -> DealConstraints {
+    let parsed = if Path::new(constraints).is_file() {
+        let json_str = fs::read_to_string(constraints).expect("Could not read the constraints");
+        serde_json::from_str(&json_str).map_err(|e| e.to_string())
+    } else {
+        DealConstraints::from_str(constraints)
+    };
+    match parsed {
+        Ok(constraints) => constraints,
+        Err(e) => {
+            eprintln!("Invalid constraints: {}", e);
+            process::exit(1);
+        }
     }
+}
 
-    if found {
-        for hand in deal.0 {
-            println!("{}", hand.to_string());
-        }
+fn checked_constraints(constraints: &str, players: usize) -> DealConstraints {
+    let constraints = read_constraints(constraints);
+    if let Err(e) = constraints.check(players) {
+        eprintln!("Invalid constraints: {}", e);
+        process::exit(1);
+    }
+    constraints
+}
 
-        println!("{:?}", seed);
+fn print_deal(hands: &[Hand], dog: Hand) {
+    for (n, hand) in hands.iter().enumerate() {
+        println!("{}: {}", n, hand.to_string());
     }
+    println!("dog: {}", dog.to_string());
 }
 
-fn check_deal(deal: &(Vec<Hand>, Hand)) -> bool {
-    // Une main avec au moins 10 atouts
-    deal.0.iter().find(|h| h.trumps_count() > 9).is_some()
+/// Prints `count` seeds giving deals which match the constraints, to be used with the
+/// `SetSeed` debug operation
+pub fn find_decks(constraints: &str, players: usize, count: usize, from: [u8; 32]) {
+    let constraints = checked_constraints(constraints, players);
+    let mut from = Some(from);
+    for _ in 0..count {
+        let seed = match from.and_then(|from| constraints.find_seed(players, from, SEED_TRIES)) {
+            Some(seed) => seed,
+            None => {
+                eprintln!("No matching seed found");
+                return;
+            }
+        };
+        let (hands, dog) = deal_seeded_hands(seed, players);
+        print_deal(&hands, dog);
+        println!("{:?}\n", seed);
+        from = next_seed(seed);
+    }
 }
 
-// XXX non exhaustif
-fn incr_seed(seed: [u8; 32]) -> Option<[u8; 32]> {
-    let mut result = seed.clone();
-    seed.iter()
-        .enumerate()
-        .find(|(i, n)| **n < 255)
-        .map(|(i, n)| {
-            result[i] = *n + 1;
-            result
-        })
+/// Prints `count` deals which match the constraints, with their JSON to be used with the
+/// `SetHands` debug operation
+pub fn make_deals(constraints: &str, players: usize, count: usize) {
+    let constraints = checked_constraints(constraints, players);
+    for _ in 0..count {
+        let (hands, dog) = match constraints.deal(players, DEAL_TRIES) {
+            Some(deal) => deal,
+            None => {
+                eprintln!("No matching deal found");
+                return;
+            }
+        };
+        print_deal(&hands, dog);
+        println!("{}\n", serde_json::to_string(&(hands, dog)).unwrap());
+    }
 }
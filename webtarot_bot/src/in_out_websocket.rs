@@ -3,53 +3,72 @@ use tungstenite::Message as TMessage;
 use tungstenite::stream::Stream;
 use tungstenite::protocol::WebSocket;
 
-use rayon::prelude::*;
-
 use url::Url;
-use serde_json::Result;
 
 use webtarot_protocol::{Message, Command};
 
-use crate::player::InOut;
+use crate::player::{InOut, InOutError};
+
+type Socket = WebSocket<Stream<std::net::TcpStream, native_tls::TlsStream<std::net::TcpStream>>>;
 
 pub struct TarotWebSocket {
-    socket: WebSocket<Stream<std::net::TcpStream, native_tls::TlsStream<std::net::TcpStream>>>,
+    str_websocket: String,
+    socket: Socket,
 }
 
 impl TarotWebSocket {
-    pub fn new(str_websocket: &str) -> Self {
-        let url_websocket = format!("{}/ws/new_new", str_websocket);
-        let socket = connect(Url::parse(&url_websocket)
-            .unwrap())
-            .expect("Can't connect")
-            .0;
-        TarotWebSocket {
+    pub fn new(str_websocket: &str) -> Result<Self, InOutError> {
+        let socket = open(str_websocket, "new_new")?;
+        Ok(TarotWebSocket {
+            str_websocket: str_websocket.to_string(),
             socket
-        }
+        })
     }
 
 }
 
+fn open(str_websocket: &str, path: &str) -> Result<Socket, InOutError> {
+    let url_websocket = format!("{}/ws/{}", str_websocket, path);
+    let url = Url::parse(&url_websocket)
+        .map_err(|e| InOutError::Connection(e.to_string()))?;
+    Ok(connect(url)?.0)
+}
+
+impl From<tungstenite::Error> for InOutError {
+    fn from(e: tungstenite::Error) -> Self {
+        match e {
+            tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => InOutError::Closed,
+            e => InOutError::Connection(e.to_string()),
+        }
+    }
+}
+
 impl InOut for TarotWebSocket {
-    fn read(&mut self) -> Message {
-        let msg = self.socket.read_message().expect("Error reading message");
-        let msg = match msg {
-            tungstenite::Message::Text(s) => { s }
-            _ => { panic!() }
-        };
-
-        let message: Message = serde_json::from_str(&msg).expect("Can't parse JSON");
-        message
+    fn read(&mut self) -> Result<Message, InOutError> {
+        loop {
+            match self.socket.read_message()? {
+                TMessage::Text(s) => { return Ok(serde_json::from_str(&s)?) }
+                TMessage::Close(_) => { return Err(InOutError::Closed) }
+                // pongs are queued by tungstenite and sent with the next read or write
+                TMessage::Ping(_) | TMessage::Pong(_) => { }
+                TMessage::Binary(_) => { return Err(InOutError::Connection(String::from("unexpected binary message"))) }
+            }
+        }
     }
 
-    fn send(&mut self, command: &Command) -> Result<()> {
+    fn send(&mut self, command: &Command) -> Result<(), InOutError> {
         let json = serde_json::to_string(command)?;
-        self.socket.write_message(TMessage::Text(json)).unwrap();
+        self.socket.write_message(TMessage::Text(json))?;
         Ok(())
     }
 
     fn close(&mut self){
-        self.socket.close(None);
+        let _ = self.socket.close(None);
+    }
+
+    fn reconnect(&mut self, path: &str) -> Result<(), InOutError> {
+        self.socket = open(&self.str_websocket, path)?;
+        Ok(())
     }
 
 }
@@ -1,3 +1,7 @@
+pub mod engine;
+pub mod executor;
+pub mod recorder;
 pub mod socket_listener;
 mod player;
-mod in_out_websocket;
+mod supervisor;
+mod trace;
@@ -1,11 +1,17 @@
 use clap::{App, Arg};
+use std::time;
 
+mod engine;
+mod executor;
 mod explorer;
 mod in_out_websocket;
 mod player;
 mod player_factory;
+mod recorder;
 mod simulator;
 mod socket_listener;
+mod supervisor;
+mod trace;
 
 pub fn main() {
     let version = format!(
@@ -68,7 +74,67 @@ pub fn main() {
                 .short("n")
                 .long("count")
                 .value_name("COUNT")
-                .help("Number of bots to start")
+                .help("Number of bots to start, or of deals found by find_decks and make_deals")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("engine")
+                .short("e")
+                .long("engine")
+                .value_name("ENGINE")
+                .help("External engine command line")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("engine_timeout")
+                .short("t")
+                .long("engine_timeout")
+                .value_name("MILLISECONDS")
+                .help("Time given to the engine to answer")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("delay")
+                .short("d")
+                .long("delay")
+                .value_name("MILLISECONDS")
+                .help("Time the bots wait before playing, also the setting of the games they create")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("SEED")
+                .help("Seed of the bots, of the game played by the record command, or first seed tried by find_decks")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("deals")
+                .long("deals")
+                .value_name("DEALS")
+                .help("Number of deals played by the record command")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("constraints")
+                .long("constraints")
+                .value_name("CONSTRAINTS")
+                .help("Deal constraints, or JSON file of constraints, of find_decks and make_deals")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("players")
+                .long("players")
+                .value_name("PLAYERS")
+                .help("Number of players of the deals found by find_decks and make_deals")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .help("Training samples file, binary if it ends with .bin")
                 .takes_value(true),
         );
     let matches = app.get_matches();
@@ -83,6 +149,20 @@ pub fn main() {
         .value_of("count")
         .and_then(|str_count| str_count.parse::<usize>().ok())
         .unwrap_or(1);
+    let engine = matches.value_of("engine");
+    let seed = matches
+        .value_of("seed")
+        .and_then(|str_seed| str_seed.parse::<u64>().ok());
+    let engine_timeout = matches
+        .value_of("engine_timeout")
+        .and_then(|str_timeout| str_timeout.parse::<u64>().ok())
+        .map(time::Duration::from_millis)
+        .unwrap_or(engine::DEFAULT_TIMEOUT);
+
+    let delay = matches
+        .value_of("delay")
+        .and_then(|str_delay| str_delay.parse::<u64>().ok())
+        .unwrap_or(webtarot_protocol::DEFAULT_BOT_DELAY as u64);
 
     if let Some(str_socket) = matches.value_of("socket") {
         socket_listener::start(str_socket, str_websocket);
@@ -90,9 +170,38 @@ pub fn main() {
         explorer::read_json(&json);
     } else {
         match str_command {
-            "find_decks" => explorer::find_decks(),
+            "find_decks" | "make_deals" => {
+                let constraints = matches.value_of("constraints").unwrap_or("");
+                let players = matches
+                    .value_of("players")
+                    .and_then(|str_players| str_players.parse::<usize>().ok())
+                    .unwrap_or(5);
+                if str_command == "find_decks" {
+                    let mut from = [0; 32];
+                    from[..8].copy_from_slice(&seed.unwrap_or(0).to_le_bytes());
+                    explorer::find_decks(constraints, players, count, from)
+                } else {
+                    explorer::make_deals(constraints, players, count)
+                }
+            }
             "simulate" => simulator::simulate(4),
-            "play" => player_factory::play(joincode, str_websocket, count),
+            "record" => {
+                let deals = matches
+                    .value_of("deals")
+                    .and_then(|str_deals| str_deals.parse::<usize>().ok())
+                    .unwrap_or(1);
+                let output = matches.value_of("output").unwrap_or("samples.jsonl");
+                executor::record(seed.unwrap_or(0), deals, output)
+            }
+            "play" => player_factory::play(
+                joincode,
+                str_websocket,
+                count,
+                engine,
+                engine_timeout,
+                seed,
+                time::Duration::from_millis(delay),
+            ),
             _ => println!("Nothing to do"),
         }
     }
@@ -1,33 +1,108 @@
-use std::{thread, time};
+use std::{fmt, thread, time};
 // use std::rc::Rc;
 use std::collections::HashMap;
 
+use log::warn;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use rayon::prelude::*;
 
-use serde_json::Result;
 use url::Url;
 use uuid::Uuid;
 
 use tarotgame::{
     bid::Target,
     cards::{Card, Deck, Hand, Rank, Suit},
-    deal::can_play,
+    deal::{can_play, DealResult},
     deal_seeded_hands,
     points::strength,
     pos::PlayerPos,
     trick::Trick,
 };
-use webgame_protocol::{AuthenticateCommand, JoinGameCommand, PlayerInfo};
+use webgame_protocol::{AuthenticateCommand, JoinGameCommand, PlayerInfo, SendTextCommand};
 use webtarot_protocol::{
-    BidCommand, CallKingCommand, Command, GamePlayCommand, GamePlayerState, GameStateSnapshot,
-    MakeDogCommand, Message, PlayCommand, PlayEvent, PlayerAction, PlayerRole, TarotVariant, Turn,
-    VariantSettings,
+    BidCommand, CallKingCommand, Command, ErrorCode, GameEvent, GamePlayCommand, GamePlayerState,
+    GameStateSnapshot, GameView, MakeDogCommand, Message, PlayCommand, PlayEvent, PlayerAction,
+    PlayerRole, ProtocolError, ReplaceAbsentCommand, ReplayCommand, SnapshotTracker,
+    TakeSeatCommand, TarotVariant, Turn, VariantSettings,
 };
 
+use crate::engine::{describe_command, Engine, EngineView, LegalMoves};
+use crate::recorder::{command_actions, legal_actions, Observation, Recorder};
+use crate::trace::{Beliefs, Decision, DecisionTrace, PlayerBeliefs, Tracer};
+
+/// Connection attempts before giving up
+pub const MAX_RECONNECTS: u32 = 6;
+
+/// Illegal moves after which we stop deciding again until the game goes on
+const MAX_REJECTED_MOVES: u32 = 3;
+
+#[derive(Debug)]
+pub enum InOutError {
+    /// The connection has been closed
+    Closed,
+    /// Network or websocket failure
+    Connection(String),
+    /// A message could not be (de)serialized
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for InOutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InOutError::Closed => write!(f, "connection closed"),
+            InOutError::Connection(e) => write!(f, "connection error: {}", e),
+            InOutError::Json(e) => write!(f, "invalid message: {}", e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for InOutError {
+    fn from(e: serde_json::Error) -> Self {
+        InOutError::Json(e)
+    }
+}
+
 pub trait InOut {
-    fn read(&mut self) -> Message;
-    fn send(&mut self, command: &Command) -> Result<()>;
+    fn read(&mut self) -> Result<Message, InOutError>;
+    fn send(&mut self, command: &Command) -> Result<(), InOutError>;
     fn close(&mut self);
+    /// Opens a new connection to the server websocket `path`
+    fn reconnect(&mut self, _path: &str) -> Result<(), InOutError> {
+        Err(InOutError::Closed)
+    }
+}
+
+/// Time source of the bots : tests and headless games use a clock which does not wait
+pub trait Clock: Send {
+    fn sleep(&mut self, duration: time::Duration);
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn sleep(&mut self, duration: time::Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Adds up the waits instead of sleeping
+#[derive(Default)]
+pub struct ManualClock {
+    pub elapsed: time::Duration,
+}
+
+impl Clock for ManualClock {
+    fn sleep(&mut self, duration: time::Duration) {
+        self.elapsed += duration;
+    }
+}
+
+/// Delay before the nth connection attempt : 1s, 2s, 4s... up to 1 minute
+pub fn backoff_delay(attempt: u32) -> time::Duration {
+    time::Duration::from_secs(2u64.pow(attempt.saturating_sub(1).min(6)))
+        .min(time::Duration::from_secs(60))
 }
 
 #[derive(Clone)]
@@ -142,6 +217,52 @@ impl DealStats {
     fn set_suit_played(&mut self, suit: &Suit) {
         self.suit_played[suit.to_n()] = true;
     }
+
+    fn beliefs(&self) -> Beliefs {
+        let suits = [
+            Suit::Club,
+            Suit::Diamond,
+            Suit::Spade,
+            Suit::Heart,
+            Suit::Trump,
+        ];
+        let players = self
+            .players
+            .iter()
+            .enumerate()
+            .map(|(pos, player)| PlayerBeliefs {
+                pos,
+                is_taker: player.is_taker,
+                in_taker_team: player.in_taker_team,
+                void_suits: suits
+                    .iter()
+                    .filter(|suit| player.suits_available.get(suit) == Some(&Some(false)))
+                    .map(|suit| suit.to_string())
+                    .collect(),
+            })
+            .collect();
+        // suit_left is empty until the deal state is initialized
+        let cut_suits = if self.suit_left.is_empty() {
+            vec![]
+        } else {
+            suits[..4]
+                .iter()
+                .filter(|suit| self.suit_is_cut(**suit))
+                .map(|suit| suit.to_string())
+                .collect()
+        };
+        Beliefs {
+            teams_known: self.teams_known,
+            teams_known_by_all: self.teams_known_by_all,
+            players,
+            cut_suits,
+            trumps_left: self
+                .suit_left
+                .get(&Suit::Trump)
+                .map(|trumps| trumps.size())
+                .unwrap_or(0),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -182,12 +303,27 @@ impl PlayerStats {
 
 pub struct Player {
     delay: time::Duration,
-    in_out: Box<dyn InOut>,
+    clock: Box<dyn Clock>,
+    rng: StdRng,
+    in_out: Box<dyn InOut + Send>,
     join_code: String,
+    game_id: Option<Uuid>,
     game_state: GameStateSnapshot,
+    tracker: SnapshotTracker,
+    /// Number of the next event we expect, to ask for the missed ones after a reconnection
+    next_event: Option<u64>,
     player_info: PlayerInfo,
     stats: DealStats,
     // stats: Rc<DealStats>,
+    tracer: Tracer,
+    engine: Option<Engine>,
+    recorder: Option<Recorder>,
+    /// Token of the seat the server started us for
+    seat_token: Option<Uuid>,
+    /// We asked for the seat offered to us and wait for it
+    taking_seat: bool,
+    /// Moves refused by the server since the last event
+    rejected_moves: u32,
 }
 
 impl Drop for Player {
@@ -198,41 +334,149 @@ impl Drop for Player {
 
 impl Player {
     pub fn new(
-        in_out: Box<dyn InOut>,
+        in_out: Box<dyn InOut + Send>,
         join_code: String,
         nickname: String,
         delay: time::Duration,
     ) -> Self {
         Player {
             delay,
+            clock: Box::new(SystemClock),
+            rng: StdRng::from_entropy(),
             in_out,
             join_code,
+            game_id: None,
             game_state: GameStateSnapshot::default(),
+            tracker: SnapshotTracker::new(),
+            next_event: None,
             player_info: PlayerInfo {
                 id: Uuid::default(),
                 nickname,
             },
             stats: DealStats::new(),
             // stats: Rc::new(DealStats::new()),
+            tracer: Tracer::from_env(),
+            engine: None,
+            recorder: Recorder::from_env(),
+            seat_token: None,
+            taking_seat: false,
+            rejected_moves: 0,
         }
     }
 
+    /// Makes the bot behaviour reproducible
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Delegates decisions to an external engine
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = Some(engine);
+    }
+
+    /// Takes the seat of an absent player, offered by the server with `token`
+    pub fn set_seat_token(&mut self, token: Uuid) {
+        self.seat_token = Some(token);
+    }
+
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = tracer;
+    }
+
+    /// Records the decisions as training samples
+    pub fn set_recorder(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    pub fn recorder_mut(&mut self) -> Option<&mut Recorder> {
+        self.recorder.as_mut()
+    }
+
     pub fn set_test_state_from_snapshot(&mut self, game_state: GameStateSnapshot) {
         self.game_state = game_state;
     }
+    pub fn nickname(&self) -> &str {
+        &self.player_info.nickname
+    }
+
+    /// Websocket path on the server : a new session until a game is joined, then the game
+    /// session so that we can resume it after a connection loss
+    pub fn websocket_path(&self) -> String {
+        match self.game_id {
+            Some(game_id) => format!("{}_{}", game_id, self.player_info.id),
+            None => String::from("new_new"),
+        }
+    }
+
+    pub fn has_joined(&self) -> bool {
+        self.game_id.is_some()
+    }
+
+    pub fn start(&mut self) {
+        self.send(&Command::Authenticate(AuthenticateCommand {
+            nickname: self.player_info.nickname.clone(),
+        }));
+    }
+
     pub fn play(&mut self) {
-        self.in_out
-            .send(&Command::Authenticate(AuthenticateCommand {
-                nickname: self.player_info.nickname.clone(),
-            }));
+        self.start();
         loop {
-            let message = self.in_out.read();
-            if self.handle_server_message(message) {
-                break;
+            match self.in_out.read() {
+                Ok(message) => {
+                    if self.handle_server_message(message) {
+                        break;
+                    }
+                }
+                Err(InOutError::Json(e)) => {
+                    warn!("{}: ignoring server message: {}", self.nickname(), e)
+                }
+                Err(e) => {
+                    warn!("{}: {}", self.nickname(), e);
+                    if !self.reconnect() {
+                        break;
+                    }
+                }
             }
         }
     }
 
+    /// Reconnects to the server, resuming the game session if a game has been joined
+    fn reconnect(&mut self) -> bool {
+        for attempt in 1..=MAX_RECONNECTS {
+            self.clock.sleep(backoff_delay(attempt));
+            match self.in_out.reconnect(&self.websocket_path()) {
+                Ok(()) => {
+                    if !self.has_joined() {
+                        self.start();
+                    } else if let Some(since) = self.next_event {
+                        self.send(&Command::GamePlay(GamePlayCommand::Replay(ReplayCommand {
+                            since,
+                        })));
+                    }
+                    return true;
+                }
+                Err(e) => warn!("{}: reconnection failed: {}", self.nickname(), e),
+            }
+        }
+        false
+    }
+
+    /// Waits the bot delay, so that humans can follow the game
+    fn wait(&mut self) {
+        self.clock.sleep(self.delay);
+    }
+
+    /// A failed command is lost : the server sends the game state again when we reconnect
+    fn send(&mut self, command: &Command) {
+        if let Err(e) = self.in_out.send(command) {
+            warn!("{}: can't send command: {}", self.nickname(), e);
+        }
+    }
+
     fn update_stats(&mut self) {
         let deal = &self.game_state.deal;
         //My stats
@@ -375,44 +619,107 @@ impl Player {
     }
 
     // Returns a bool : do we exit ?
-    fn handle_server_message(&mut self, msg: Message) -> bool {
+    pub fn handle_server_message(&mut self, msg: Message) -> bool {
         match msg {
             Message::Authenticated(player_info) => {
                 self.player_info = player_info;
                 // println!("Authenticated with id {}", self.player_info.id);
-                if self.join_code != "" {
-                    self.in_out.send(&Command::JoinGame(JoinGameCommand {
+                if self.has_joined() {
+                    // resumed session
+                } else if self.join_code != "" {
+                    self.send(&Command::JoinGame(JoinGameCommand {
                         join_code: self.join_code.clone(),
                     }));
                 } else {
                     //Create game
                     // println!("creating game");
-                    let variant = TarotVariant {
-                        parameters: VariantSettings { nb_players: 5 },
-                    };
-                    self.in_out.send(&Command::NewGame(variant));
+                    // the bots we invite wait as long as we do
+                    let mut parameters = VariantSettings::new(5);
+                    parameters.bot_delay = self.delay.as_millis() as u32;
+                    let variant = TarotVariant { parameters };
+                    self.send(&Command::NewGame(variant));
                 }
             }
             Message::GameJoined(game_info) => {
                 // println!("Game joined: {:?}", game_info);
+                self.game_id = Some(game_info.game_id);
                 if self.join_code == "" {
                     // println!("need to invite other bots");
                     self.join_code = game_info.join_code.clone();
-                    self.in_out.send(&Command::InviteBot);
-                    self.in_out.send(&Command::InviteBot);
-                    self.in_out.send(&Command::InviteBot);
-                    self.in_out.send(&Command::InviteBot);
+                    self.send(&Command::InviteBot);
+                    self.send(&Command::InviteBot);
+                    self.send(&Command::InviteBot);
+                    self.send(&Command::InviteBot);
                 }
-                self.in_out.send(&Command::MarkReady);
+                self.send(&Command::MarkReady);
             }
-            Message::GameStateSnapshot(game_state) => {
+            Message::GameStateSnapshot(update) => {
+                let game_state = match self.tracker.apply_view(update) {
+                    Some(GameView::Player(game_state)) => game_state,
+                    Some(GameView::Spectator(view)) => {
+                        if self.taking_seat {
+                            return false;
+                        }
+                        // We take the seat we were started for, or leave : a bot has nothing to watch
+                        let offered = view.absent.iter().any(|absent| absent.invited_by.is_some());
+                        if let Some(token) = self.seat_token.filter(|_| offered) {
+                            self.taking_seat = true;
+                            self.send(&Command::GamePlay(GamePlayCommand::TakeSeat(
+                                TakeSeatCommand { token },
+                            )));
+                            return false;
+                        }
+                        self.send(&Command::LeaveGame);
+                        return true;
+                    }
+                    None => {
+                        // We missed some changes
+                        self.send(&Command::GamePlay(GamePlayCommand::Resync));
+                        return false;
+                    }
+                };
+                self.taking_seat = false;
+                if game_state.turn == Turn::Endgame {
+                    // The game is over, we leave
+                    self.send(&Command::LeaveGame);
+                    return true;
+                }
+                if let Some(delay) = game_state.bot_delay {
+                    // The table host can change it during the game
+                    self.delay = time::Duration::from_millis(delay as u64);
+                }
+                if !game_state
+                    .players
+                    .iter()
+                    .any(|state| state.player.id == self.player_info.id)
+                {
+                    // The player we replaced is back
+                    self.send(&Command::LeaveGame);
+                    return true;
+                }
+                self.handle_absent_players(&game_state);
                 if game_state != self.game_state {
                     self.game_state = game_state;
                     self.handle_new_state();
                 }
             }
-            Message::PlayEvent(play_event) => {
+            Message::PlayEvent(GameEvent {
+                seq,
+                event: play_event,
+            }) => {
+                if self.next_event.map_or(false, |next| seq < next) {
+                    // already received before a reconnection
+                    return false;
+                }
+                self.next_event = Some(seq + 1);
+                self.rejected_moves = 0;
                 // println!("play event for {}: {:?}", self.player_info.nickname, play_event);
+                if let PlayEvent::EndDeal(DealResult::GameOver { scores, .. }) = play_event {
+                    let pos = self.my_state().pos.to_n();
+                    if let Some(recorder) = self.recorder.as_mut() {
+                        recorder.end_deal(scores[pos]);
+                    }
+                }
             }
             Message::Chat(_) => {}
             Message::Pong => {
@@ -422,14 +729,30 @@ impl Player {
                 server_status.games.iter().next().map(|g| {
                     // println!("Found a game with code {}", g.game.join_code);
                     self.join_code = g.game.join_code.clone();
-                    self.in_out.send(&Command::JoinGame(JoinGameCommand {
+                    self.send(&Command::JoinGame(JoinGameCommand {
                         join_code: self.join_code.clone(),
                     }));
                 });
             }
-            Message::PlayerDisconnected(_) => {
-                // println!("A player disconnected, I disconnect too");
-                return true; //We exit
+            Message::GameLeft => {
+                return true;
+            }
+            Message::Error(error) => {
+                let error = ProtocolError::from(&error);
+                if matches!(error.code(), Some(code) if code.is_illegal_move())
+                    && self.rejected_moves < MAX_REJECTED_MOVES
+                {
+                    // Our view of the game was wrong : forget it and decide again on the
+                    // whole state
+                    self.rejected_moves += 1;
+                    self.game_state = GameStateSnapshot::default();
+                    self.send(&Command::GamePlay(GamePlayCommand::Resync));
+                } else if error.code() == Some(ErrorCode::IncompatibleProtocol)
+                    || error.code() == Some(ErrorCode::SeatNotOffered)
+                {
+                    self.send(&Command::LeaveGame);
+                    return true;
+                }
             }
             _ => {
                 // println!("Unmanaged server message for {}: {:?}", self.player_info.nickname, msg);
@@ -438,9 +761,118 @@ impl Player {
         false // we don't exit
     }
 
+    /// Votes again for the players who left, their time to come back may be over
+    pub fn check_absent_players(&mut self) {
+        let game_state = self.game_state.clone();
+        self.handle_absent_players(&game_state);
+    }
+
+    /// Votes for a bot to replace the players who left once they had the time to come back.
+    /// Bots leave the decision to the humans when the game settings don't give this time.
+    fn handle_absent_players(&mut self, game_state: &GameStateSnapshot) {
+        let my_id = self.player_info.id;
+        for absent in game_state.absent.iter() {
+            if absent.substitute.is_some() {
+                continue;
+            }
+            if absent.invited_by.is_none()
+                && !absent.votes.contains(&my_id)
+                && game_state.replace_absent_after > 0
+                && absent.grace_expired(game_state.replace_absent_after)
+            {
+                self.send(&Command::GamePlay(GamePlayCommand::ReplaceAbsent(
+                    ReplaceAbsentCommand { pos: absent.pos },
+                )));
+            }
+        }
+    }
+
+    fn trace(
+        &mut self,
+        decision: Decision,
+        rule: &'static str,
+        candidates: Vec<String>,
+        choice: String,
+        detail: Option<String>,
+    ) {
+        let trace = DecisionTrace {
+            player: self.player_info.nickname.clone(),
+            decision,
+            trick_count: self.game_state.deal.trick_count,
+            rule,
+            candidates,
+            choice,
+            detail,
+            beliefs: self.stats.beliefs(),
+        };
+        if let Some(text) = self.tracer.record(&trace) {
+            self.send(&Command::SendText(SendTextCommand { text }));
+        }
+    }
+
+    /// Sends the decision to the server, recording it as a training sample if enabled
+    fn decide(&mut self, decision: Decision, legal: &LegalMoves, command: GamePlayCommand) {
+        if self.recorder.is_some() {
+            let pos = self.my_state().pos.to_n();
+            let played: Vec<Hand> = self.stats.players.iter().map(|p| p.played).collect();
+            let observation = Observation::new(&self.game_state, pos, &played);
+            if let Some(recorder) = self.recorder.as_mut() {
+                recorder.record(
+                    decision,
+                    observation,
+                    legal_actions(legal),
+                    command_actions(&command),
+                );
+            }
+        }
+        self.send(&Command::GamePlay(command));
+    }
+
+    /// Lets the external engine, if any, take the decision. The command is sent and returned,
+    /// None means that the builtin strategy must be used.
+    fn engine_decision(
+        &mut self,
+        decision: Decision,
+        legal: LegalMoves,
+    ) -> Option<GamePlayCommand> {
+        let view = EngineView::new(&self.game_state, self.my_state().pos.to_n());
+        let engine = self.engine.as_mut()?;
+        let command = match engine.decide(view, legal.clone()) {
+            Ok(command) => command,
+            Err(e) => {
+                warn!(
+                    "{}: engine {} failed, using builtin strategy: {}",
+                    self.player_info.nickname,
+                    engine.name(),
+                    e
+                );
+                return None;
+            }
+        };
+        self.trace(
+            decision,
+            "engine",
+            legal.candidates(),
+            describe_command(&command),
+            None,
+        );
+        self.decide(decision, &legal, command.clone());
+        Some(command)
+    }
+
     fn handle_new_state(&mut self) {
         self.update_stats();
         let player_action = self.my_state().get_turn_player_action(self.game_state.turn);
+        if self.stats.players.is_empty()
+            && player_action.is_some()
+            && player_action != Some(PlayerAction::Bid)
+        {
+            // we replace a player during the deal, or a previous player took a garde contre
+            self.stats.init_state(
+                self.game_state.nb_players as usize,
+                self.game_state.deal.hand,
+            );
+        }
         // let mypos = my_state.pos.to_n();
         // let is_my_turn = self.game_state.get_playing_pos() == Some(self.my_state().pos);
         match player_action {
@@ -451,65 +883,116 @@ impl Player {
                     self.game_state.deal.hand,
                 );
 
-                if let Some(target) = self.guess_bid() {
-                    self.in_out
-                        .send(&Command::GamePlay(GamePlayCommand::Bid(BidCommand {
-                            target,
-                            slam: false,
-                        })));
-                } else {
-                    self.in_out.send(&Command::GamePlay(GamePlayCommand::Pass));
+                let legal = LegalMoves::bid(self.game_state.deal.contract_target());
+                if self.engine_decision(Decision::Bid, legal.clone()).is_some() {
+                    return;
                 }
+                let bid = self.guess_bid();
+                self.trace(
+                    Decision::Bid,
+                    "hand_evaluation",
+                    legal.candidates(),
+                    bid.map(|target| target.to_string())
+                        .unwrap_or_else(|| String::from("pass")),
+                    Some(format!("{} points", self.evaluate_hand())),
+                );
+                let command = match bid {
+                    Some(target) => GamePlayCommand::Bid(BidCommand {
+                        target,
+                        slam: false,
+                    }),
+                    None => GamePlayCommand::Pass,
+                };
+                self.decide(Decision::Bid, &legal, command);
             }
             Some(PlayerAction::CallKing) => {
-                self.in_out
-                    .send(&Command::GamePlay(GamePlayCommand::CallKing(
-                        CallKingCommand {
-                            card: self.call_king(),
-                        },
-                    )));
+                let legal = LegalMoves::call_king(self.game_state.deal.hand);
+                if self
+                    .engine_decision(Decision::CallKing, legal.clone())
+                    .is_some()
+                {
+                    return;
+                }
+                let (card, rule) = self.call_king();
+                self.trace(
+                    Decision::CallKing,
+                    rule,
+                    legal.candidates(),
+                    card.to_string(),
+                    None,
+                );
+                self.decide(
+                    Decision::CallKing,
+                    &legal,
+                    GamePlayCommand::CallKing(CallKingCommand { card }),
+                );
             }
             Some(PlayerAction::MakeDog) => {
-                let dog = self.make_dog();
+                //Let the players see the initial dog
+                self.wait();
+
+                let deal = &self.game_state.deal;
+                //remove dog cards from suit_left
+                for card in deal.initial_dog {
+                    (*self.stats.suit_left.get_mut(&card.suit()).unwrap()).remove(card);
+                }
+
+                let legal = LegalMoves::make_dog(deal.hand, deal.initial_dog);
+                let dog = match self.engine_decision(Decision::MakeDog, legal.clone()) {
+                    Some(GamePlayCommand::MakeDog(cmd)) => cmd.cards,
+                    _ => {
+                        let (dog, rule) = self.make_dog();
+                        self.trace(
+                            Decision::MakeDog,
+                            rule,
+                            legal.candidates(),
+                            dog.to_string(),
+                            None,
+                        );
+                        self.decide(
+                            Decision::MakeDog,
+                            &legal,
+                            GamePlayCommand::MakeDog(MakeDogCommand {
+                                cards: dog,
+                                slam: false,
+                            }),
+                        );
+                        dog
+                    }
+                };
                 for card in dog.list() {
                     (*self.stats.suit_left.get_mut(&card.suit()).unwrap()).remove(card);
                 }
-                self.in_out
-                    .send(&Command::GamePlay(GamePlayCommand::MakeDog(
-                        MakeDogCommand {
-                            cards: dog,
-                            slam: false,
-                        },
-                    )));
             }
             Some(PlayerAction::Play) => {
-                // init state if not already done ( if garde contre by a previous player)
-                if self.stats.players.len() == 0 {
-                    self.stats.init_state(
-                        self.game_state.nb_players as usize,
-                        self.game_state.deal.hand,
-                    );
-                }
-
                 let card_played = self
                     .game_state
                     .deal
                     .last_trick
                     .card_played(self.my_state().pos);
                 if card_played.is_none() {
-                    self.choose_card()
-                        .or_else(|| {
-                            // println!("\n============================ No card played !!");
-                            // println!("state : {:?}", mystate);
-                            None
-                        })
-                        .map(|card| {
-                            // println!(">> {} is playing {}...\n\n", self.player_info.nickname, card.to_string());
-                            self.stats.set_suit_played(&card.suit());
-                            self.in_out.send(&Command::GamePlay(GamePlayCommand::Play(
-                                PlayCommand { card },
-                            )));
-                        });
+                    let legal = LegalMoves::play(&self.playable_cards());
+                    let card = match self.engine_decision(Decision::Play, legal.clone()) {
+                        Some(GamePlayCommand::Play(cmd)) => Some(cmd.card),
+                        _ => self.choose_card().map(|(card, rule)| {
+                            self.trace(
+                                Decision::Play,
+                                rule,
+                                legal.candidates(),
+                                card.to_string(),
+                                None,
+                            );
+                            self.decide(
+                                Decision::Play,
+                                &legal,
+                                GamePlayCommand::Play(PlayCommand { card }),
+                            );
+                            card
+                        }),
+                    };
+                    if let Some(card) = card {
+                        self.stats.set_suit_played(&card.suit());
+                    }
                 }
             }
             _ => {}
@@ -617,98 +1100,15 @@ impl Player {
         points
     }
 
-    fn call_king(&self) -> Card {
-        let deal = &self.game_state.deal;
-        let hand = deal.hand;
-        let rank = if hand.has_all_rank(Rank::RankK) {
-            if hand.has_all_rank(Rank::RankQ) {
-                Rank::RankC
-            } else {
-                Rank::RankQ
-            }
-        } else {
-            Rank::RankK
-        };
-
-        let mut suits = [Suit::Club, Suit::Diamond, Suit::Spade, Suit::Heart];
-        suits.sort_by(|a, b| {
-            let a_cards: Vec<Card> = hand.into_iter().filter(|c| &c.suit() == a).collect();
-            let b_cards: Vec<Card> = hand.into_iter().filter(|c| &c.suit() == b).collect();
-            a_cards.len().cmp(&b_cards.len())
-        });
-
-        let mut candidates: Vec<Card> = suits
-            .into_iter()
-            .filter(|suit| !hand.has(Card::new(**suit, rank)))
-            .map(|suit| Card::new(*suit, rank))
-            .collect();
-        candidates.pop().unwrap()
+    fn call_king(&self) -> (Card, &'static str) {
+        called_card(self.game_state.deal.hand)
     }
 
-    fn make_dog(&mut self) -> Hand {
-        //Let the players see the initial dog
-        let now = time::Instant::now();
-        thread::sleep(self.delay);
-
-        let mut dog = Hand::new();
+    fn make_dog(&self) -> (Hand, &'static str) {
         let deal = &self.game_state.deal;
-
-        //remove dog cards from suit_left
-        for card in deal.initial_dog {
-            (*self.stats.suit_left.get_mut(&card.suit()).unwrap()).remove(card);
-        }
-
-        let dog_size = deal.initial_dog.size();
-        let mut hand_all = deal.hand.clone();
+        let mut hand_all = deal.hand;
         hand_all.merge(deal.initial_dog);
-        let mut hand = hand_all.no_trumps();
-        //Check if we can make a cut
-        for suit in &[Suit::Club, Suit::Diamond, Suit::Spade, Suit::Heart] {
-            let suit_cards: Vec<Card> = hand.into_iter().filter(|c| &c.suit() == suit).collect();
-            let king = Card::new(*suit, Rank::RankK);
-            let suit_count = suit_cards.len();
-            if suit_count <= (dog_size - dog.size()) && !hand.has(king) {
-                for card in suit_cards {
-                    dog.add(card);
-                    hand.remove(card);
-                }
-            }
-            hand.remove(king); // kings not allowed in dog
-        }
-        //Put points
-        let mut queens: Vec<Card> = hand
-            .into_iter()
-            .filter(|c| c.rank() == Rank::RankQ)
-            .collect();
-        let mut cavales: Vec<Card> = hand
-            .into_iter()
-            .filter(|c| c.rank() == Rank::RankC)
-            .collect();
-        let mut candidates: Vec<Card> = hand
-            .into_iter()
-            .filter(|c| c.rank() == Rank::RankJ)
-            .collect();
-        candidates.append(&mut cavales);
-        candidates.append(&mut queens);
-        let mut candidate = candidates.pop();
-        while dog.size() < dog_size && candidate.is_some() {
-            let card = candidate.unwrap();
-            dog.add(card);
-            hand.remove(card);
-            candidate = candidates.pop();
-        }
-        //other cards
-        //(we assume there is enough non trumps cards :
-        //if not, the contract should have been "garde sans" or "garde contre")
-        let mut cards = hand.list();
-        let mut card = cards.pop();
-        while dog.size() < dog_size && card.is_some() {
-            dog.add(card.unwrap());
-            card = cards.pop();
-        }
-
-        assert!(dog.size() == dog_size);
-        dog
+        dog_cards(hand_all, deal.initial_dog.size(), deal.king)
     }
 
     fn check_play_excuse_instead_of_trump(&self) -> bool {
@@ -740,7 +1140,8 @@ impl Player {
         false
     }
 
-    fn choose_card(&self) -> Option<Card> {
+    /// Returns the chosen card along with the name of the rule which selected it
+    fn choose_card(&mut self) -> Option<(Card, &'static str)> {
         let deal = &self.game_state.deal;
         let trick = &deal.last_trick;
         let hand = deal.hand;
@@ -749,7 +1150,7 @@ impl Player {
 
         //Play the excuse before the last trick
         if hand.size() == 2 && hand.has(excuse) {
-            return Some(excuse);
+            return Some((excuse, "excuse_before_last_trick"));
         }
 
         let mepos = self.my_state().pos.to_n();
@@ -759,21 +1160,19 @@ impl Player {
 
         if let Some(starting_suit) = trick.suit() {
             // Not the first to play
-            // print!("not the first to play..  ");
             let winner_card = trick.cards[trick.winner.pos as usize].unwrap();
 
             if starting_suit == Suit::Trump {
-                // print!("trump asked..  ");
                 // Try to save partner's petit by playing 21
                 let found = self.play_try_save_petit(false);
-                if found.is_some() {
-                    return found;
+                if let Some(card) = found {
+                    return Some((card, "save_partner_petit"));
                 };
 
                 // Try to save own's petit
                 let found = self.play_try_own_petit();
-                if found.is_some() {
-                    return found;
+                if let Some(card) = found {
+                    return Some((card, "save_own_petit"));
                 };
 
                 // If there is points to save and i am not the last to play : play high (fix https://github.com/mmai/webtarot/issues/34)
@@ -782,26 +1181,25 @@ impl Player {
                 {
                     if let Some(highest) = hand.trump_highest() {
                         if highest.rank() > winner_card.rank() {
-                            return Some(highest);
+                            return Some((highest, "high_trump_to_secure_points"));
                         }
                     }
                 }
 
                 //Play the excuse if there is no points to gain
                 if self.check_play_excuse_instead_of_trump() {
-                    return Some(excuse);
+                    return Some((excuse, "excuse_nothing_to_win"));
                 }
 
                 // Must be higher trump if exists
                 if let Some(mylowest) = hand.suit_lowest_over_card(Suit::Trump, winner_card) {
-                    return Some(mylowest);
+                    return Some((mylowest, "lowest_winning_trump"));
                 }
                 // or cut with smallest (except petit)
                 if let Some(mylowest) = hand.suit_lowest_over_card(Suit::Trump, petit) {
-                    return Some(mylowest);
+                    return Some((mylowest, "lowest_trump"));
                 }
             } else {
-                // print!("no trump asked..  ");
                 let my_highest = hand.suit_highest(starting_suit);
                 let highest_left = self
                     .stats
@@ -810,23 +1208,18 @@ impl Player {
                     .unwrap()
                     .suit_highest(starting_suit);
                 if my_highest.is_some() {
-                    // print!("i have color..  ");
                     let mut myhighest = my_highest.unwrap();
                     if myhighest > winner_card && winner_card.suit() != Suit::Trump {
                         // I can win the trick
-                        // print!("i can win..  ");
                         // If not points, take the lowest still winning
                         if myhighest.rank() < Rank::RankJ {
-                            // print!("with a no points card..  ");
-                            return hand.suit_lowest_over_card(starting_suit, winner_card);
+                            return hand
+                                .suit_lowest_over_card(starting_suit, winner_card)
+                                .map(|c| (c, "lowest_winning_card"));
                         }
 
                         if highest_left.is_some() {
-                            // print!("highest_left: {}..  ", highest_left.unwrap().to_string());
                             let is_cut = self.stats.suit_is_cut(starting_suit);
-                            // if is_cut { println!("I am cut"); } else { println!("I am not cut"); }
-                            // if me.in_taker_team == Some(true) { println!("in taker team"); } else { println!("not in taker team"); }
-                            // if danger { println!("danger"); } else { println!("no danger"); }
 
                             let taker_pos = PlayerPos::from_n(
                                 self.get_taker_pos().unwrap() as usize,
@@ -841,17 +1234,15 @@ impl Player {
                                     || (self.is_first_time_suit_played(&starting_suit)
                                         && taker_already_played))
                             {
-                                return Some(myhighest);
+                                return Some((myhighest, "master_card"));
                             }
                         } else {
-                            // print!("i will be cut..  ");
                             if let Some(mylowest) = hand.suit_lowest(starting_suit) {
-                                return Some(mylowest);
+                                return Some((mylowest, "lowest_card_will_be_cut"));
                             }
                         }
                     } else {
                         // I can't win the trick
-                        // print!("i can't win..  ");
                         // Give points if my parter win the trick
                         if self.stats.players[trick.winner.pos.to_n()].is_partner(me) == Some(true)
                             && myhighest.rank() >= Rank::RankJ
@@ -861,18 +1252,17 @@ impl Player {
                                         || highest_left.map(|c| c.rank() > winner_card.rank())
                                             != Some(true))))
                         {
-                            return Some(myhighest);
+                            return Some((myhighest, "give_points_to_partner"));
                         } else {
-                            return hand.suit_lowest(starting_suit);
+                            return hand.suit_lowest(starting_suit).map(|c| (c, "lowest_card"));
                         }
                     }
                 } else {
                     // I must cut or piss
-                    // print!("i have not the color..  ");
                     // Try to save own's petit
                     let found = self.play_try_own_petit();
-                    if found.is_some() {
-                        return found;
+                    if let Some(card) = found {
+                        return Some((card, "save_own_petit"));
                     };
 
                     // If there is points to save and i am not the last to play and it is not the
@@ -883,7 +1273,7 @@ impl Player {
                     {
                         if let Some(highest) = hand.trump_highest() {
                             if highest.rank() > winner_card.rank() {
-                                return Some(highest);
+                                return Some((highest, "high_trump_to_secure_points"));
                             }
                         }
                     }
@@ -892,17 +1282,17 @@ impl Player {
                     if winner_card.suit() == Suit::Trump {
                         if let Some(mylowest) = hand.suit_lowest_over_card(Suit::Trump, winner_card)
                         {
-                            return Some(mylowest);
+                            return Some((mylowest, "overcut"));
                         }
                     }
                     // or cut with smallest (except petit)
                     if let Some(mylowest) = hand.suit_lowest_over_card(Suit::Trump, petit) {
-                        return Some(mylowest);
+                        return Some((mylowest, "cut"));
                     }
 
                     //Play the excuse if there is no points to gain
                     if self.check_play_excuse_instead_of_trump() {
-                        return Some(excuse);
+                        return Some((excuse, "excuse_nothing_to_win"));
                     }
                 }
             }
@@ -910,21 +1300,18 @@ impl Player {
             //First to play
             // Try to save partner's petit by playing 21
             let found = self.play_try_save_petit(true);
-            if found.is_some() {
-                return found;
+            if let Some(card) = found {
+                return Some((card, "lead_21_to_save_petit"));
             };
 
             // I am the taker and it is not the first trick :
             // play for the king I called if the suit has not been already played
             if me.is_taker && deal.trick_count > 1 {
-                // print!("i am the taker..  ");
                 if let Some(king) = deal.king {
-                    // print!("i called the {} king..  ", king.to_string());
                     if !self.stats.suit_already_played(king.suit()) {
-                        // print!("whose color has not been played..  ");
                         let card = hand.suit_highest(king.suit()); //We give points
-                        if card.is_some() {
-                            return card;
+                        if let Some(card) = card {
+                            return Some((card, "taker_plays_called_suit"));
                         }
                     }
                 }
@@ -932,7 +1319,6 @@ impl Player {
 
             // In  taker team & have king & suit not cut by opponents : play king
             if me.in_taker_team == Some(true) {
-                // print!("i am in the taker team..  ");
                 let kings: Vec<Card> = hand
                     .list()
                     .into_iter()
@@ -940,8 +1326,7 @@ impl Player {
                     .collect();
                 for king in kings {
                     if !self.stats.suit_is_cut(king.suit()) {
-                        return Some(king);
-                        // } else { print!("my king {:?} would be cut.. ", king);
+                        return Some((king, "lead_king"));
                     }
                 }
             }
@@ -971,8 +1356,7 @@ impl Player {
                     if let Some(lowest) = hand.suit_lowest(**unplayed_suit) {
                         if lowest.rank() < Rank::RankJ {
                             // we don't play points
-                            // print!("an opening.. ");
-                            return Some(lowest);
+                            return Some((lowest, "opening"));
                         }
                     }
                 }
@@ -986,8 +1370,7 @@ impl Player {
                     if let Some(lowest) = hand.suit_lowest(**played_suit) {
                         if lowest.rank() < Rank::RankJ {
                             // we don't play points
-                            // print!("not an opening.. ");
-                            return Some(lowest);
+                            return Some((lowest, "no_opening"));
                         }
                     }
                 }
@@ -1011,44 +1394,54 @@ impl Player {
 
             // Play small card of long suit
             if let Some(long_suit) = playable_suits.last() {
-                // print!("small card of long suite.. ");
                 let lowest = hand.suit_lowest(*long_suit).unwrap(); // this card exists because we previously filtered suits with hand.has_any()
                 if lowest.rank() < Rank::RankJ {
                     // we don't play points
-                    return Some(lowest);
+                    return Some((lowest, "long_suit_small_card"));
                 }
             }
 
             // if we are here, we should only have trumps left (or points we don't want to give), play the highest
             let card = hand.suit_highest(Suit::Trump);
-            if card.is_some() && card != Some(petit) {
-                return card;
+            if let Some(card) = card.filter(|c| c != &petit) {
+                return Some((card, "lead_highest_trump"));
             }
         }
 
-        // print!("default play..  ");
         //Low playable card
-        let mut playable: Vec<Card> = hand
+        let mut playable = self.playable_cards();
+        // Si on a plus d'une carte en main, on s'assure qu'on ne joue pas le petit
+        if playable.len() > 1 {
+            playable = playable.into_iter().filter(|card| card != &petit).collect();
+        }
+        // the seed of the bot picks one of the lowest cards
+        let lowest = playable.iter().map(|card| card.rank()).min();
+        let lowest_cards: Vec<Card> = playable
+            .into_iter()
+            .filter(|card| Some(card.rank()) == lowest)
+            .collect();
+        lowest_cards
+            .choose(&mut self.rng)
+            .map(|c| (*c, "lowest_playable"))
+    }
+
+    fn playable_cards(&self) -> Vec<Card> {
+        let deal = &self.game_state.deal;
+        deal.hand
             .list()
             .into_iter()
             .filter(|card| {
                 can_play(
                     self.my_state().pos,
                     *card,
-                    hand,
+                    deal.hand,
                     &deal.last_trick,
                     deal.king,
                     deal.trick_count == 1,
                 )
                 .is_ok()
             })
-            .collect();
-        // Si on a plus d'une carte en main, on s'assure qu'on ne joue pas le petit
-        if playable.len() > 1 {
-            playable = playable.into_iter().filter(|card| card != &petit).collect();
-        }
-        playable.sort_by(|a, b| a.rank().cmp(&b.rank()));
-        playable.first().map(|c| *c)
+            .collect()
     }
 
     fn play_try_save_petit(&self, is_first_player: bool) -> Option<Card> {
@@ -1165,6 +1558,136 @@ impl Player {
     }
 }
 
+const SUITS: [Suit; 4] = [Suit::Club, Suit::Diamond, Suit::Spade, Suit::Heart];
+
+/// Card called by the taker : a king (a queen if the taker has all the kings, a jack if he also
+/// has all the queens) in a suit where he is short, so that his partner can take his small
+/// cards, and the taker is soon able to cut the suit.
+fn called_card(hand: Hand) -> (Card, &'static str) {
+    let rank = if !hand.has_all_rank(Rank::RankK) {
+        Rank::RankK
+    } else if !hand.has_all_rank(Rank::RankQ) {
+        Rank::RankQ
+    } else {
+        Rank::RankJ
+    };
+    let candidates: Vec<Suit> = SUITS
+        .iter()
+        .copied()
+        .filter(|suit| !hand.has(Card::new(*suit, rank)))
+        .collect();
+    let count = |suit: &Suit| hand.get_suit_cards(suit).len();
+    // with a card of the suit, the taker can lead it to his partner
+    if let Some(suit) = candidates
+        .iter()
+        .filter(|suit| count(suit) > 0)
+        .min_by_key(|suit| count(suit))
+    {
+        (Card::new(*suit, rank), "short_suit")
+    } else if let Some(suit) = candidates.first() {
+        (Card::new(*suit, rank), "void_suit")
+    } else {
+        // can't happen : the taker can't have all the jacks, queens and kings
+        (Card::new(Suit::Heart, rank), "own_card")
+    }
+}
+
+/// Cards put in the dog by the taker holding `hand_all` (his hand and the initial dog), when
+/// `king` has been called :
+/// - voids : the shortest suits without king are discarded, but the called suit
+/// - singletons : the king is left alone in the short suits
+/// - honours : queens, knights and jacks which may be lost
+/// - small cards of the shortest suits, the called suit last
+/// - the smallest trumps when only trumps and kings are left (nothing else is allowed then)
+fn dog_cards(hand_all: Hand, dog_size: usize, king: Option<Card>) -> (Hand, &'static str) {
+    let mut dog = Hand::new();
+    let mut rule = "small_cards";
+    let called_suit = king.map(|k| k.suit());
+    // kings are not allowed in the dog
+    let mut allowed = hand_all.no_trumps();
+    for suit in SUITS.iter() {
+        allowed.remove(Card::new(*suit, Rank::RankK));
+    }
+    let mut suits: Vec<Suit> = SUITS
+        .iter()
+        .copied()
+        .filter(|suit| Some(*suit) != called_suit)
+        .collect();
+    suits.sort_by_key(|suit| hand_all.get_suit_cards(suit).len());
+
+    let discard = |cards: Vec<Card>, dog: &mut Hand, allowed: &mut Hand| {
+        if !cards.is_empty() && cards.len() <= dog_size - dog.size() {
+            for card in cards {
+                dog.add(card);
+                allowed.remove(card);
+            }
+            true
+        } else {
+            false
+        }
+    };
+
+    //Voids and singletons
+    let mut singletons = false;
+    for with_king in &[false, true] {
+        for suit in suits.iter() {
+            let has_king = hand_all.has(Card::new(*suit, Rank::RankK));
+            let cards = allowed.get_suit_cards(suit);
+            // a long suit with its king is worth keeping
+            let short = !has_king || cards.len() <= 2;
+            if has_king == *with_king && short && discard(cards, &mut dog, &mut allowed) {
+                if has_king {
+                    singletons = true;
+                } else {
+                    rule = "voids";
+                }
+            }
+        }
+    }
+    if singletons && rule != "voids" {
+        rule = "singletons";
+    }
+
+    //Honours
+    for rank in &[Rank::RankQ, Rank::RankC, Rank::RankJ] {
+        for card in allowed.list() {
+            if card.rank() == *rank && dog.size() < dog_size {
+                dog.add(card);
+                allowed.remove(card);
+            }
+        }
+    }
+
+    //Small cards
+    suits.extend(called_suit);
+    for suit in suits.iter() {
+        let mut cards = allowed.get_suit_cards(suit);
+        cards.sort_by_key(|c| c.rank());
+        for card in cards {
+            if dog.size() < dog_size {
+                dog.add(card);
+            }
+        }
+    }
+
+    //Trumps, when there is nothing else
+    if dog.size() < dog_size {
+        rule = "trumps_discarded";
+        let mut trumps: Vec<Card> = hand_all
+            .trumps()
+            .into_iter()
+            .filter(|c| !c.is_oudler())
+            .collect();
+        trumps.sort_by_key(|c| c.rank());
+        for card in trumps {
+            if dog.size() < dog_size {
+                dog.add(card);
+            }
+        }
+    }
+    (dog, rule)
+}
+
 #[test]
 fn test_assure_petit() {
     let in_out = Box::new(TestInOut {});
@@ -1201,6 +1724,7 @@ fn test_save_own_petit() {
     let in_out = Box::new(TestInOut {});
     let delay = time::Duration::from_millis(0);
     let mut bot = Player::new(in_out, "joincode".to_string(), "bot1".to_string(), delay);
+    bot.set_tracer(Tracer::disabled());
 
     let nb_players = 5u8;
     let henri_pos = PlayerPos::from_n(0, nb_players);
@@ -1303,19 +1827,280 @@ fn test_save_own_petit() {
     // bot1 should save the petit: Henri (partner/taker) wins with 21T and
     // the Excuse cannot capture the petit, so there is no real threat.
     assert_eq!(bot.play_try_own_petit(), Some(petit));
+    assert_eq!(bot.choose_card(), Some((petit, "save_own_petit")));
 }
 
 pub struct TestInOut {}
 
 impl InOut for TestInOut {
-    fn read(&mut self) -> Message {
-        let message: Message = serde_json::from_str("{}").expect("Can't parse JSON");
-        message
+    fn read(&mut self) -> Result<Message, InOutError> {
+        let message: Message = serde_json::from_str("{}")?;
+        Ok(message)
     }
 
-    fn send(&mut self, command: &Command) -> Result<()> {
+    fn send(&mut self, command: &Command) -> Result<(), InOutError> {
         Ok(())
     }
 
     fn close(&mut self) {}
 }
+
+#[test]
+fn test_backoff_delay() {
+    assert_eq!(backoff_delay(1), time::Duration::from_secs(1));
+    assert_eq!(backoff_delay(2), time::Duration::from_secs(2));
+    assert_eq!(backoff_delay(4), time::Duration::from_secs(8));
+    assert_eq!(backoff_delay(20), time::Duration::from_secs(60));
+}
+
+#[test]
+fn test_bot_delay_from_settings() {
+    let delay = time::Duration::from_millis(6000);
+    let mut bot = Player::new(
+        Box::new(TestInOut {}),
+        "joincode".to_string(),
+        "bot1".to_string(),
+        delay,
+    );
+    bot.handle_server_message(Message::GameStateSnapshot(
+        GameStateSnapshot::default().into(),
+    ));
+    assert_eq!(bot.delay, delay);
+
+    let mut game_state = GameStateSnapshot::default();
+    game_state.bot_delay = Some(1000);
+    bot.handle_server_message(Message::GameStateSnapshot(game_state.into()));
+    assert_eq!(bot.delay, time::Duration::from_millis(1000));
+}
+
+#[test]
+fn test_replayed_events() {
+    let delay = time::Duration::from_millis(0);
+    let mut bot = Player::new(
+        Box::new(TestInOut {}),
+        "joincode".to_string(),
+        "bot1".to_string(),
+        delay,
+    );
+    let event = |seq| {
+        Message::PlayEvent(GameEvent {
+            seq,
+            event: PlayEvent::EndTrick,
+        })
+    };
+    bot.handle_server_message(event(3));
+    assert_eq!(bot.next_event, Some(4));
+    // events replayed after a reconnection are only handled once
+    bot.handle_server_message(event(3));
+    bot.handle_server_message(event(4));
+    assert_eq!(bot.next_event, Some(5));
+}
+
+#[test]
+fn test_rejected_move() {
+    let delay = time::Duration::from_millis(0);
+    let mut bot = Player::new(
+        Box::new(TestInOut {}),
+        "joincode".to_string(),
+        "bot1".to_string(),
+        delay,
+    );
+    let rejected = |error: ProtocolError| Message::Error(error.into());
+    bot.game_state.nb_players = 4;
+    bot.handle_server_message(rejected(tarotgame::deal::PlayError::IncorrectSuit.into()));
+    assert_eq!(bot.game_state, GameStateSnapshot::default());
+    assert_eq!(bot.rejected_moves, 1);
+
+    // not our turn : the next state will tell us what to do
+    bot.game_state.nb_players = 4;
+    bot.handle_server_message(rejected(tarotgame::deal::PlayError::TurnError.into()));
+    assert_eq!(bot.game_state.nb_players, 4);
+
+    for _ in 0..MAX_REJECTED_MOVES {
+        bot.handle_server_message(rejected(tarotgame::deal::PlayError::IncorrectSuit.into()));
+    }
+    assert_eq!(bot.rejected_moves, MAX_REJECTED_MOVES);
+}
+
+#[cfg(test)]
+fn hand_of(cards: &[&str]) -> Hand {
+    use std::str::FromStr;
+    let mut hand = Hand::new();
+    for card in cards {
+        hand.add(Card::from_str(card).unwrap());
+    }
+    hand
+}
+
+#[test]
+fn test_seeded_choice() {
+    // with no heart nor trump, the bot discards one of its three 2s, chosen by its seed
+    let chosen = |seed| {
+        let mut bot = Player::new(
+            Box::new(TestInOut {}),
+            "joincode".to_string(),
+            "bot1".to_string(),
+            time::Duration::from_millis(0),
+        );
+        bot.set_tracer(Tracer::disabled());
+        bot.set_seed(seed);
+        let nb_players = 4u8;
+        let hand = hand_of(&["2♣", "2♦", "9♦", "2♠"]);
+        let mut trick = Trick::new(PlayerPos::from_n(0, nb_players));
+        trick.play_card(
+            PlayerPos::from_n(0, nb_players),
+            hand_of(&["5♥"]).get_card(),
+        );
+        bot.game_state.nb_players = nb_players;
+        bot.game_state.turn = Turn::Playing(PlayerPos::from_n(1, nb_players));
+        bot.game_state.deal.hand = hand;
+        bot.game_state.deal.last_trick = trick;
+        bot.game_state.players = (0..nb_players as usize)
+            .map(|n| GamePlayerState {
+                player: PlayerInfo {
+                    id: if n == 1 {
+                        bot.player_info.id
+                    } else {
+                        Uuid::new_v4()
+                    },
+                    nickname: format!("player{}", n),
+                },
+                pos: PlayerPos::from_n(n, nb_players),
+                role: PlayerRole::Unknown,
+                ready: true,
+            })
+            .collect();
+        bot.stats.init_state(nb_players as usize, hand);
+        bot.choose_card().map(|(card, _)| card.to_string())
+    };
+    assert_eq!(chosen(7), chosen(7));
+    let choices: Vec<Option<String>> = (0..20).map(chosen).collect();
+    assert!(choices
+        .iter()
+        .all(|card| card.as_ref().map_or(false, |card| card.starts_with('2'))));
+    assert!(choices.iter().any(|card| *card != choices[0]));
+}
+
+#[test]
+fn test_call_king() {
+    // the taker calls the suit where he has only one card
+    let hand = hand_of(&[
+        "K♣", "2♣", "3♣", "4♣", "3♠", "5♦", "6♦", "1♥", "2♥", "3♥", "4♥", "5♥", "1T", "5T", "8T",
+    ]);
+    assert_eq!(
+        called_card(hand),
+        (hand_of(&["K♠"]).get_card(), "short_suit")
+    );
+
+    // all kings : a queen is called
+    let hand = hand_of(&[
+        "K♣", "K♦", "K♠", "K♥", "Q♥", "2♥", "3♠", "4♠", "5♦", "6♦", "2♣", "1T", "5T", "8T", "9T",
+    ]);
+    assert_eq!(
+        called_card(hand),
+        (hand_of(&["Q♣"]).get_card(), "short_suit")
+    );
+
+    // all kings and queens : a jack is called
+    let hand = hand_of(&[
+        "K♣", "K♦", "K♠", "K♥", "Q♣", "Q♦", "Q♠", "Q♥", "2♥", "1T", "5T", "8T", "9T", "10T", "11T",
+    ]);
+    assert_eq!(
+        called_card(hand),
+        (hand_of(&["J♣"]).get_card(), "short_suit")
+    );
+
+    // the only king missing is in a void suit
+    let hand = hand_of(&[
+        "K♣", "2♣", "K♠", "3♠", "K♥", "4♥", "1T", "5T", "8T", "9T", "10T", "11T", "12T", "13T",
+        "14T",
+    ]);
+    assert_eq!(
+        called_card(hand),
+        (hand_of(&["K♦"]).get_card(), "void_suit")
+    );
+}
+
+#[test]
+fn test_dog_voids() {
+    let king = hand_of(&["K♠"]).get_card();
+    let hand_all = hand_of(&[
+        "2♦", "3♦", "K♣", "7♣", "5♠", "6♠", "1♥", "2♥", "3♥", "4♥", "5♥", "Q♥", "1T", "5T", "8T",
+        "9T", "10T", "21T",
+    ]);
+    assert_eq!(
+        dog_cards(hand_all, 3, Some(king)),
+        (hand_of(&["2♦", "3♦", "7♣"]), "voids")
+    );
+}
+
+#[test]
+fn test_dog_singletons() {
+    // no void possible : the kings of diamonds and clubs are left alone, spades are kept for
+    // the partner
+    let king = hand_of(&["K♠"]).get_card();
+    let hand_all = hand_of(&[
+        "K♦", "4♦", "K♣", "7♣", "8♣", "5♠", "6♠", "1♥", "2♥", "3♥", "4♥", "5♥", "6♥", "1T", "5T",
+        "8T", "9T", "10T",
+    ]);
+    assert_eq!(
+        dog_cards(hand_all, 3, Some(king)),
+        (hand_of(&["4♦", "7♣", "8♣"]), "singletons")
+    );
+}
+
+#[test]
+fn test_dog_trumps() {
+    // four players : only kings and one heart besides trumps, the smallest trumps are discarded
+    let mut hand_all = hand_of(&["K♣", "K♦", "K♠", "K♥", "2♥", "22T"]);
+    // 1T to 18T
+    for n in 0..18 {
+        hand_all.add(Card::new(Suit::Trump, Rank::from_n(n)));
+    }
+    let (dog, rule) = dog_cards(hand_all, 6, None);
+    assert_eq!(dog, hand_of(&["2♥", "2T", "3T", "4T", "5T", "6T"]));
+    assert_eq!(rule, "trumps_discarded");
+}
+
+#[test]
+fn test_dog_is_legal() {
+    use crate::engine::EngineResponse;
+    for nb_players in 3..=5 {
+        for n in 0..20u8 {
+            let (hands, dog) = deal_seeded_hands([n; 32], nb_players);
+            let mut hand_all = hands[0];
+            hand_all.merge(dog);
+            let king = if nb_players == 5 {
+                Some(called_card(hands[0]).0)
+            } else {
+                None
+            };
+            let (cards, _rule) = dog_cards(hand_all, dog.size(), king);
+            let legal = LegalMoves::make_dog(hands[0], dog);
+            let response = EngineResponse::MakeDog {
+                cards: cards.list().iter().map(|c| c.to_string()).collect(),
+            };
+            assert!(
+                response.into_command(&legal).is_ok(),
+                "{} players, dog {}",
+                nb_players,
+                cards.to_string()
+            );
+            // and by the game rules
+            let taker = PlayerPos::from_n(0, nb_players as u8);
+            let contract = tarotgame::bid::Contract {
+                author: taker,
+                target: Target::Garde,
+                slam: false,
+            };
+            let mut deal = tarotgame::deal::DealState::new(taker, hands, dog, contract, taker);
+            assert_eq!(
+                deal.make_dog(taker, cards, false),
+                Ok(()),
+                "{} players, dog {}",
+                nb_players,
+                cards.to_string()
+            );
+        }
+    }
+}
@@ -5,6 +5,7 @@ use tungstenite::protocol::WebSocket;
 // use std::thread;
 use rayon::prelude::*;
 
+use log::warn;
 use std::time;
 use uuid::Uuid;
 use url::Url;
@@ -14,29 +15,60 @@ use webtarot_protocol::{Message, Command, GameStateSnapshot, PlayerAction, GameP
 use webgame_protocol::{AuthenticateCommand, JoinGameCommand, PlayerInfo};
 
 use crate::player;
+use crate::engine::Engine;
 use crate::in_out_websocket::TarotWebSocket;
 
-pub fn play(join_code: &str, str_websocket: &str, count: usize) {
+/// Starts `count` bots joining the game `join_code`, or a bot creating a game and inviting the
+/// other ones when there is no join code. The bots wait `delay` before playing, until they
+/// follow the delay of the game settings.
+pub fn play(join_code: &str, str_websocket: &str, count: usize, engine: Option<&str>, engine_timeout: time::Duration, seed: Option<u64>, delay: time::Duration) {
     env_logger::init();
 
     if join_code == "" {
-        let in_out = Box::new(TarotWebSocket::new(str_websocket));
+        let in_out = match TarotWebSocket::new(str_websocket) {
+            Ok(in_out) => Box::new(in_out),
+            Err(e) => {
+                warn!("Can't connect to {}: {}", str_websocket, e);
+                return;
+            }
+        };
         let nickname = format!("parent");
-        let delay = time::Duration::from_millis(1000);
         let mut bot = crate::player::Player::new(in_out, join_code.to_string(), nickname, delay);
+        if let Some(seed) = seed {
+            bot.set_seed(seed);
+        }
+        start_engine(&mut bot, engine, engine_timeout);
         bot.play();
     } else {
-        let in_outs: Vec<Box<TarotWebSocket>> = (0..count).map(|_| {
-            Box::new(TarotWebSocket::new(str_websocket))
+        let in_outs: Vec<Box<TarotWebSocket>> = (0..count).filter_map(|_| {
+            match TarotWebSocket::new(str_websocket) {
+                Ok(in_out) => Some(Box::new(in_out)),
+                Err(e) => {
+                    warn!("Can't connect to {}: {}", str_websocket, e);
+                    None
+                }
+            }
         }).collect();
 
         in_outs.into_par_iter().enumerate().for_each(|(i, in_out)| {
             let nickname = format!("TAROBOT-{}", i);
-            let delay = time::Duration::from_millis(1000);
 
             let mut bot = crate::player::Player::new(in_out, join_code.to_string(), nickname, delay);
+            if let Some(seed) = seed {
+                bot.set_seed(seed.wrapping_add(i as u64));
+            }
+            start_engine(&mut bot, engine, engine_timeout);
             bot.play();
         });
     }
 
 }
+
+fn start_engine(bot: &mut player::Player, engine: Option<&str>, timeout: time::Duration) {
+    if let Some(command_line) = engine {
+        match Engine::spawn(command_line, timeout) {
+            Ok(engine) => bot.set_engine(engine),
+            Err(e) => warn!("Can't start engine {}: {}", command_line, e),
+        }
+    }
+}
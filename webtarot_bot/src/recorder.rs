@@ -0,0 +1,349 @@
+//! Training data : `(observation, legal actions, chosen action, outcome)` samples of the bot
+//! decisions, to train a learned policy (see `doc/training-data.md`).
+//!
+//! Samples of a deal are kept until the deal is over, then written with the score of the
+//! player for this deal as outcome. Samples are written as JSON lines, or in a compact binary
+//! format when the file name ends with `.bin`.
+//!
+//! Live bots record their decisions when `WEBTAROT_BOT_RECORD` gives the path of the file.
+
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use log::error;
+use serde::Serialize;
+
+use tarotgame::{
+    bid::Target,
+    cards::{Card, Hand},
+};
+use webtarot_protocol::{GamePlayCommand, GameStateSnapshot};
+
+use crate::engine::LegalMoves;
+use crate::trace::Decision;
+
+pub const RECORD_ENV: &str = "WEBTAROT_BOT_RECORD";
+
+/// Number of cards, size of the card encodings
+pub const CARDS_COUNT: usize = 78;
+/// Actions 0 to 77 are cards (played, called or put in the dog)
+pub const ACTION_PASS: u8 = 78;
+/// Bids are `ACTION_BID + target` (prise, garde, garde sans, garde contre)
+pub const ACTION_BID: u8 = 79;
+
+/// Index of the card in the encodings : suit cards from 0 to 55 (1 to king of hearts,
+/// spades, diamonds, clubs), then trumps from 56 (1T) to 76 (21T) and the excuse (77)
+pub fn card_index(card: Card) -> u8 {
+    let id = card.id();
+    // trump ids skip 66 to 69
+    if id < 66 {
+        id as u8
+    } else {
+        (id - 4) as u8
+    }
+}
+
+fn target_index(target: Target) -> u8 {
+    match target {
+        Target::Prise => 0,
+        Target::Garde => 1,
+        Target::GardeSans => 2,
+        Target::GardeContre => 3,
+    }
+}
+
+fn encode_hand(hand: Hand) -> Vec<u8> {
+    let mut encoded = vec![0; CARDS_COUNT];
+    for card in hand.list() {
+        encoded[card_index(card) as usize] = 1;
+    }
+    encoded
+}
+
+/// What the player sees when taking a decision. Positions are relative to the player (0 is the
+/// player, 1 the next one...). Card encodings have one value per card (see `card_index`).
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub nb_players: u8,
+    pub pos: u8,
+    pub trick_count: u8,
+    /// 0 prise, 1 garde, 2 garde sans, 3 garde contre
+    pub contract: Option<u8>,
+    pub taker: Option<u8>,
+    pub king: Option<u8>,
+    /// 1 if the card is in the hand
+    pub hand: Vec<u8>,
+    /// 1 + relative position of the player who played the card in the current trick, 0 if not
+    /// played
+    pub trick: Vec<u8>,
+    /// 1 + relative position of the player who played the card in a previous trick, 0 if not
+    /// played
+    pub played: Vec<u8>,
+    /// 1 if the card is known to be in the dog
+    pub dog: Vec<u8>,
+}
+
+impl Observation {
+    /// `played` : cards played by each player in the previous tricks
+    pub fn new(state: &GameStateSnapshot, pos: usize, played: &[Hand]) -> Self {
+        let deal = &state.deal;
+        let nb_players = state.nb_players as usize;
+        let relative = |p: usize| ((p + nb_players - pos) % nb_players) as u8;
+
+        let mut trick = vec![0; CARDS_COUNT];
+        for (p, card) in deal.last_trick.cards[..nb_players].iter().enumerate() {
+            if let Some(card) = card {
+                trick[card_index(*card) as usize] = 1 + relative(p);
+            }
+        }
+        let mut encoded_played = vec![0; CARDS_COUNT];
+        for (p, hand) in played.iter().enumerate() {
+            for card in hand.list() {
+                if trick[card_index(card) as usize] == 0 {
+                    encoded_played[card_index(card) as usize] = 1 + relative(p);
+                }
+            }
+        }
+        let mut dog = deal.initial_dog;
+        dog.merge(deal.dog);
+
+        Observation {
+            nb_players: state.nb_players,
+            pos: pos as u8,
+            trick_count: deal.trick_count as u8,
+            contract: deal.contract_target().map(target_index),
+            taker: deal.contract.as_ref().map(|c| relative(c.author.to_n())),
+            king: deal.king.map(card_index),
+            hand: encode_hand(deal.hand),
+            trick,
+            played: encoded_played,
+            dog: encode_hand(dog),
+        }
+    }
+}
+
+/// Actions allowed for the decision
+pub fn legal_actions(legal: &LegalMoves) -> Vec<u8> {
+    match legal {
+        LegalMoves::Bid { targets } => std::iter::once(ACTION_PASS)
+            .chain(
+                targets
+                    .iter()
+                    .filter_map(|t| Target::from_str(t).ok())
+                    .map(|t| ACTION_BID + target_index(t)),
+            )
+            .collect(),
+        LegalMoves::CallKing { cards }
+        | LegalMoves::MakeDog { cards, .. }
+        | LegalMoves::Play { cards } => cards
+            .iter()
+            .filter_map(|c| Card::from_str(c).ok())
+            .map(card_index)
+            .collect(),
+    }
+}
+
+/// Actions of a command : one action, or the cards put in the dog
+pub fn command_actions(command: &GamePlayCommand) -> Vec<u8> {
+    match command {
+        GamePlayCommand::Pass => vec![ACTION_PASS],
+        GamePlayCommand::Bid(cmd) => vec![ACTION_BID + target_index(cmd.target)],
+        GamePlayCommand::CallKing(cmd) => vec![card_index(cmd.card)],
+        GamePlayCommand::MakeDog(cmd) => cmd.cards.list().into_iter().map(card_index).collect(),
+        GamePlayCommand::Play(cmd) => vec![card_index(cmd.card)],
+        GamePlayCommand::Announce(_)
+        | GamePlayCommand::ReplaceAbsent(_)
+        | GamePlayCommand::TakeSeat(_)
+        | GamePlayCommand::SetBotDelay(_)
+        | GamePlayCommand::SetHands(_)
+        | GamePlayCommand::Entropy(_)
+        | GamePlayCommand::Resync
+        | GamePlayCommand::Replay(_)
+        | GamePlayCommand::Hello(_)
+        | GamePlayCommand::Timeout => vec![],
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// Seed of the game, for games played by the headless executor
+    pub seed: Option<u64>,
+    /// Deal number in the game
+    pub deal: u32,
+    pub decision: Decision,
+    pub observation: Observation,
+    pub legal: Vec<u8>,
+    pub chosen: Vec<u8>,
+    /// Score of the player for the deal
+    pub outcome: f32,
+}
+
+impl Sample {
+    /// Little endian binary record :
+    /// decision (u8), nb_players, pos, trick_count, contract, taker, king (u8, 255 if none),
+    /// hand, trick, played, dog (78 u8 each), legal and chosen (u8 length then u8 actions),
+    /// outcome (f32), deal (u32), seed (u8 flag then u64)
+    pub fn write_binary<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let obs = &self.observation;
+        let decision = match self.decision {
+            Decision::Bid => 0,
+            Decision::CallKing => 1,
+            Decision::MakeDog => 2,
+            Decision::Play => 3,
+        };
+        out.write_all(&[
+            decision,
+            obs.nb_players,
+            obs.pos,
+            obs.trick_count,
+            obs.contract.unwrap_or(255),
+            obs.taker.unwrap_or(255),
+            obs.king.unwrap_or(255),
+        ])?;
+        out.write_all(&obs.hand)?;
+        out.write_all(&obs.trick)?;
+        out.write_all(&obs.played)?;
+        out.write_all(&obs.dog)?;
+        out.write_all(&[self.legal.len() as u8])?;
+        out.write_all(&self.legal)?;
+        out.write_all(&[self.chosen.len() as u8])?;
+        out.write_all(&self.chosen)?;
+        out.write_all(&self.outcome.to_le_bytes())?;
+        out.write_all(&self.deal.to_le_bytes())?;
+        out.write_all(&[self.seed.is_some() as u8])?;
+        out.write_all(&self.seed.unwrap_or(0).to_le_bytes())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Jsonl,
+    Binary,
+}
+
+pub struct Recorder {
+    /// Shared by the recorders of the players writing to the same file
+    out: Arc<Mutex<Box<dyn Write + Send>>>,
+    format: Format,
+    seed: Option<u64>,
+    deal: u32,
+    /// Samples of the current deal, waiting for its outcome
+    pending: Vec<Sample>,
+}
+
+impl Recorder {
+    pub fn new(out: Box<dyn Write + Send>, format: Format, seed: Option<u64>) -> Self {
+        Recorder {
+            out: Arc::new(Mutex::new(out)),
+            format,
+            seed,
+            deal: 0,
+            pending: vec![],
+        }
+    }
+
+    /// Appends to the file `path`, binary if its extension is `.bin`
+    pub fn create(path: &str, seed: Option<u64>) -> io::Result<Self> {
+        let format = if Path::new(path)
+            .extension()
+            .map_or(false, |ext| ext == "bin")
+        {
+            Format::Binary
+        } else {
+            Format::Jsonl
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Recorder::new(Box::new(BufWriter::new(file)), format, seed))
+    }
+
+    /// Recorder for another player, writing to the same output
+    pub fn share(&self) -> Self {
+        Recorder {
+            out: self.out.clone(),
+            format: self.format,
+            seed: self.seed,
+            deal: 0,
+            pending: vec![],
+        }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        let path = env::var(RECORD_ENV).ok()?;
+        Recorder::create(&path, None)
+            .map_err(|e| error!("Can't open record file {}: {}", path, e))
+            .ok()
+    }
+
+    /// Starts the deal number `deal`. The samples of a previous deal without result (everybody
+    /// passed) are written with a null outcome.
+    pub fn start_deal(&mut self, deal: u32) {
+        if !self.pending.is_empty() {
+            self.end_deal(0.0);
+        }
+        self.deal = deal;
+    }
+
+    pub fn record(
+        &mut self,
+        decision: Decision,
+        observation: Observation,
+        legal: Vec<u8>,
+        chosen: Vec<u8>,
+    ) {
+        // A new bid with samples still pending : the previous deal was cancelled
+        if decision == Decision::Bid && !self.pending.is_empty() {
+            self.end_deal(0.0);
+        }
+        self.pending.push(Sample {
+            seed: self.seed,
+            deal: self.deal,
+            decision,
+            observation,
+            legal,
+            chosen,
+            outcome: 0.0,
+        });
+    }
+
+    /// Writes the samples of the deal with the player score
+    pub fn end_deal(&mut self, outcome: f32) {
+        let result = self.write_pending(outcome);
+        if let Err(e) = result {
+            error!("Can't write training samples: {}", e);
+        }
+        self.pending.clear();
+        self.deal += 1;
+    }
+
+    fn write_pending(&mut self, outcome: f32) -> io::Result<()> {
+        let mut out = self.out.lock().unwrap();
+        for sample in self.pending.iter_mut() {
+            sample.outcome = outcome;
+            match self.format {
+                Format::Jsonl => {
+                    serde_json::to_writer(&mut *out, sample)?;
+                    out.write_all(b"\n")?;
+                }
+                Format::Binary => sample.write_binary(&mut *out)?,
+            }
+        }
+        out.flush()
+    }
+}
+
+#[test]
+fn test_card_index() {
+    use tarotgame::cards::{Rank, Suit};
+    let mut indexes: Vec<u8> = (0..82)
+        .filter(|id| *id < 66 || *id >= 70)
+        .map(|id| card_index(Card::from_id(id)))
+        .collect();
+    indexes.sort();
+    assert_eq!(indexes, (0..CARDS_COUNT as u8).collect::<Vec<u8>>());
+    assert_eq!(card_index(Card::new(Suit::Trump, Rank::Rank1)), 56);
+    assert_eq!(card_index(Card::excuse()), 77);
+}
@@ -1,6 +1,7 @@
 // use std::thread;
 use rayon::prelude::*;
 
+use log::warn;
 use std::time;
 use uuid::Uuid;
 use url::Url;
@@ -21,8 +22,14 @@ pub fn simulate(count: usize) {
     // let game_executor = GameExecutor::new();
 
     //----- BOTS ----------
-    let in_outs: Vec<Box<TarotWebSocket>> = (0..count).map(|_| {
-        Box::new(TarotWebSocket::new(str_websocket))
+    let in_outs: Vec<Box<TarotWebSocket>> = (0..count).filter_map(|_| {
+        match TarotWebSocket::new(str_websocket) {
+            Ok(in_out) => Some(Box::new(in_out)),
+            Err(e) => {
+                warn!("Can't connect to {}: {}", str_websocket, e);
+                None
+            }
+        }
     }).collect();
 
     in_outs.into_par_iter().enumerate().for_each(|(i, in_out)| {
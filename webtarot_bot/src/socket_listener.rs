@@ -1,16 +1,18 @@
-// use tungstenite::connect;
-use tungstenite::stream::Stream;
-// use tungstenite::protocol::WebSocket;
-
 use std::time;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
-use std::io::{BufRead, BufReader};
-use std::os::unix::net::{UnixStream,UnixListener};
-use url::Url;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+use crate::engine::Engine;
+use crate::supervisor::{Supervisor, MAX_BOTS};
+use webtarot_protocol::DEFAULT_BOT_DELAY;
 
-use crate::in_out_websocket::TarotWebSocket;
+/// Overrides the maximum number of bots running at the same time
+pub const MAX_BOTS_ENV: &str = "WEBTAROT_BOT_MAX";
 
 struct NickNamer {
     parties: HashMap<String, u8>,
@@ -31,50 +33,69 @@ impl NickNamer {
     }
 }
 
+/// Listens to commands on the unix socket `str_socket`, one per line :
+/// - a join code, optionally followed by the bot delay in milliseconds : starts a bot joining
+///   this game. The bot then follows the delay of the game settings.
+/// - `SEAT <join code> <token>` : starts a bot taking the seat of an absent player, offered by
+///   the server with this token
+/// - `LIST` : lists the running bots (id, nickname, join code, status), ends with `END`
+/// - `KILL <id>` : makes a bot leave its game
+/// - `SHUTDOWN` : makes all bots leave and stops listening
+///
+/// Called from a runtime, as in the server, the listener is spawned on it and `start` returns at
+/// once. Otherwise it runs on a runtime of its own until it is shut down.
 pub fn start(str_socket: &str , str_websocket: &str) {
-    let bot_delay = time::Duration::from_millis(6000); // 6s
-    // let bot_delay = time::Duration::from_millis(0);
-    let socket_file = std::path::Path::new(str_socket);
+    let listening = listen(String::from(str_socket), String::from(str_websocket));
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn(listening);
+        }
+        Err(_) => {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .expect("Can't start bots runtime");
+            runtime.block_on(listening);
+        }
+    }
+}
+
+async fn listen(str_socket: String, str_websocket: String) {
+    let socket_file = std::path::Path::new(&str_socket);
     if socket_file.exists() {
         // remove dangling socket file
         std::fs::remove_file(&socket_file).unwrap();
     }
     if let Ok(listener) = UnixListener::bind(socket_file) {
         println!("Bots listening to socket {}", str_socket);
-        let nicknamer = Mutex::new(NickNamer::new()); 
-        let nicknamer = Arc::new(Mutex::new(NickNamer::new())); 
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
-                    let stream = BufReader::new(stream);
-                    // let url_websocket = format!("{}/ws/new_new", str_websocket);
-                    for line in stream.lines() {
-                        let code = line.unwrap();
-                        if code == "SHUTDOWN" {
-                            break
-                        } else {
-                            let my_str_websocket = String::from(str_websocket);
-                            let my_nicknamer = nicknamer.clone();
-                            let my_delay = bot_delay.clone();
-                            std::thread::spawn(move || {
-                                let nickname = my_nicknamer.lock().unwrap().get_nickname(&code);
-                                let in_out = Box::new(TarotWebSocket::new(&my_str_websocket));
-                                let mut bot = crate::player::Player::new(in_out, code.to_string(), nickname, my_delay);
-                                bot.play();
-                                // we clean the nicknamer as soon as the first bot quits the party
-                                my_nicknamer.lock().unwrap().delete_party(&code);
-                                // println!("a bot finished");
-                            });
-                        }
+        let max_bots = std::env::var(MAX_BOTS_ENV).ok()
+            .and_then(|max| max.parse().ok())
+            .unwrap_or(MAX_BOTS);
+        let supervisor = Supervisor::new(max_bots);
+        let nicknamer = Arc::new(Mutex::new(NickNamer::new()));
+        let (shutdown_sender, mut shutdown) = watch::channel(false);
+        loop {
+            tokio::select! {
+                stream = listener.accept() => match stream {
+                    Ok((stream, _)) => {
+                        let client = Client {
+                            supervisor: supervisor.clone(),
+                            nicknamer: nicknamer.clone(),
+                            str_websocket: str_websocket.clone(),
+                            shutdown: shutdown_sender.clone(),
+                        };
+                        tokio::spawn(client.serve(stream));
                     }
-                }
-                Err(err) => {
-                    println!("Error: {}", err);
-                    break;
-                }
+                    Err(err) => {
+                        println!("Error: {}", err);
+                        break;
+                    }
+                },
+                _ = shutdown.changed() => break,
             }
         }
         println!("Shuting down bot listener");
+        supervisor.kill_all();
         drop(listener);
         if socket_file.exists() {
             std::fs::remove_file(&socket_file).unwrap();
@@ -83,3 +104,132 @@ pub fn start(str_socket: &str , str_websocket: &str) {
         println!("couldn't connect to socket {}", str_socket);
     }
 }
+
+struct Client {
+    supervisor: Supervisor,
+    nicknamer: Arc<Mutex<NickNamer>>,
+    str_websocket: String,
+    shutdown: watch::Sender<bool>,
+}
+
+impl Client {
+    async fn serve(self, stream: UnixStream) {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let answer = match words.as_slice() {
+                ["SHUTDOWN"] => {
+                    let _ = self.shutdown.send(true);
+                    break
+                }
+                ["LIST"] => {
+                    let mut answer: String = self.supervisor.list().iter()
+                        .map(|bot| format!("{}\n", bot))
+                        .collect();
+                    answer.push_str("END");
+                    answer
+                }
+                ["KILL", id] => {
+                    match id.parse() {
+                        Ok(id) if self.supervisor.kill(id) => String::from("OK"),
+                        _ => format!("ERROR unknown bot {}", id),
+                    }
+                }
+                ["SEAT", code, token] => {
+                    match Uuid::parse_str(token) {
+                        Ok(token) => {
+                            let bot_delay = time::Duration::from_millis(DEFAULT_BOT_DELAY as u64);
+                            self.spawn_bot(code.to_string(), bot_delay, Some(token))
+                        }
+                        Err(_) => String::from("ERROR bad token"),
+                    }
+                }
+                [code, delay @ ..] if delay.len() <= 1 => {
+                    let bot_delay = match delay.first().map(|delay| delay.parse()) {
+                        None => time::Duration::from_millis(DEFAULT_BOT_DELAY as u64),
+                        Some(Ok(ms)) => time::Duration::from_millis(ms),
+                        Some(Err(_)) => {
+                            if writer.write_all(b"ERROR bad delay\n").await.is_err() {
+                                break
+                            }
+                            continue
+                        }
+                    };
+                    self.spawn_bot(code.to_string(), bot_delay, None)
+                }
+                _ => String::from("ERROR unknown command"),
+            };
+            if writer.write_all(format!("{}\n", answer).as_bytes()).await.is_err() {
+                break
+            }
+        }
+    }
+
+    /// Starts a bot joining the game `code`, returns the answer to the command
+    fn spawn_bot(&self, code: String, bot_delay: time::Duration, seat_token: Option<Uuid>) -> String {
+        let nickname = self.nicknamer.lock().unwrap().get_nickname(&code);
+        let spawned = self.supervisor.spawn_bot(&self.str_websocket, &code, nickname, bot_delay, |bot| {
+            if let Some(engine) = Engine::from_env() {
+                bot.set_engine(engine);
+            }
+            if let Some(token) = seat_token {
+                bot.set_seat_token(token);
+            }
+        });
+        match spawned {
+            Ok((id, handle)) => {
+                let nicknamer = self.nicknamer.clone();
+                tokio::spawn(async move {
+                    let _ = handle.await;
+                    // we clean the nicknamer as soon as the first bot quits the party
+                    nicknamer.lock().unwrap().delete_party(&code);
+                });
+                format!("OK {}", id)
+            }
+            Err(err) => format!("ERROR {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_start_from_runtime() {
+        let socket = std::env::temp_dir()
+            .join(format!("webtarot-bots-{}.sock", std::process::id()));
+        let str_socket = socket.to_str().unwrap();
+        // returns at once, the listener runs on the runtime of the test
+        start(str_socket, "ws://127.0.0.1:9");
+        let mut stream = loop {
+            match UnixStream::connect(&socket).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(time::Duration::from_millis(10)).await,
+            }
+        };
+        let (reader, mut writer) = stream.split();
+        let mut lines = BufReader::new(reader).lines();
+
+        writer.write_all(b"LIST\n").await.unwrap();
+        assert_eq!(Some(String::from("END")), lines.next_line().await.unwrap());
+        // the bot can't reach the server and waits to reconnect
+        writer.write_all(b"ABCD 0\n").await.unwrap();
+        assert_eq!(Some(String::from("OK 1")), lines.next_line().await.unwrap());
+        writer.write_all(b"KILL 1\n").await.unwrap();
+        assert_eq!(Some(String::from("OK")), lines.next_line().await.unwrap());
+        writer.write_all(b"SEAT ABCD 42\n").await.unwrap();
+        assert_eq!(Some(String::from("ERROR bad token")), lines.next_line().await.unwrap());
+        writer
+            .write_all(format!("SEAT ABCD {}\n", Uuid::new_v4()).as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(Some(String::from("OK 2")), lines.next_line().await.unwrap());
+
+        writer.write_all(b"SHUTDOWN\n").await.unwrap();
+        while socket.exists() {
+            tokio::time::sleep(time::Duration::from_millis(10)).await;
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! Runs bots as tasks of an async runtime.
+//!
+//! The supervisor caps the number of concurrent bots, keeps track of their status and allows
+//! to kill them. Each bot task owns its websocket connection : it reconnects with an
+//! exponential backoff when the connection is lost, and stops when its game is over.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use log::{info, warn};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use webtarot_protocol::{Command, Message};
+
+use crate::player::{backoff_delay, InOut, InOutError, Player, MAX_RECONNECTS};
+
+/// Default maximum number of bots running at the same time
+pub const MAX_BOTS: usize = 40;
+/// A bot leaves its game after this time without any message from the server
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+/// How often the bots check whether the absent players had the time to come back
+const ABSENT_CHECK: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BotStatus {
+    Connecting,
+    Playing,
+    /// Waiting before the nth reconnection attempt
+    Reconnecting(u32),
+}
+
+impl fmt::Display for BotStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BotStatus::Connecting => write!(f, "connecting"),
+            BotStatus::Playing => write!(f, "playing"),
+            BotStatus::Reconnecting(attempt) => write!(f, "reconnecting ({})", attempt),
+        }
+    }
+}
+
+struct BotInfo {
+    nickname: String,
+    join_code: String,
+    status: BotStatus,
+    kill: Option<oneshot::Sender<()>>,
+}
+
+/// Commands sent by the player are serialized and queued, the bot task forwards them to the
+/// websocket. Server messages are given to the player directly by the task.
+struct ChannelInOut {
+    commands: mpsc::UnboundedSender<String>,
+}
+
+impl InOut for ChannelInOut {
+    fn read(&mut self) -> Result<Message, InOutError> {
+        unreachable!("server messages are pushed to the player by its task")
+    }
+
+    fn send(&mut self, command: &Command) -> Result<(), InOutError> {
+        let json = serde_json::to_string(command)?;
+        self.commands.send(json).map_err(|_| InOutError::Closed)
+    }
+
+    fn close(&mut self) {}
+}
+
+enum SessionEnd {
+    /// The game is over or the bot has been killed
+    Over,
+    ConnectionLost,
+}
+
+#[derive(Clone)]
+pub struct Supervisor {
+    bots: Arc<Mutex<HashMap<usize, BotInfo>>>,
+    next_id: Arc<AtomicUsize>,
+    slots: Arc<Semaphore>,
+}
+
+impl Supervisor {
+    pub fn new(max_bots: usize) -> Self {
+        Supervisor {
+            bots: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(AtomicUsize::new(1)),
+            slots: Arc::new(Semaphore::new(max_bots)),
+        }
+    }
+
+    /// Starts a bot joining the game `join_code`. Fails if too many bots are already running.
+    /// Must be called from within the runtime.
+    pub fn spawn_bot(
+        &self,
+        str_websocket: &str,
+        join_code: &str,
+        nickname: String,
+        delay: Duration,
+        setup: impl FnOnce(&mut Player),
+    ) -> Result<(usize, JoinHandle<()>), String> {
+        let permit = self
+            .slots
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| String::from("too many bots"))?;
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (commands_sender, commands) = mpsc::unbounded_channel();
+        let in_out = Box::new(ChannelInOut {
+            commands: commands_sender,
+        });
+        let mut player = Player::new(in_out, join_code.to_string(), nickname.clone(), delay);
+        setup(&mut player);
+
+        let (kill_sender, kill) = oneshot::channel();
+        self.bots.lock().unwrap().insert(
+            id,
+            BotInfo {
+                nickname,
+                join_code: join_code.to_string(),
+                status: BotStatus::Connecting,
+                kill: Some(kill_sender),
+            },
+        );
+
+        let supervisor = self.clone();
+        let str_websocket = str_websocket.to_string();
+        let handle = tokio::spawn(async move {
+            supervisor
+                .run_bot(id, player, commands, &str_websocket, kill)
+                .await;
+            supervisor.bots.lock().unwrap().remove(&id);
+            drop(permit);
+        });
+        Ok((id, handle))
+    }
+
+    /// One line per running bot : id, nickname, join code and status
+    pub fn list(&self) -> Vec<String> {
+        let bots = self.bots.lock().unwrap();
+        let mut ids: Vec<&usize> = bots.keys().collect();
+        ids.sort();
+        ids.into_iter()
+            .map(|id| {
+                let bot = &bots[id];
+                format!("{} {} {} {}", id, bot.nickname, bot.join_code, bot.status)
+            })
+            .collect()
+    }
+
+    /// Asks the bot to leave its game
+    pub fn kill(&self, id: usize) -> bool {
+        let kill = self
+            .bots
+            .lock()
+            .unwrap()
+            .get_mut(&id)
+            .and_then(|bot| bot.kill.take());
+        kill.map(|kill| kill.send(()).is_ok()).unwrap_or(false)
+    }
+
+    pub fn kill_all(&self) {
+        let ids: Vec<usize> = self.bots.lock().unwrap().keys().cloned().collect();
+        for id in ids {
+            self.kill(id);
+        }
+    }
+
+    fn set_status(&self, id: usize, status: BotStatus) {
+        if let Some(bot) = self.bots.lock().unwrap().get_mut(&id) {
+            bot.status = status;
+        }
+    }
+
+    async fn run_bot(
+        &self,
+        id: usize,
+        mut player: Player,
+        mut commands: mpsc::UnboundedReceiver<String>,
+        str_websocket: &str,
+        mut kill: oneshot::Receiver<()>,
+    ) {
+        let mut attempt = 0;
+        loop {
+            if !player.has_joined() {
+                // new session : start from the beginning
+                while commands.try_recv().is_ok() {}
+                player.start();
+            }
+            let url = format!("{}/ws/{}", str_websocket, player.websocket_path());
+            self.set_status(id, BotStatus::Connecting);
+            let connected = tokio::select! {
+                connected = connect_async(url.as_str()) => connected,
+                _ = &mut kill => break,
+            };
+            let end = match connected {
+                Ok((socket, _)) => {
+                    attempt = 0;
+                    self.set_status(id, BotStatus::Playing);
+                    let (back, end) = play_session(player, socket, &mut commands, &mut kill).await;
+                    player = back;
+                    end
+                }
+                Err(e) => {
+                    warn!("{}: can't connect to {}: {}", player.nickname(), url, e);
+                    SessionEnd::ConnectionLost
+                }
+            };
+            if let SessionEnd::Over = end {
+                break;
+            }
+
+            attempt += 1;
+            if attempt > MAX_RECONNECTS {
+                warn!(
+                    "{}: giving up after {} attempts",
+                    player.nickname(),
+                    attempt - 1
+                );
+                break;
+            }
+            self.set_status(id, BotStatus::Reconnecting(attempt));
+            tokio::select! {
+                _ = tokio::time::sleep(backoff_delay(attempt)) => {},
+                _ = &mut kill => break,
+            };
+        }
+        info!("{}: bot stopped", player.nickname());
+    }
+}
+
+/// Runs the player on a blocking thread, as he may sleep to let humans see what happens, and
+/// gives him back with the result
+async fn run_blocking<T, F>(mut player: Player, decide: F) -> (Player, T)
+where
+    T: Send + 'static,
+    F: FnOnce(&mut Player) -> T + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        let result = decide(&mut player);
+        (player, result)
+    })
+    .await
+    .expect("bot player panicked")
+}
+
+async fn play_session(
+    mut player: Player,
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    commands: &mut mpsc::UnboundedReceiver<String>,
+    kill: &mut oneshot::Receiver<()>,
+) -> (Player, SessionEnd) {
+    let (mut sink, mut stream) = socket.split();
+    let idle = tokio::time::sleep(IDLE_TIMEOUT);
+    tokio::pin!(idle);
+    let mut absent_check = tokio::time::interval(ABSENT_CHECK);
+    let end = loop {
+        tokio::select! {
+            Some(json) = commands.recv() => {
+                if sink.send(WsMessage::Text(json)).await.is_err() {
+                    break SessionEnd::ConnectionLost;
+                }
+            }
+            _ = &mut idle => {
+                info!("{}: nothing happened for too long, leaving", player.nickname());
+                leave(&mut sink).await;
+                break SessionEnd::Over;
+            }
+            // nothing is sent while the game waits for an absent player
+            _ = absent_check.tick() => {
+                player = run_blocking(player, |player| player.check_absent_players()).await.0;
+            }
+            received = stream.next() => match received {
+                Some(Ok(WsMessage::Text(text))) => {
+                    idle.as_mut().reset(tokio::time::Instant::now() + IDLE_TIMEOUT);
+                    match serde_json::from_str::<Message>(&text) {
+                        Ok(message) => {
+                            let (back, over) = run_blocking(player, move |player| {
+                                player.handle_server_message(message)
+                            })
+                            .await;
+                            player = back;
+                            if over {
+                                break SessionEnd::Over;
+                            }
+                        }
+                        Err(e) => warn!("{}: can't parse server message: {}", player.nickname(), e),
+                    }
+                }
+                // pings are answered by tungstenite
+                Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => {
+                    break SessionEnd::ConnectionLost;
+                }
+                Some(Ok(_)) => {}
+            },
+            _ = &mut *kill => {
+                leave(&mut sink).await;
+                break SessionEnd::Over;
+            }
+        }
+    };
+    if let SessionEnd::Over = end {
+        // send the last commands (ie. leaving the game) before closing
+        while let Ok(json) = commands.try_recv() {
+            let _ = sink.send(WsMessage::Text(json)).await;
+        }
+        let _ = sink.close().await;
+    }
+    (player, end)
+}
+
+async fn leave<S>(sink: &mut S)
+where
+    S: futures::Sink<WsMessage> + Unpin,
+{
+    if let Ok(json) = serde_json::to_string(&Command::LeaveGame) {
+        let _ = sink.send(WsMessage::Text(json)).await;
+    }
+}
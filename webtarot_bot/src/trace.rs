@@ -0,0 +1,146 @@
+//! Decision traces : why did the bot play what it played ?
+//!
+//! Each decision (bid, king call, dog, card) produces a `DecisionTrace` holding the rule which
+//! fired, the candidates considered and what the bot believed about the other players at that
+//! time. Traces can be appended as JSON lines to a log file and/or sent to the game chat.
+//!
+//! Configuration is read from the environment so that bots started by the server socket
+//! listener can be traced too :
+//! - `WEBTAROT_BOT_TRACE` : path of the JSON lines log file
+//! - `WEBTAROT_BOT_TRACE_CHAT` : if set, a one line summary is sent to the game chat
+
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+use log::{debug, error};
+use serde::Serialize;
+
+pub const TRACE_LOG_ENV: &str = "WEBTAROT_BOT_TRACE";
+pub const TRACE_CHAT_ENV: &str = "WEBTAROT_BOT_TRACE_CHAT";
+
+/// Kind of decision taken by the bot
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    Bid,
+    CallKing,
+    MakeDog,
+    Play,
+}
+
+impl Decision {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Decision::Bid => "bid",
+            Decision::CallKing => "call king",
+            Decision::MakeDog => "dog",
+            Decision::Play => "play",
+        }
+    }
+}
+
+/// What the bot thinks it knows about a player
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PlayerBeliefs {
+    pub pos: usize,
+    pub is_taker: bool,
+    pub in_taker_team: Option<bool>,
+    /// Suits the player is known not to have anymore
+    pub void_suits: Vec<String>,
+}
+
+/// State of the bot knowledge when the decision was taken
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct Beliefs {
+    pub teams_known: bool,
+    pub teams_known_by_all: bool,
+    pub players: Vec<PlayerBeliefs>,
+    /// Suits which an opponent can cut
+    pub cut_suits: Vec<String>,
+    /// Number of trumps not yet seen
+    pub trumps_left: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DecisionTrace {
+    pub player: String,
+    pub decision: Decision,
+    pub trick_count: usize,
+    /// Name of the rule which produced the choice
+    pub rule: &'static str,
+    /// Options the bot could choose from (legal cards, possible bids...)
+    pub candidates: Vec<String>,
+    pub choice: String,
+    /// Free-form complement (ie. hand evaluation points)
+    pub detail: Option<String>,
+    pub beliefs: Beliefs,
+}
+
+impl DecisionTrace {
+    /// One line summary suitable for the game chat
+    pub fn summary(&self) -> String {
+        let detail = self
+            .detail
+            .as_ref()
+            .map(|d| format!(" ({})", d))
+            .unwrap_or_default();
+        format!(
+            "{} {} [{}]{} among {}",
+            self.decision.to_str(),
+            self.choice,
+            self.rule,
+            detail,
+            self.candidates.join(" ")
+        )
+    }
+}
+
+/// Dispatches decision traces to their configured outputs
+pub struct Tracer {
+    log: Option<File>,
+    to_chat: bool,
+}
+
+impl Tracer {
+    /// A tracer which only forwards traces to the `log` crate at debug level
+    pub fn disabled() -> Self {
+        Tracer {
+            log: None,
+            to_chat: false,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let log = env::var(TRACE_LOG_ENV).ok().and_then(|path| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(|e| error!("Can't open bot trace file {}: {}", path, e))
+                .ok()
+        });
+        Tracer {
+            log,
+            to_chat: env::var(TRACE_CHAT_ENV).is_ok(),
+        }
+    }
+
+    /// Records the trace, returns the chat text to send if chat output is enabled
+    pub fn record(&mut self, trace: &DecisionTrace) -> Option<String> {
+        debug!("{}: {}", trace.player, trace.summary());
+        if let Some(file) = self.log.as_mut() {
+            // serializing our own types can't fail
+            let mut line = serde_json::to_string(trace).unwrap();
+            line.push('\n');
+            if let Err(e) = file.write_all(line.as_bytes()) {
+                error!("Can't write bot trace: {}", e);
+            }
+        }
+        if self.to_chat {
+            Some(trace.summary())
+        } else {
+            None
+        }
+    }
+}
@@ -8,9 +8,10 @@ use web_sys::MessageEvent;
 use yew::Callback;
 use weblog::*;
 use gloo_storage::{LocalStorage, Storage};
+use gloo_timers::callback::Timeout;
 
 use crate::protocol::{Command, Message};
-use crate::gprotocol::{PlayerInfo, GameInfo};
+use crate::gprotocol::{PlayerInfo, GameInfo, ProtocolError, ProtocolErrorKind};
 
 fn get_websocket_location() -> String {
     let player_info: Option<PlayerInfo> = LocalStorage::get("webtarot.self").ok();
@@ -26,9 +27,23 @@ fn get_websocket_location() -> String {
     )
 }
 
+/// Maximum delay between two reconnection attempts, in milliseconds
+const MAX_RECONNECT_DELAY: u32 = 30_000;
+
+/// Delay before the reconnection attempt number `attempt` : 1s, 2s, 4s...
+fn reconnect_delay(attempt: u32) -> u32 {
+    1000u32
+        .saturating_mul(1 << attempt.saturating_sub(1).min(15))
+        .min(MAX_RECONNECT_DELAY)
+}
+
 struct ApiState {
     ws: Option<web_sys::WebSocket>,
     subscribers: HashMap<usize, Callback<Message>>,
+    status_subscribers: HashMap<usize, Callback<bool>>,
+    reconnect_attempts: u32,
+    /// A message from the server could not be read
+    incompatible: bool,
     next_id: usize,
     _on_message: Option<Closure<dyn FnMut(MessageEvent)>>,
     _on_open: Option<Closure<dyn FnMut()>>,
@@ -40,6 +55,9 @@ impl ApiState {
         Self {
             ws: None,
             subscribers: HashMap::new(),
+            status_subscribers: HashMap::new(),
+            reconnect_attempts: 0,
+            incompatible: false,
             next_id: 0,
             _on_message: None,
             _on_open: None,
@@ -69,9 +87,9 @@ fn ensure_connected() {
         let api_msg = api_rc.clone();
         let on_message = Closure::wrap(Box::new(move |e: MessageEvent| {
             if let Some(text) = e.data().as_string() {
-                let state = api_msg.borrow();
                 match serde_json::from_str::<Message>(&text) {
                     Ok(msg) => {
+                        let state = api_msg.borrow();
                         for cb in state.subscribers.values() {
                             cb.emit(msg.clone());
                         }
@@ -79,6 +97,18 @@ fn ensure_connected() {
                     Err(err) => {
                         let err_str = format!("websocket parse error: {:?}", err);
                         console_error!(err_str);
+                        // Most likely a newer server : tell the player once
+                        let first = !std::mem::replace(&mut api_msg.borrow_mut().incompatible, true);
+                        if first {
+                            let msg = Message::Error(ProtocolError::new(
+                                ProtocolErrorKind::InternalError,
+                                "the server has been updated, please reload the page",
+                            ));
+                            let state = api_msg.borrow();
+                            for cb in state.subscribers.values() {
+                                cb.emit(msg.clone());
+                            }
+                        }
                     }
                 }
             }
@@ -88,15 +118,32 @@ fn ensure_connected() {
         let api_open = api_rc.clone();
         let on_open = Closure::wrap(Box::new(move || {
             console_log!("Connected web socket!");
+            api_open.borrow_mut().reconnect_attempts = 0;
             let state = api_open.borrow();
+            for cb in state.status_subscribers.values() {
+                cb.emit(true);
+            }
             for cb in state.subscribers.values() {
                 cb.emit(Message::Connected);
             }
         }) as Box<dyn FnMut()>);
         ws.set_onopen(Some(on_open.as_ref().unchecked_ref()));
 
+        let api_close = api_rc.clone();
         let on_close = Closure::wrap(Box::new(move || {
             console_log!("Lost connection on web socket!");
+            let (delay, status_subscribers) = {
+                let mut state = api_close.borrow_mut();
+                state.ws = None;
+                state.reconnect_attempts += 1;
+                let subscribers: Vec<Callback<bool>> =
+                    state.status_subscribers.values().cloned().collect();
+                (reconnect_delay(state.reconnect_attempts), subscribers)
+            };
+            for cb in status_subscribers {
+                cb.emit(false);
+            }
+            Timeout::new(delay, ensure_connected).forget();
         }) as Box<dyn FnMut()>);
         ws.set_onclose(Some(on_close.as_ref().unchecked_ref()));
 
@@ -132,7 +179,9 @@ impl ApiBridge {
 impl Drop for ApiBridge {
     fn drop(&mut self) {
         GLOBAL_API.with(|api| {
-            api.borrow_mut().subscribers.remove(&self.id);
+            let mut state = api.borrow_mut();
+            state.subscribers.remove(&self.id);
+            state.status_subscribers.remove(&self.id);
         });
     }
 }
@@ -151,4 +200,14 @@ impl Api {
             ApiBridge { id }
         })
     }
+
+    /// Subscribe to WebSocket messages and to the connection status : `false` when the
+    /// connection is lost, `true` when it is back. The connection is reopened automatically.
+    pub fn bridge_with_status(callback: Callback<Message>, on_status: Callback<bool>) -> ApiBridge {
+        let bridge = Self::bridge(callback);
+        GLOBAL_API.with(|api| {
+            api.borrow_mut().status_subscribers.insert(bridge.id, on_status);
+        });
+        bridge
+    }
 }
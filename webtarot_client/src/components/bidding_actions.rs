@@ -6,6 +6,7 @@ use yew::{html, Callback, Component, Context, Html, Properties};
 use tr::tr;
 
 use crate::protocol::GameStateSnapshot;
+use crate::utils::target_name;
 use tarotgame::bid;
 
 pub enum Msg {
@@ -88,7 +89,7 @@ impl Component for BiddingActions {
                         .map(|bidtarget| {
                             html! {
                                 <button onclick={ctx.link().callback(move |_| Msg::Bid(bidtarget))}>
-                                    {format!("{}", bidtarget.to_str())}
+                                    { target_name(bidtarget) }
                                 </button>
                             }
                     })
@@ -0,0 +1,123 @@
+use std::str::FromStr;
+
+use wasm_bindgen::JsCast;
+use web_sys::HtmlInputElement;
+use yew::{html, Callback, Component, Context, Html, Properties};
+use tr::tr;
+
+use tarotgame::cards;
+
+pub enum Msg {
+    SetCards(usize, String),
+    Submit,
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    /// Nicknames of the players, by position
+    pub players: Vec<String>,
+    pub on_set_hands: Callback<(Vec<cards::Hand>, cards::Hand)>,
+}
+
+/// Hands and dog of the next deal, typed by the host of a training table
+pub struct HandsSetup {
+    /// Cards of each seat, the dog being the last one, as typed: 7H KS 21T ET...
+    texts: Vec<String>,
+    error: Option<String>,
+}
+
+/// Cards separated by spaces or commas
+fn parse_hand(text: &str) -> Result<cards::Hand, String> {
+    let mut hand = cards::Hand::new();
+    for name in text.split(|c: char| c.is_whitespace() || c == ',').filter(|name| !name.is_empty()) {
+        let card = cards::Card::from_str(name).map_err(|_| tr!("Unknown card: {0}", name))?;
+        hand.add(card);
+    }
+    Ok(hand)
+}
+
+impl HandsSetup {
+    fn hands(&self) -> Result<(Vec<cards::Hand>, cards::Hand), String> {
+        let mut hands = self.texts.iter().map(|text| parse_hand(text)).collect::<Result<Vec<_>, _>>()?;
+        let dog = hands.pop().unwrap_or_default();
+        tarotgame::check_hands(&hands, dog, hands.len())?;
+        Ok((hands, dog))
+    }
+}
+
+impl Component for HandsSetup {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        HandsSetup {
+            texts: vec![String::new(); ctx.props().players.len() + 1],
+            error: None,
+        }
+    }
+
+    fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+        self.texts.resize(ctx.props().players.len() + 1, String::new());
+        true
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::SetCards(seat, text) => {
+                if let Some(cards) = self.texts.get_mut(seat) {
+                    *cards = text;
+                }
+            }
+            Msg::Submit => match self.hands() {
+                Ok(hands) => {
+                    self.error = None;
+                    ctx.props().on_set_hands.emit(hands);
+                }
+                Err(error) => {
+                    self.error = Some(error);
+                }
+            },
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let seats = ctx.props().players.iter().cloned().chain(Some(tr!("Dog")));
+        html! {
+            <div class="hands-setup">
+                <h2>{ tr!("Hands of the next deal") }</h2>
+                <p class="explanation">{ tr!("Cards are written 7H, KS, 21T, ET for the excuse") }</p>
+                <table>
+                { seats.enumerate().map(|(seat, name)| {
+                    let count = parse_hand(&self.texts[seat]).map_or(0, |hand| hand.size());
+                    html! {
+                        <tr>
+                            <td>{ name }</td>
+                            <td>
+                                <input value={self.texts[seat].clone()} size="60"
+                                    oninput={ctx.link().callback(move |e: web_sys::InputEvent| {
+                                        let input: HtmlInputElement = e.target().unwrap().unchecked_into();
+                                        Msg::SetCards(seat, input.value())
+                                    })} />
+                            </td>
+                            <td>{ tr!("{0} cards", count) }</td>
+                        </tr>
+                    }
+                }).collect::<Html>() }
+                </table>
+                {
+                    if let Some(ref error) = self.error {
+                        html! {
+                            <p class="error">{tr!("Error: {0}", error)}</p>
+                        }
+                    } else {
+                        html!{}
+                    }
+                }
+                <div class="toolbar">
+                    <button class="primary" onclick={ctx.link().callback(|_| Msg::Submit)}>{ tr!("Deal these hands") }</button>
+                </div>
+            </div>
+        }
+    }
+}
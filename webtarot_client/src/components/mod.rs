@@ -4,3 +4,5 @@ pub mod bidding_actions;
 pub mod call_king_action;
 pub mod scores;
 pub mod announces;
+pub mod tricks_review;
+pub mod hands_setup;
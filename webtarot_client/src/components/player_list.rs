@@ -84,6 +84,9 @@ impl Component for PlayerList {
                         if is_my_turn {
                             player_classes.push("current-player");
                         }
+                        if self.game_state.absent_at(state.pos).is_some() {
+                            player_classes.push("player-absent");
+                        }
                         player_classes.push(
                             match state.role {
                                 PlayerRole::Taker => "role-taker",
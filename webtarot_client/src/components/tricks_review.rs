@@ -0,0 +1,76 @@
+use yew::{html, Component, Context, Html, Properties};
+
+use tarotgame::trick;
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    /// Nicknames of the players, by position
+    pub players: Vec<String>,
+    pub tricks: Vec<trick::Trick>,
+    pub language: String,
+}
+
+/// Tricks already played in the deal, with the leader and the winner of each one
+pub struct TricksReview {
+    players: Vec<String>,
+    tricks: Vec<trick::Trick>,
+    language: String,
+}
+
+impl Component for TricksReview {
+    type Message = ();
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        TricksReview {
+            players: ctx.props().players.clone(),
+            tricks: ctx.props().tricks.clone(),
+            language: ctx.props().language.clone(),
+        }
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, _msg: Self::Message) -> bool {
+        false
+    }
+
+    fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+        self.players = ctx.props().players.clone();
+        self.tricks = ctx.props().tricks.clone();
+        self.language = ctx.props().language.clone();
+        true
+    }
+
+    fn view(&self, _ctx: &Context<Self>) -> Html {
+        html! {
+            <table class="tricks-review">
+                <tr>
+                <th></th>
+                {
+                    self.players.iter().map(|nickname| {
+                        html! {
+                        <th> {nickname} </th>
+                        }
+                    }).collect::<Html>()
+                }
+                </tr>
+                { self.tricks.iter().enumerate().map(|(idx, trick)| {
+                    html! { <tr><td>{idx + 1}</td> {
+                        (0..self.players.len()).map(|pos| {
+                            let mut classes = vec![];
+                            if trick.first.to_n() == pos {
+                                classes.push("leader");
+                            }
+                            if trick.winner.to_n() == pos {
+                                classes.push("winner");
+                            }
+                            let card = trick.cards[pos].map_or("".into(), |card| card.to_locale_string(&self.language));
+                            html! {
+                                <td class={classes.join(" ")}> {card} </td>
+                            }
+                        }).collect::<Html>()
+                    } </tr> }
+                 }).collect::<Html>() }
+            </table>
+        }
+    }
+}
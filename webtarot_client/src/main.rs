@@ -18,6 +18,7 @@ use weblog::*;
 use crate::api::{Api, ApiBridge};
 use crate::gprotocol::{GameInfo, JoinGameCommand, PlayerInfo};
 use crate::protocol::{Command, Message};
+use crate::views::archives::ArchivesPage;
 use crate::views::game::GamePage;
 use crate::views::menu::MenuPage;
 use crate::views::start::StartPage;
@@ -48,12 +49,15 @@ enum AppState {
     Start,
     Authenticated,
     InGame,
+    Archives,
 }
 
 pub enum Msg {
     Ping,
     Authenticated(PlayerInfo),
     GameJoined(GameInfo),
+    ShowArchives,
+    CloseArchives,
     ServerMessage(Message),
 }
 
@@ -131,6 +135,12 @@ impl Component for App {
                 LocalStorage::set(KEY_GAME, &game_info).ok();
                 self.game_info = Some(game_info);
             }
+            Msg::ShowArchives => {
+                self.state = AppState::Archives;
+            }
+            Msg::CloseArchives => {
+                self.state = AppState::Authenticated;
+            }
             Msg::ServerMessage(Message::Connected) => {}
             Msg::ServerMessage(Message::GameLeft) => {
                 self.state = AppState::Start;
@@ -159,9 +169,15 @@ impl Component for App {
                     html! {
                         <MenuPage
                             player_info={self.player_info.as_ref().unwrap().clone()}
-                            on_game_joined={ctx.link().callback(Msg::GameJoined)} />
+                            on_game_joined={ctx.link().callback(Msg::GameJoined)}
+                            on_show_archives={ctx.link().callback(|_| Msg::ShowArchives)} />
                     }
                 },
+                AppState::Archives => html! {
+                    <ArchivesPage
+                        language={self.language.clone().unwrap_or_else(|| String::from("en"))}
+                        on_close={ctx.link().callback(|_| Msg::CloseArchives)} />
+                },
                 AppState::InGame => html! {
                     <GamePage
                         player_info={self.player_info.as_ref().unwrap().clone()}
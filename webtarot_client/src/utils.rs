@@ -1,3 +1,6 @@
+use tarotgame::bid;
+use tr::tr;
+
 pub fn format_join_code(code: &str) -> String {
     let code = code.replace("-", "").to_ascii_uppercase();
     if code.len() > 3 {
@@ -6,3 +9,13 @@ pub fn format_join_code(code: &str) -> String {
         code
     }
 }
+
+/// Name of a contract target in the language of the player
+pub fn target_name(target: bid::Target) -> String {
+    match target {
+        bid::Target::Prise => tr!("Prise"),
+        bid::Target::Garde => tr!("Garde"),
+        bid::Target::GardeSans => tr!("Garde sans"),
+        bid::Target::GardeContre => tr!("Garde contre"),
+    }
+}
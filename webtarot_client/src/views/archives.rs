@@ -0,0 +1,209 @@
+use gloo_net::http::Request;
+use serde::de::DeserializeOwned;
+use tr::tr;
+use uuid::Uuid;
+use yew::{html, Callback, Component, Context, Html, Properties};
+
+use crate::components::scores::Scores;
+use crate::components::tricks_review::TricksReview;
+use crate::protocol::{ArchiveEntry, DealArchive};
+use crate::utils::target_name;
+use crate::views::game::view_small_hand;
+
+/// URL of the archives endpoint, set with `WEBTAROT_ARCHIVES_URL` when building the client.
+/// The default path needs a web server forwarding `/archives` to the archives port.
+const ARCHIVES_URL: &str = match option_env!("WEBTAROT_ARCHIVES_URL") {
+    Some(url) => url,
+    None => "/archives",
+};
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub language: String,
+    pub on_close: Callback<()>,
+}
+
+/// Finished deals of the server, replayed trick by trick
+pub struct ArchivesPage {
+    entries: Vec<ArchiveEntry>,
+    /// Deal being replayed, with the number of tricks already played
+    replay: Option<(DealArchive, usize)>,
+    error: Option<String>,
+}
+
+pub enum Msg {
+    Listed(Vec<ArchiveEntry>),
+    Open(Uuid, usize),
+    Opened(DealArchive),
+    Failed(String),
+    Step(usize),
+    BackToList,
+    Close,
+}
+
+async fn fetch<T: DeserializeOwned>(url: String) -> Result<T, String> {
+    let response = Request::get(&url).send().await.map_err(|e| e.to_string())?;
+    if !response.ok() {
+        return Err(response.status_text());
+    }
+    response.json().await.map_err(|e| e.to_string())
+}
+
+fn format_date(timestamp: u64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map_or(String::new(), |date| date.format("%Y-%m-%d %H:%M UTC").to_string())
+}
+
+impl Component for ArchivesPage {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_future(async {
+            match fetch(ARCHIVES_URL.to_string()).await {
+                Ok(entries) => Msg::Listed(entries),
+                Err(e) => Msg::Failed(e),
+            }
+        });
+        ArchivesPage {
+            entries: vec![],
+            replay: None,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::Listed(entries) => {
+                self.entries = entries;
+            }
+            Msg::Open(game_id, deal) => {
+                let url = format!("{}/{}/{}", ARCHIVES_URL, game_id, deal);
+                ctx.link().send_future(async move {
+                    match fetch(url).await {
+                        Ok(archive) => Msg::Opened(archive),
+                        Err(e) => Msg::Failed(e),
+                    }
+                });
+            }
+            Msg::Opened(archive) => {
+                self.error = None;
+                self.replay = Some((archive, 0));
+            }
+            Msg::Failed(error) => {
+                self.error = Some(error);
+            }
+            Msg::Step(played) => {
+                if let Some((archive, step)) = self.replay.as_mut() {
+                    *step = played.min(archive.tricks.len());
+                }
+            }
+            Msg::BackToList => {
+                self.replay = None;
+            }
+            Msg::Close => {
+                ctx.props().on_close.emit(());
+            }
+        }
+        true
+    }
+
+    fn changed(&mut self, _ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div class="wrapper archives">
+                {
+                    if let Some((archive, step)) = &self.replay {
+                        self.view_replay(ctx, archive, *step)
+                    } else {
+                        self.view_list(ctx)
+                    }
+                }
+                {
+                    if let Some(ref error) = self.error {
+                        html! {
+                            <p class="error">{tr!("Error: {0}", error)}</p>
+                        }
+                    } else {
+                        html!{}
+                    }
+                }
+            </div>
+        }
+    }
+}
+
+impl ArchivesPage {
+    fn view_list(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <>
+                <h1>{ tr!("Past games") }</h1>
+                <div class="toolbar">
+                    <button onclick={ctx.link().callback(|_| Msg::Close)}>{ tr!("Back") }</button>
+                </div>
+                {
+                    if self.entries.is_empty() {
+                        html! { <p class="explanation">{ tr!("No finished deal yet") }</p> }
+                    } else {
+                        html! {
+                            <table class="archives-list">
+                            { self.entries.iter().map(|entry| {
+                                let (game_id, deal) = (entry.game_id, entry.deal);
+                                let taker = entry.players.get(entry.contract.author.to_n()).cloned().unwrap_or_default();
+                                html! {
+                                    <tr onclick={ctx.link().callback(move |_| Msg::Open(game_id, deal))}>
+                                        <td>{ format_date(entry.finished_at) }</td>
+                                        <td>{ tr!("Deal {0}", deal) }</td>
+                                        <td>{ entry.players.join(", ") }</td>
+                                        <td>{ tr!("{0} took a {1}", taker, target_name(entry.contract.target)) }</td>
+                                    </tr>
+                                }
+                            }).collect::<Html>() }
+                            </table>
+                        }
+                    }
+                }
+            </>
+        }
+    }
+
+    fn view_replay(&self, ctx: &Context<Self>, archive: &DealArchive, step: usize) -> Html {
+        let count = archive.tricks.len();
+        let taker = archive.players.get(archive.summary.contract.author.to_n()).cloned().unwrap_or_default();
+        let hands = archive.hands_before(step);
+        html! {
+            <>
+                <h1>{ tr!("Deal {0}", archive.deal) }</h1>
+                <div class="toolbar">
+                    <button onclick={ctx.link().callback(|_| Msg::BackToList)}>{ tr!("Back") }</button>
+                    <button disabled={step == 0} onclick={ctx.link().callback(move |_| Msg::Step(step.saturating_sub(1)))}>{ tr!("Previous trick") }</button>
+                    <span>{ tr!("Trick {0}/{1}", step, count) }</span>
+                    <button disabled={step == count} onclick={ctx.link().callback(move |_| Msg::Step(step + 1))}>{ tr!("Next trick") }</button>
+                </div>
+                <p class="explanation">{ tr!("{0} took a {1}", taker, target_name(archive.summary.contract.target)) }</p>
+                <section class="revealed-hands">
+                { archive.players.iter().zip(hands.iter()).map(|(nickname, hand)| {
+                    view_small_hand(nickname.clone(), hand)
+                }).collect::<Html>() }
+                { view_small_hand(tr!("Dog"), &archive.summary.dog) }
+                </section>
+                <TricksReview
+                    players={archive.players.clone()}
+                    tricks={archive.tricks[..step].to_vec()}
+                    language={ctx.props().language.clone()} />
+                {
+                    if step == count {
+                        html! {
+                            <Scores players={archive.players.clone()} scores={archive.scores.clone()} />
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+            </>
+        }
+    }
+}
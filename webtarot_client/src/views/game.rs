@@ -1,5 +1,4 @@
 
-use std::str::FromStr;
 use std::rc::Rc;
 use std::f32;
 use im_rc::Vector;
@@ -18,17 +17,25 @@ use crate::components::bidding_actions::BiddingActions;
 use crate::components::call_king_action::CallKingAction;
 use crate::components::announces::Announces;
 use crate::components::scores::Scores;
+use crate::components::tricks_review::TricksReview;
+use crate::components::hands_setup::HandsSetup;
 use crate::gprotocol::{GameInfo, PlayerInfo, SendTextCommand};
 
 use crate::protocol::{
     Command, GamePlayerState, GameStateSnapshot, Message, PlayerAction,
     GamePlayCommand,
-    BidCommand, PlayCommand, CallKingCommand, MakeDogCommand, AnnounceCommand,
+    BidCommand, PlayCommand, CallKingCommand, MakeDogCommand, AnnounceCommand, ReplaceAbsentCommand,
+    SetBotDelayCommand, SetHandsCommand, EntropyCommand, ReplayCommand, HelloCommand, PROTOCOL_VERSION,
+    ProtocolError, ErrorCode,
+    SnapshotTracker, StateUpdate, GameEvent, GameView, SpectatorVisibility,
+    AbsentPlayer, ClockSnapshot,
     Turn,
-    PlayEvent,
+    PlayEvent, DealSummary,
 };
-use tarotgame::{bid, deal, cards, Announce};
+use tarotgame::{bid, deal, cards, pos, Announce};
+use tarotgame::fair::{self, FairError, FairProof, Seed};
 use crate::sound_player::SoundPlayer;
+use crate::utils::target_name;
 
 #[derive(Clone, Properties)]
 pub struct Props {
@@ -46,6 +53,10 @@ impl PartialEq for Props {
 pub struct GamePage {
     #[allow(dead_code)]
     _keepalive: Interval,
+    #[allow(dead_code)]
+    _absent_check: Interval,
+    #[allow(dead_code)]
+    _clock_tick: Interval,
     api: ApiBridge,
     game_info: GameInfo,
     player_info: PlayerInfo,
@@ -56,13 +67,46 @@ pub struct GamePage {
     dog: cards::Hand,
     hand: cards::Hand,
     language: String,
-    error: Option<String>,
+    error: Option<ProtocolError>,
     sound_player: SoundPlayer,
     overlay_box: Option<Html>,
     is_waiting: bool,
     update_needs_confirm: bool,
     slam_selected: bool,
     chatbox_visible: bool,
+    tricks_review_visible: bool,
+    /// The host of a training table is giving the hands of the next deal
+    hands_setup_visible: bool,
+    tracker: SnapshotTracker,
+    /// Hands shown to us when we watch the game without a seat
+    spectator_hands: Option<Vec<cards::Hand>>,
+    spectator_visibility: SpectatorVisibility,
+    /// The connection to the server was lost
+    reconnecting: bool,
+    /// Number of the next event expected from the server
+    next_event: Option<u64>,
+    /// Players who left, from the last state received
+    absent: Vec<AbsentPlayer>,
+    replace_absent_after: u32,
+    /// When we noticed that a player left
+    absent_since: Vec<(Uuid, f64)>,
+    /// Time left to the player who must act, and when we received it
+    clock: Option<(ClockSnapshot, f64)>,
+    /// We told the server that the player who must act ran out of time
+    timeout_sent: bool,
+    /// Commitment of the next provably fair deal
+    fair_next: Option<FairDealSeen>,
+    /// Commitment the current deal was dealt from, when we saw it before the deal
+    fair_current: Option<FairDealSeen>,
+}
+
+/// Commitment of a provably fair deal, with our position and the entropy we gave for it, and
+/// our position and hand once it is dealt
+#[derive(Clone, Copy)]
+struct FairDealSeen {
+    commitment: Seed,
+    given: Option<(usize, Seed)>,
+    dealt: Option<(usize, cards::Hand)>,
 }
 
 pub enum Msg {
@@ -80,20 +124,44 @@ pub enum Msg {
     MakeDog,
     ToggleSlam,
     ToggleChatbox,
+    ToggleTricksReview,
+    ToggleHandsSetup,
+    SetHands((Vec<cards::Hand>, cards::Hand)),
     AddToDog(cards::Card),
     AddToHand(cards::Card),
     ServerMessage(Message),
     Announce(Announce),
+    ReplaceAbsent(pos::PlayerPos),
+    CheckAbsent,
+    ClockTick,
+    SetBotDelay(u32),
+    ConnectionStatus(bool),
 }
 
+/// Bot delays the host can choose from, in milliseconds
+const BOT_DELAYS: [u32; 4] = [1000, 3000, 6000, 10000];
+
 impl GamePage {
     pub fn add_chat_message(&mut self, player_id: Uuid, data: ChatLineData) {
         let nickname = self.get_nickname(player_id);
         let chat_line = Rc::new(ChatLine { nickname, data });
-        let idx = self.player_index(player_id);
         let msg = chat_line.text().to_string();
+        let bubble = self.translate_chat(&msg);
+        self.add_chat_line(player_id, chat_line, bubble);
+    }
 
-        self.players_chat[idx] = Some((self.translate_chat(&msg), js_sys::Date::now() + 5000.));
+    /// Shows a game action of a player in the chat and in his speech bubble
+    fn add_event_message(&mut self, player_id: Uuid, text: String) {
+        let nickname = self.get_nickname(player_id);
+        let chat_line = Rc::new(ChatLine { nickname, data: ChatLineData::Text(text.clone()) });
+        self.add_chat_line(player_id, chat_line, text);
+    }
+
+    fn add_chat_line(&mut self, player_id: Uuid, chat_line: Rc<ChatLine>, bubble: String) {
+        // Spectators have no seat to show a speech bubble
+        if let Some(idx) = self.player_index(player_id) {
+            self.players_chat[idx] = Some((bubble, js_sys::Date::now() + 5000.));
+        }
 
         self.chat_log.push_back(chat_line);
         while self.chat_log.len() > 100 {
@@ -138,48 +206,481 @@ impl GamePage {
             .unwrap()
     }
 
-    fn player_index(&self, id: Uuid) -> usize {
+    fn player_index(&self, id: Uuid) -> Option<usize> {
         self.game_state
             .players
             .iter()
             .find(|state| state.player.id == id)
-            .unwrap()
-            .pos.to_n()
+            .map(|state| state.pos.to_n())
     }
 
     fn apply_snapshot(&mut self, snapshot: GameStateSnapshot) {
+        self.clock = snapshot.clock.clone().map(|clock| (clock, js_sys::Date::now()));
+        self.timeout_sent = false;
         self.game_state = Rc::new(snapshot);
         self.dog = self.game_state.deal.initial_dog;
         self.hand = self.game_state.deal.hand;
+        self.contribute_entropy();
+    }
+
+    /// Gives our entropy for the next deal of a provably fair table, once per commitment. A new
+    /// commitment means that the previous one has just been dealt : we keep it with our hand.
+    fn contribute_entropy(&mut self) {
+        let fair = match &self.game_state.fair {
+            Some(fair) => fair.clone(),
+            None => return,
+        };
+        if self.fair_next.map(|seen| seen.commitment) == Some(fair.commitment) {
+            return;
+        }
+        let pos = self.game_state.players.iter()
+            .find(|state| state.player.id == self.player_info.id)
+            .map(|state| state.pos.to_n());
+        self.fair_current = self.fair_next.take().map(|mut seen| {
+            // our hand is the one dealt until the auction is over
+            if let (Some(pos), Turn::Bidding(_)) = (pos, self.game_state.turn) {
+                seen.dealt = Some((pos, self.game_state.deal.hand));
+            }
+            seen
+        });
+        let mut given = None;
+        if let Some(pos) = pos {
+            if fair.contributed.get(pos) == Some(&false) {
+                if let Some(entropy) = random_seed() {
+                    self.api.send(Command::GamePlay(GamePlayCommand::Entropy(EntropyCommand { entropy })));
+                    given = Some((pos, entropy));
+                }
+            }
+        }
+        self.fair_next = Some(FairDealSeen { commitment: fair.commitment, given, dealt: None });
+    }
+
+    /// Checks the proof of the current deal against the commitment we saw before it, our
+    /// entropy and the hand we were dealt, and against all the cards when they are revealed.
+    /// None when we didn't see the commitment.
+    fn check_fair_deal(
+        &self,
+        proof: Option<&FairProof>,
+        revealed: Option<(&[cards::Hand], cards::Hand)>,
+    ) -> Option<Result<(), FairError>> {
+        let proof = proof?;
+        let seen = self.fair_current?;
+        Some(proof.dealt(&seen.commitment, seen.given).and_then(|(hands, dog)| {
+            let own_ok = seen.dealt.map_or(true, |(pos, hand)| hands.get(pos) == Some(&hand));
+            let cards_ok = revealed.map_or(true, |(all, all_dog)| all == hands.as_slice() && all_dog == dog);
+            if own_ok && cards_ok { Ok(()) } else { Err(FairError::Cards) }
+        }))
+    }
+
+    fn view_fair_check(
+        &self,
+        proof: Option<&FairProof>,
+        revealed: Option<(&[cards::Hand], cards::Hand)>,
+    ) -> Html {
+        match self.check_fair_deal(proof, revealed) {
+            Some(Ok(())) => html! {
+                <p class="fair-check">{ tr!("Deal checked: the cards were dealt from the seed committed to and the entropy of the players") }</p>
+            },
+            Some(Err(error)) => {
+                let reason = match error {
+                    FairError::Commitment => tr!("the revealed seed doesn't match the commitment"),
+                    FairError::Entropy => tr!("your entropy was not used"),
+                    FairError::Cards => tr!("the cards were not dealt from the revealed seeds"),
+                };
+                html! {
+                    <p class="fair-check failed">{ tr!("Deal check failed: {0}", reason) }</p>
+                }
+            }
+            None => html! {},
+        }
+    }
+
+    fn view_fair_badge(&self) -> Html {
+        match &self.game_state.fair {
+            Some(fair) => html! {
+                <span class="fair-badge" title={ tr!("Commitment of the next deal: {0}", fair::to_hex(&fair.commitment)) }>
+                    { tr!("provably fair") }
+                </span>
+            },
+            None => html! {},
+        }
+    }
+
+    fn update_absent(&mut self, absent: Vec<AbsentPlayer>, replace_absent_after: u32) {
+        let now = js_sys::Date::now();
+        self.absent_since.retain(|(id, _)| absent.iter().any(|a| a.player.id == *id && a.substitute.is_none()));
+        for player in absent.iter().filter(|a| a.substitute.is_none()) {
+            if !self.absent_since.iter().any(|(id, _)| *id == player.player.id) {
+                self.absent_since.push((player.player.id, now));
+            }
+        }
+        self.absent = absent;
+        self.replace_absent_after = replace_absent_after;
+    }
+
+    fn view_bot_delay(&self, ctx: &Context<Self>) -> Html {
+        let delay = match self.game_state.bot_delay {
+            Some(delay) if self.my_state().pos.to_n() == 0 => delay,
+            _ => return html! {},
+        };
+        // Clicking selects the next delay
+        let next = BOT_DELAYS.iter().find(|d| **d > delay).unwrap_or(&BOT_DELAYS[0]);
+        let next = *next;
+        html! {
+            <button class="btn-link" title={ tr!("Bots delay") } onclick={ctx.link().callback(move |_| Msg::SetBotDelay(next))}>
+                { tr!("bots: {0}s", delay as f32 / 1000.) }
+            </button>
+        }
+    }
+
+    /// Milliseconds left to the player who must act, and whether he is using his bank
+    fn clock_left(&self) -> Option<(pos::PlayerPos, f64, bool)> {
+        let (clock, received) = self.clock.as_ref()?;
+        let remaining = clock.remaining as f64 - (js_sys::Date::now() - received);
+        if remaining > 0. {
+            Some((clock.pos, remaining, false))
+        } else {
+            let bank = clock.banks.get(clock.pos.to_n()).copied().unwrap_or(0) as f64;
+            Some((clock.pos, (bank + remaining).max(0.), true))
+        }
+    }
+
+    fn view_clock(&self) -> Html {
+        let (pos, left, in_bank) = match self.clock_left() {
+            Some(clock) => clock,
+            None => return html! {},
+        };
+        let name = self.game_state.players.iter()
+            .find(|p| p.pos == pos)
+            .map_or(String::new(), |p| p.player.nickname.clone());
+        let seconds = (left / 1000.).ceil();
+        let mut classes = vec!["clock"];
+        if in_bank {
+            classes.push("bank");
+        }
+        if seconds <= 5. {
+            classes.push("urgent");
+        }
+        html! {
+            <div class={classes.join(" ")} title={ tr!("Time left to play") }>
+                { if in_bank {
+                    tr!("{0}: {1}s (extra time)", name, seconds)
+                } else {
+                    tr!("{0}: {1}s", name, seconds)
+                }}
+            </div>
+        }
+    }
+
+    fn view_absent(&self, ctx: &Context<Self>) -> Html {
+        let my_id = self.player_info.id;
+        self.absent.iter().filter(|a| a.substitute.is_none()).map(|absent| {
+            let pos = absent.pos;
+            html! {
+                <div class="absent-player">
+                    { tr!("{0} left the game", absent.player.nickname) }{ " " }
+                    { if absent.invited_by.is_some() {
+                        html! { <span>{ tr!("a bot is coming") }</span> }
+                    } else if absent.votes.contains(&my_id) {
+                        html! { <span>{ tr!("waiting for the other players") }</span> }
+                    } else {
+                        html! { <button class="btn-link" onclick={ctx.link().callback(move |_| Msg::ReplaceAbsent(pos))}>{ tr!("Replace with a bot") }</button> }
+                    }}
+                </div>
+            }
+        }).collect::<Html>()
+    }
+
+    /// Errors and game results, waiting for the player to close them
+    fn view_notifications(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <>
+                { if let Some(error) = &self.error {
+                    let error_str = self.translate_error(&error);
+                    html! {
+                  <div class="notify-wrapper">
+                    <div class="error notify">
+                        <div>
+                        { error_str }
+                        </div>
+                        <div class="toolbar">
+                            <button class="btn-error" onclick={ctx.link().callback(|_| Msg::CloseError)}>{"Ok"}</button>
+                        </div>
+                      </div>
+                    </div>
+                }} else { html! {} }}
+
+                { if self.overlay_box.is_some() { html! {
+                  <div class="notify-wrapper">
+                    <div class="notify wrapper">
+                        { self.display_overlay_box() }
+                        <div class="toolbar">
+                            <button class="primary" onclick={ctx.link().callback(|_| Msg::Continue)}>{"Ok"}</button>
+                        </div>
+                    </div>
+                </div>
+                }} else { html! {} }}
+
+                { self.view_tricks_review(ctx) }
+                { self.view_hands_setup(ctx) }
+            </>
+        }
+    }
+
+    /// Shown when the game rules let the players look at the previous tricks
+    fn view_tricks_review_button(&self, ctx: &Context<Self>) -> Html {
+        if self.game_state.deal.tricks.is_empty() {
+            return html! {};
+        }
+        html! {
+            <button class="btn-link" onclick={ctx.link().callback(|_| Msg::ToggleTricksReview)}>{ tr!("review tricks") }</button>
+        }
+    }
+
+    fn view_tricks_review(&self, ctx: &Context<Self>) -> Html {
+        if !self.tricks_review_visible || self.game_state.deal.tricks.is_empty() {
+            return html! {};
+        }
+        let players: Vec<String> = self.game_state.players.iter().map(|pl| pl.player.nickname.clone()).collect();
+        html! {
+          <div class="notify-wrapper">
+            <div class="notify wrapper">
+                <TricksReview players={players} tricks={self.game_state.deal.tricks.clone()} language={self.language.clone()} />
+                <div class="toolbar">
+                    <button class="primary" onclick={ctx.link().callback(|_| Msg::ToggleTricksReview)}>{"Ok"}</button>
+                </div>
+            </div>
+        </div>
+        }
+    }
+
+    /// The host of a training table gives the hands of the next deal
+    fn is_teacher(&self) -> bool {
+        self.game_state.training && self.my_state().pos.to_n() == 0
+    }
+
+    fn view_hands_setup_button(&self, ctx: &Context<Self>) -> Html {
+        if !self.is_teacher() {
+            return html! {};
+        }
+        html! {
+            <button class="btn-link" onclick={ctx.link().callback(|_| Msg::ToggleHandsSetup)}>{ tr!("give the hands") }</button>
+        }
+    }
+
+    fn view_hands_setup(&self, ctx: &Context<Self>) -> Html {
+        if !self.hands_setup_visible || !self.is_teacher() {
+            return html! {};
+        }
+        let mut players = vec![String::new(); self.game_state.nb_players as usize];
+        for state in self.game_state.players.iter() {
+            if let Some(name) = players.get_mut(state.pos.to_n()) {
+                *name = state.player.nickname.clone();
+            }
+        }
+        html! {
+          <div class="notify-wrapper">
+            <div class="notify wrapper">
+                <HandsSetup players={players} on_set_hands={ctx.link().callback(Msg::SetHands)} />
+                <div class="toolbar">
+                    <button onclick={ctx.link().callback(|_| Msg::ToggleHandsSetup)}>{ tr!("Cancel") }</button>
+                </div>
+            </div>
+        </div>
+        }
+    }
+
+    fn view_chat(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <>
+                { if self.chatbox_visible {
+                    html! {
+                       <ChatBox log={self.chat_log.clone()}
+                                 on_send_chat={ctx.link().callback(|text| Msg::SetChatLine(text))}
+                                 on_close={ctx.link().callback(|_| Msg::ToggleChatbox)}
+                       />
+                    }
+                } else {
+                    html!{
+                      <button class="btn-menu" onclick={ctx.link().callback(|_| Msg::ToggleChatbox)}>{"💬 chat" }</button>
+                   }
+                }}
+            </>
+        }
+    }
+
+    /// The table seen by a spectator, with the hands the game settings show
+    fn view_spectator(&self, ctx: &Context<Self>, hands: &[cards::Hand]) -> Html {
+        let players_msg: Vec<Option<String>> = (*self.players_chat.clone())
+            .into_iter()
+            .map(|chat| chat.map(|c| c.0))
+            .collect();
+        let visibility_info = match self.spectator_visibility {
+            SpectatorVisibility::Hidden => tr!("You are watching the game"),
+            SpectatorVisibility::Delayed(delay) => tr!("You are watching the game, the hands are shown {0} tricks late", delay),
+            SpectatorVisibility::All => tr!("You are watching the game, all the hands are shown"),
+        };
+        html! {
+    <div class="game spectator">
+      <header class="header">
+      { if self.reconnecting {
+          html! { <div class="reconnecting">{ tr!("Connection lost, reconnecting…") }</div> }
+      } else {
+          html! {}
+      }}
+      <div class="spectating">{ visibility_info }</div>
+      { self.view_clock() }
+      <div class="nav-right">
+      <button class="btn-link" onclick={ctx.link().callback(|_| Msg::Disconnect)}>{"\u{23FB} "} { tr!("disconnect") }</button>
+      { self.view_tricks_review_button(ctx) }
+      </div>
+      </header>
+
+      <PlayerList game_state={self.game_state.clone()} players={self.game_state.players.clone()} players_chat={players_msg} language={self.language.clone()}/>
+
+        { self.view_notifications(ctx) }
+
+        <section class="spectator-hands">
+        { self.game_state.players.iter().filter_map(|state| {
+            hands.get(state.pos.to_n()).map(|hand| view_small_hand(state.player.nickname.clone(), hand))
+        }).collect::<Html>() }
+        { if !self.game_state.deal.dog.is_empty() {
+            view_small_hand(tr!("Dog"), &self.game_state.deal.dog)
+        } else {
+            html! {}
+        }}
+        </section>
+
+        { self.view_chat(ctx) }
+    </div>
+        }
+    }
+
+    /// Result of a deal, with the contract math step by step and the hands as they were dealt
+    fn view_deal_summary(&self, summary: &DealSummary) -> Html {
+        let (taker_diff, oudlers_count, petit_bonus, multiplier, slam_bonus, poignees_bonus) = match summary.result {
+            deal::DealResult::GameOver { taker_diff, oudlers_count, petit_bonus, multiplier, slam_bonus, poignees_bonus, .. } =>
+                (taker_diff, oudlers_count, petit_bonus, multiplier, slam_bonus, poignees_bonus),
+            deal::DealResult::Nothing => return html! {},
+        };
+        let scores: Vec<Vec<f32>> = self.game_state.scores.iter().map(|score| score.to_vec()).collect();
+        let players: Vec<String> = self.game_state.players.iter().map(|pl| pl.player.nickname.clone()).collect();
+
+        let taker = self.game_state.pos_player_name(summary.contract.author);
+        let mut taker_message = tr!("{0} took a {1}", taker, target_name(summary.contract.target));
+        if let Some(king) = summary.king {
+            taker_message.push_str(&tr!(", calling {0}", king.to_locale_string(&self.language)));
+        }
+
+        let taker_won = taker_diff >= 0.0;
+        let diff_abs = f32::abs(taker_diff);
+        let mut contract_message = if taker_won {
+            tr!("Contract succeded by {} point", diff_abs)
+        } else {
+            tr!("Contract failed by {} point", diff_abs)
+        };
+        if diff_abs > 1.0 { contract_message.push('s'); }
+
+        let mut str_oudlers = tr!("{} oudler", oudlers_count);
+        if oudlers_count > 1 { str_oudlers.push('s'); }
+
+        contract_message.push_str(" ( ");
+        contract_message.push_str(&str_oudlers);
+        contract_message.push_str(" )");
+
+        // The contract math, step by step
+        let base = if taker_won { 25.0 + diff_abs } else { -25.0 - diff_abs };
+        let mut steps = vec![
+            tr!("{0} points needed, {1} points made", summary.needed_points(), summary.taker_points()),
+            tr!("Base: 25 + {0} = {1}", diff_abs, base),
+        ];
+        if petit_bonus != 0.0 {
+            steps.push(tr!("Petit au bout bonus: {}", petit_bonus));
+        }
+        steps.push(tr!("Multiplied by {0} for the contract: {1}", multiplier, multiplier as f32 * summary.contract_points()));
+        if poignees_bonus != 0.0 {
+            steps.push(tr!("Poignees bonus: {}", poignees_bonus));
+        }
+        if slam_bonus != 0.0 {
+            steps.push(tr!("Slam bonus: {}", slam_bonus));
+        }
+        let deal_points = summary.deal_points();
+        steps.push(if deal_points >= 0.0 {
+            tr!("Each defender pays {0} points", deal_points)
+        } else {
+            tr!("Each defender receives {0} points", -deal_points)
+        });
+
+        html! {
+            <div class="deal-summary">
+                <div>{ taker_message }</div>
+                <div>{ contract_message }</div>
+                <ol class="breakdown">
+                { steps.into_iter().map(|step| html! { <li>{ step }</li> }).collect::<Html>() }
+                </ol>
+                { self.view_fair_check(summary.fair.as_ref(), Some((summary.hands.as_slice(), summary.dog))) }
+                <Scores players={players} scores={scores} />
+                <section class="revealed-hands">
+                { self.game_state.players.iter().filter_map(|state| {
+                    summary.hands.get(state.pos.to_n()).map(|hand| view_small_hand(state.player.nickname.clone(), hand))
+                }).collect::<Html>() }
+                { view_small_hand(tr!("Dog"), &summary.dog) }
+                { if summary.discard != summary.dog {
+                    view_small_hand(tr!("Discard"), &summary.discard)
+                } else {
+                    html! {}
+                }}
+                </section>
+            </div>
+        }
     }
 
     fn is_first_trick(&self) -> bool {
         self.game_state.deal.trick_count == 1
     }
 
-    fn translate_error(&self, error: &String) -> String {
-        match error.as_str() {
-            "game is currently not joinable" => tr!("game is currently not joinable"),
-            "bid: auctions are closed" => tr!("auctions are closed"),
-            "bid: invalid turn order" => tr!("invalid turn order"),
-            "bid: bid must be higher than current contract" => tr!("bid must be higher than current contract"),
-            "bid: the auction are still running" => tr!("the auctions are still running"),
-            "bid: no contract was offered" => tr!("no contract was offered"),
-            "play: invalid turn order" => tr!("invalid turn order"),
-            "play: you can only play cards you have" => tr!("you can only play cards you have"),
-            "play: wrong suit played" => tr!("wrong suit played"),
-            "play: you must use trumps" => tr!("you must use trumps"),
-            "play: too weak trump played" => tr!("too weak trump played"),
-            "play: you cannot play the suit of the called king in the first trick" => tr!("you cannot play the suit of the called king in the first trick"),
-            "play: no trick has been played yet" => tr!("no trick has been played yet"),
-            "play: you are not the taker" => tr!("you are not the taker"),
-            "play: Wrong number of cards" => tr!("Wrong number of cards"),
-            "play: Can't put the same card twice in the dog" => tr!("Can't put the same card twice in the dog"),
-            "play: Card neither in the taker's hand nor in the dog" => tr!("Card neither in the taker's hand nor in the dog"),
-            "play: Can't put an oudler in the dog" => tr!("Can't put an oudler in the dog"),
-            "play: Can't put a king in the dog" => tr!("Can't put a king in the dog"),
-            "play: Can't put a trump in the dog" => tr!("Can't put a trump in the dog"),
-            _ => error.to_string()
+    fn translate_error(&self, error: &ProtocolError) -> String {
+        let code = match error.code() {
+            Some(code) => code,
+            None => return match error.message() {
+                "game is currently not joinable" => tr!("game is currently not joinable"),
+                "the server has been updated, please reload the page" => tr!("the server has been updated, please reload the page"),
+                message => message.to_string()
+            },
+        };
+        match code {
+            ErrorCode::TurnError => tr!("invalid turn order"),
+            ErrorCode::AuctionClosed => tr!("auctions are closed"),
+            ErrorCode::NonRaisedTarget => tr!("bid must be higher than current contract"),
+            ErrorCode::AuctionRunning => tr!("the auctions are still running"),
+            ErrorCode::NoContract => tr!("no contract was offered"),
+            ErrorCode::CardMissing => tr!("you can only play cards you have"),
+            ErrorCode::IncorrectSuit => tr!("wrong suit played"),
+            ErrorCode::InvalidPiss => tr!("you must use trumps"),
+            ErrorCode::NonRaisedTrump => tr!("too weak trump played"),
+            ErrorCode::CallKingSuit => tr!("you cannot play the suit of the called king in the first trick"),
+            ErrorCode::NoLastTrick => tr!("no trick has been played yet"),
+            ErrorCode::DogNotTaker => tr!("you are not the taker"),
+            ErrorCode::DogWrongNumberOfCards(given, expected) => tr!("Wrong number of cards: {0} instead of {1}", given, expected),
+            ErrorCode::DogSameCardTwice(card) => tr!("Can't put the same card ({0}) twice in the dog", card.to_locale_string(&self.language)),
+            ErrorCode::DogCardNotFound(card) => tr!("{0} is neither in the taker's hand nor in the dog", card.to_locale_string(&self.language)),
+            ErrorCode::DogOudler(card) => tr!("Can't put an oudler ({0}) in the dog", card.to_locale_string(&self.language)),
+            ErrorCode::DogKing(card) => tr!("Can't put a king ({0}) in the dog", card.to_locale_string(&self.language)),
+            ErrorCode::DogTrump(card) => tr!("Can't put a trump ({0}) in the dog", card.to_locale_string(&self.language)),
+            ErrorCode::IncompatibleProtocol => tr!("incompatible protocol version, please reload the page"),
+            ErrorCode::NotTraining => tr!("the hands can only be given on training tables"),
+            ErrorCode::NotProvablyFair => tr!("the deals of this table are not provably fair"),
+            ErrorCode::InvalidAnnounce => tr!("this announce is not allowed"),
+            ErrorCode::NotCallingKing => tr!("not the time to call a king"),
+            ErrorCode::InvalidKing(card) => tr!("Can't call {0}", card.to_locale_string(&self.language)),
+            ErrorCode::NotSeated => tr!("you are not seated at the table"),
+            ErrorCode::NotPlayer => tr!("only players can vote"),
+            ErrorCode::NoAbsentPlayer => tr!("no absent player at this position"),
+            ErrorCode::SeatNotOffered => tr!("this seat is not offered"),
+            ErrorCode::NotSpectator => tr!("only spectators can take a seat"),
+            ErrorCode::NotHost => tr!("only the host can do this"),
+            ErrorCode::InvalidHands => tr!("the hands must share the 78 cards"),
+            ErrorCode::UnknownDealState | ErrorCode::UnknownAuction => tr!("internal error of the server"),
         }
     }
 
@@ -187,36 +688,16 @@ impl GamePage {
         let log_str = format!("translating : '{}' in {}", &msg, &self.language);
         console_log!(log_str);
 
-        let mut parts = msg.split(':').fuse();
-        let str_type = parts.next();
-        let str_val = parts.next();
-
-        match str_type {
-            Some("call king") => match str_val {
-                Some(str_card) => {
-                    tr!("I call {}", self.translate_card(str_card.trim()))
-                },
-                _ => String::from(""),
-            },
-            _ => match msg.as_str() {
-                "*connected*" => tr!("connected"),
-                "Pass" | "pass" => tr!("Pass"),
-                _ => {
-                    let log_str = format!("non trouvé : '{}'", &msg);
-                    console_log!(log_str);
-                    msg.to_string()
-                }
+        match msg.as_str() {
+            "*connected*" => tr!("connected"),
+            _ => {
+                let log_str = format!("non trouvé : '{}'", &msg);
+                console_log!(log_str);
+                msg.to_string()
             }
         }
     }
 
-    fn translate_card(&self, str_card: &str) -> String {
-        let locale = &self.language;
-        cards::Card::from_str(str_card)
-            .map(|c| c.to_locale_string(locale))
-            .unwrap()
-    }
-
     fn display_overlay_box(&self) -> Html {
         if let Some(message) = &self.overlay_box {
             message.clone()
@@ -231,12 +712,46 @@ impl GamePage {
         } else {
             self.clean_chat_messages();
             match msg {
-                Message::GameStateSnapshot(snapshot) => {
+                Message::GameStateSnapshot(StateUpdate::Full { snapshot, .. }) => {
                     self.is_waiting = false;
+                    self.spectator_hands = None;
                     self.apply_snapshot(snapshot);
                 }
-                Message::PlayEvent(evt) => {
-                    self.sound_player.play("card".into());
+                Message::GameStateSnapshot(StateUpdate::Spectator { snapshot, .. }) => {
+                    self.spectator_hands = Some(snapshot.hands.clone());
+                    self.spectator_visibility = snapshot.visibility;
+                    self.apply_snapshot(snapshot.table());
+                }
+                Message::PlayEvent(GameEvent { event: evt, .. }) => {
+                    match evt {
+                        PlayEvent::Bid(uuid, target) => {
+                            self.add_event_message(uuid, target_name(target));
+                        }
+                        PlayEvent::Pass(uuid) => {
+                            self.add_event_message(uuid, tr!("Pass"));
+                        }
+                        PlayEvent::KingCalled(uuid, card) => {
+                            let text = tr!("I call {}", card.to_locale_string(&self.language));
+                            self.add_event_message(uuid, text);
+                        }
+                        PlayEvent::DogMade(uuid, shown) => {
+                            let text = if shown.is_empty() {
+                                tr!("Dog done")
+                            } else {
+                                let shown: Vec<String> = shown.list().iter().map(|card| card.to_locale_string(&self.language)).collect();
+                                tr!("Dog done, with {0}", shown.join(" "))
+                            };
+                            self.add_event_message(uuid, text);
+                        }
+                        PlayEvent::SlamDeclared(uuid) => {
+                            self.add_event_message(uuid, tr!("I declare a slam!"));
+                        }
+                        PlayEvent::TimedOut(uuid) => {
+                            self.add_event_message(uuid, tr!("Out of time!"));
+                        }
+                        PlayEvent::DealCancelled(_) => {}
+                        _ => self.sound_player.play("card".into()),
+                    }
                     match evt {
                         PlayEvent::Play(uuid, card) => {
                             self.add_chat_message(uuid, ChatLineData::Text(format!("play: {}", card.to_locale_string(&self.language))));
@@ -252,49 +767,24 @@ impl GamePage {
                             });
                             self.update_needs_confirm = true;
                         },
-                        PlayEvent::EndDeal(result) => {
-                            if let deal::DealResult::GameOver{oudlers_count, petit_bonus, slam_bonus, poignees_bonus, ..} = result {
-                                let scores: Vec<Vec<f32>> = self.game_state.scores.iter().map(|score| score.to_vec()).collect();
-                                let players: Vec<String> = self.game_state.players.iter().map(|pl| pl.player.nickname.clone()).collect();
-
-                                let taker_won = self.game_state.deal.taker_diff >= 0.0;
-                                let diff_abs = f32::abs(self.game_state.deal.taker_diff);
-                                let mut contract_message = if taker_won {
-                                    tr!("Contract succeded by {} point", diff_abs)
-                                } else {
-                                    tr!("Contract failed by {} point", diff_abs)
-                                };
-                                if diff_abs > 1.0 { contract_message.push('s'); }
-
-                                let mut str_oudlers = tr!("{} oudler", oudlers_count);
-                                if oudlers_count > 1 { str_oudlers.push('s'); }
-
-                                contract_message.push_str(" ( ");
-                                contract_message.push_str(&str_oudlers);
-                                contract_message.push_str(" )");
-
-                                let petit_message = if petit_bonus != 0.0 { tr!("Petit au bout bonus: {}", petit_bonus) } else { "".into() };
-                                let poignees_message = if poignees_bonus != 0.0 { tr!("Poignees bonus: {}", poignees_bonus) } else { "".into() };
-                                let slam_message = if slam_bonus != 0.0 { tr!("Slam bonus: {}", slam_bonus) } else { "".into() };
-
-                                let dog_message = tr!("Dog : {0}", self.game_state.deal.dog.to_string());
-                                self.overlay_box = Some(html! {
-                                    <div>
-                                        <div>{ contract_message }</div>
-                                        <div>{ dog_message }</div>
-                                        <div>{ petit_message }</div>
-                                        <div>{ poignees_message }</div>
-                                        <div>{ slam_message }</div>
-                                        <br/><br/>
-                                        <Scores players={players} scores={scores} />
-                                    </div>
-                                });
+                        PlayEvent::DealSummary(summary) => {
+                            if let deal::DealResult::GameOver{..} = summary.result {
+                                self.overlay_box = Some(self.view_deal_summary(&summary));
                                 self.update_needs_confirm = true;
                             } else {
-                                let log_str = format!("result : {:?}", result);
+                                let log_str = format!("result : {:?}", summary.result);
                                 console_log!(log_str);
                             }
                         },
+                        PlayEvent::DealCancelled(proof) => {
+                            self.overlay_box = Some(html! {
+                                <div class="results">
+                                { tr!("Everybody passed, the cards are dealt again") }
+                                { self.view_fair_check(proof.as_ref(), None) }
+                                </div>
+                            });
+                            self.update_needs_confirm = true;
+                        },
                         PlayEvent::Announce(uuid, announce) => {
                             let nickname = self.get_nickname(uuid);
                             let proof_html = if let Some(proof_hand) = announce.proof {
@@ -318,6 +808,7 @@ impl GamePage {
                             });
                             self.update_needs_confirm = true;
                         },
+                        _ => {}
                     }
                 }
                 _ => {}
@@ -326,6 +817,31 @@ impl GamePage {
     }
 }
 
+/// Cards of a hand shown to everybody, in a smaller size
+pub(crate) fn view_small_hand(name: String, hand: &cards::Hand) -> Html {
+    html! {
+        <div class="small-hand">
+            <div class="nickname">{ name }</div>
+            <div class="hand">
+            { hand.list().iter().map(|card| {
+                let style = format!("--bg-image: url('cards/{}-{}.svg')", &card.rank().to_string(), &card.suit().to_safe_string());
+                html! {
+                    <div class="card" style={style}></div>
+                }
+            }).collect::<Html>()}
+            </div>
+        </div>
+    }
+}
+
+/// Entropy drawn by the browser, for the deals of provably fair tables
+fn random_seed() -> Option<Seed> {
+    let crypto = web_sys::window()?.crypto().ok()?;
+    let mut seed = [0; 32];
+    crypto.get_random_values_with_u8_array(&mut seed).ok()?;
+    Some(seed)
+}
+
 impl Component for GamePage {
     type Message = Msg;
     type Properties = Props;
@@ -335,8 +851,19 @@ impl Component for GamePage {
         let keepalive = Interval::new(50_000, move || {
             link.send_message(Msg::Ping);
         });
+        let link = ctx.link().clone();
+        let absent_check = Interval::new(10_000, move || {
+            link.send_message(Msg::CheckAbsent);
+        });
+        let link = ctx.link().clone();
+        let clock_tick = Interval::new(1000, move || {
+            link.send_message(Msg::ClockTick);
+        });
 
-        let mut api = Api::bridge(ctx.link().callback(Msg::ServerMessage));
+        let mut api = Api::bridge_with_status(
+            ctx.link().callback(Msg::ServerMessage),
+            ctx.link().callback(Msg::ConnectionStatus),
+        );
         let sound_paths = vec![
             ("chat".into(), "sounds/misc_menu.ogg"),
             ("card".into(), "sounds/cardPlace4.ogg"),
@@ -344,10 +871,13 @@ impl Component for GamePage {
         ].into_iter().collect();
 
         api.send(Command::MarkReady);
+        api.send(Command::GamePlay(GamePlayCommand::Hello(HelloCommand { version: PROTOCOL_VERSION })));
 
         let props = ctx.props();
         GamePage {
             _keepalive: keepalive,
+            _absent_check: absent_check,
+            _clock_tick: clock_tick,
             api,
             game_info: props.game_info.clone(),
             players_chat: Box::new(vec![None; 5]),
@@ -368,6 +898,20 @@ impl Component for GamePage {
             overlay_box: None,
             language: props.language.clone(),
             chatbox_visible: false,
+            tricks_review_visible: false,
+            hands_setup_visible: false,
+            tracker: SnapshotTracker::new(),
+            spectator_hands: None,
+            spectator_visibility: SpectatorVisibility::default(),
+            reconnecting: false,
+            next_event: None,
+            absent: vec![],
+            replace_absent_after: 0,
+            absent_since: vec![],
+            clock: None,
+            timeout_sent: false,
+            fair_next: None,
+            fair_current: None,
         }
     }
 
@@ -377,11 +921,46 @@ impl Component for GamePage {
 
     fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
-            Msg::ServerMessage(message) => match message {
+            Msg::ServerMessage(message) => {
+              let message = match message {
+                  Message::GameStateSnapshot(update) => match self.tracker.apply_view(update) {
+                      Some(GameView::Player(snapshot)) => {
+                          if self.next_event.is_none() {
+                              self.next_event = Some(snapshot.next_event);
+                          }
+                          self.update_absent(snapshot.absent.clone(), snapshot.replace_absent_after);
+                          // Patches are applied as they arrive, queued messages hold whole snapshots
+                          Message::GameStateSnapshot(snapshot.into())
+                      }
+                      Some(GameView::Spectator(snapshot)) => {
+                          if self.next_event.is_none() {
+                              self.next_event = Some(snapshot.next_event);
+                          }
+                          // Spectators don't vote for replacing absent players
+                          self.absent = snapshot.absent.clone();
+                          Message::GameStateSnapshot(snapshot.into())
+                      }
+                      None => {
+                          // We missed some changes
+                          self.api.send(Command::GamePlay(GamePlayCommand::Resync));
+                          return false;
+                      }
+                  },
+                  Message::PlayEvent(event) => {
+                      if self.next_event.map_or(false, |next| event.seq < next) {
+                          // Already received before a reconnection
+                          return false;
+                      }
+                      self.next_event = Some(event.seq + 1);
+                      Message::PlayEvent(event)
+                  }
+                  message => message,
+              };
+              match message {
                 Message::Pong => {}
                 Message::Error(e) => {
                     self.is_waiting = false;
-                    self.error = Some(e.message().into());
+                    self.error = Some(ProtocolError::from(&e));
                     self.sound_player.play("error".into());
                 }
                 Message::PlayerConnected(state) => {
@@ -393,17 +972,50 @@ impl Component for GamePage {
                 Message::PlayerDisconnected(msg) => {
                     self.add_chat_message(msg.player_id, ChatLineData::Disconnected);
                     let game_state = Rc::make_mut(&mut self.game_state);
-                    game_state.players.retain(|x| x.player.id != msg.player_id);
+                    if game_state.turn.has_player_pos() {
+                        // The seat is kept until the player comes back or a bot replaces him
+                        let player = game_state.players.iter().find(|x| x.player.id == msg.player_id);
+                        if let Some(player) = player {
+                            if !game_state.absent.iter().any(|a| a.player.id == msg.player_id) {
+                                game_state.absent.push(AbsentPlayer {
+                                    player: player.player.clone(),
+                                    pos: player.pos,
+                                    since: (js_sys::Date::now() / 1000.) as u64,
+                                    votes: vec![],
+                                    invited_by: None,
+                                    substitute: None,
+                                });
+                            }
+                        }
+                        let (absent, replace_absent_after) = (game_state.absent.clone(), game_state.replace_absent_after);
+                        self.update_absent(absent, replace_absent_after);
+                    } else {
+                        game_state.players.retain(|x| x.player.id != msg.player_id);
+                    }
                 }
                 Message::Chat(msg) => {
                     self.sound_player.play("chat".into());
                     self.add_chat_message(msg.player_id, ChatLineData::Text(msg.text));
                 }
                 _ => self.manage_game_message(message)
+              }
             },
             Msg::Ping => {
                 self.api.send(Command::Ping);
             }
+            Msg::ConnectionStatus(connected) => {
+                if !connected {
+                    self.reconnecting = true;
+                } else if self.reconnecting {
+                    self.reconnecting = false;
+                    self.api.send(Command::GamePlay(GamePlayCommand::Hello(HelloCommand { version: PROTOCOL_VERSION })));
+                    // Ask for what happened while we were away
+                    if let Some(since) = self.next_event {
+                        self.api.send(Command::GamePlay(GamePlayCommand::Replay(ReplayCommand { since })));
+                    }
+                    self.api.send(Command::GamePlay(GamePlayCommand::Resync));
+                }
+            }
             Msg::SetChatLine(text) => {
                 self.api.send(Command::SendText(SendTextCommand { text }));
             }
@@ -466,6 +1078,16 @@ impl Component for GamePage {
             Msg::ToggleChatbox => {
                 self.chatbox_visible = !self.chatbox_visible;
             },
+            Msg::ToggleTricksReview => {
+                self.tricks_review_visible = !self.tricks_review_visible;
+            },
+            Msg::ToggleHandsSetup => {
+                self.hands_setup_visible = !self.hands_setup_visible;
+            },
+            Msg::SetHands((hands, dog)) => {
+                self.hands_setup_visible = false;
+                self.api.send(Command::GamePlay(GamePlayCommand::SetHands(SetHandsCommand { hands, dog })));
+            },
             Msg::Announce(announce) => {
                 self.api.send(Command::GamePlay(GamePlayCommand::Announce(AnnounceCommand { announce })));
             }
@@ -473,6 +1095,37 @@ impl Component for GamePage {
                 self.is_waiting = true;
                 self.api.send(Command::GamePlay(GamePlayCommand::Play(PlayCommand { card })));
             }
+            Msg::ReplaceAbsent(pos) => {
+                self.api.send(Command::GamePlay(GamePlayCommand::ReplaceAbsent(ReplaceAbsentCommand { pos })));
+            }
+            Msg::SetBotDelay(delay) => {
+                self.api.send(Command::GamePlay(GamePlayCommand::SetBotDelay(SetBotDelayCommand { delay })));
+            }
+            Msg::CheckAbsent => {
+                // After the delay of the game settings, a single vote replaces the player
+                let delay = self.replace_absent_after as f64 * 1000.;
+                let now = js_sys::Date::now();
+                let due: Vec<pos::PlayerPos> = self.absent.iter()
+                    .filter(|a| a.substitute.is_none() && a.invited_by.is_none())
+                    .filter(|a| self.absent_since.iter().any(|(id, since)| *id == a.player.id && delay > 0. && now - since >= delay))
+                    .map(|a| a.pos)
+                    .collect();
+                for pos in due {
+                    self.api.send(Command::GamePlay(GamePlayCommand::ReplaceAbsent(ReplaceAbsentCommand { pos })));
+                }
+                return false;
+            }
+            Msg::ClockTick => {
+                let left = match self.clock_left() {
+                    Some((_pos, left, _in_bank)) => left,
+                    None => return false,
+                };
+                if left <= 0. && !self.timeout_sent {
+                    // The server checks it and acts for the player
+                    self.timeout_sent = true;
+                    self.api.send(Command::GamePlay(GamePlayCommand::Timeout));
+                }
+            }
         }
         true
     }
@@ -481,6 +1134,15 @@ impl Component for GamePage {
         if self.game_state.players.is_empty() {
             return html! {};
         }
+        if let Some(hands) = &self.spectator_hands {
+            return self.view_spectator(ctx, hands);
+        }
+        if !self.game_state.players.iter().any(|state| state.player.id == self.player_info.id) {
+            // No free seat for us
+            return html! {
+                <div class="wrapper">{ tr!("The game has already started") }</div>
+            };
+        }
 
         let my_state = self.my_state();
         let card_played = self.game_state.deal.last_trick.card_played(my_state.pos);
@@ -537,38 +1199,25 @@ impl Component for GamePage {
         html! {
     <div class={game_classes.join(" ")}>
       <header class="header">
+      { self.view_absent(ctx) }
+      { self.view_clock() }
+      { if self.reconnecting {
+          html! { <div class="reconnecting">{ tr!("Connection lost, reconnecting…") }</div> }
+      } else {
+          html! {}
+      }}
       <div class="nav-right">
       <button class="btn-link" onclick={ctx.link().callback(|_| Msg::Disconnect)}>{"\u{23FB} "} { tr!("disconnect") }</button>
+      { self.view_tricks_review_button(ctx) }
+      { self.view_bot_delay(ctx) }
+      { self.view_hands_setup_button(ctx) }
+      { self.view_fair_badge() }
       </div>
       </header>
 
       <PlayerList game_state={self.game_state.clone()} players={others} players_chat={players_msg} language={self.language.clone()}/>
 
-        { if let Some(error) = &self.error {
-            let error_str = self.translate_error(&error);
-            html! {
-          <div class="notify-wrapper">
-            <div class="error notify">
-                <div>
-                { error_str }
-                </div>
-                <div class="toolbar">
-                    <button class="btn-error" onclick={ctx.link().callback(|_| Msg::CloseError)}>{"Ok"}</button>
-                </div>
-              </div>
-            </div>
-        }} else { html! {} }}
-
-        { if self.overlay_box.is_some() { html! {
-          <div class="notify-wrapper">
-            <div class="notify wrapper">
-                { self.display_overlay_box() }
-                <div class="toolbar">
-                    <button class="primary" onclick={ctx.link().callback(|_| Msg::Continue)}>{"Ok"}</button>
-                </div>
-            </div>
-        </div>
-        }} else { html! {} }}
+        { self.view_notifications(ctx) }
 
         <section class={actions_classes.join(" ")}>
             {match self.game_state.turn {
@@ -725,18 +1374,7 @@ impl Component for GamePage {
         }}
         </section>
 
-        { if self.chatbox_visible {
-            html! {
-               <ChatBox log={self.chat_log.clone()}
-                         on_send_chat={ctx.link().callback(|text| Msg::SetChatLine(text))}
-                         on_close={ctx.link().callback(|_| Msg::ToggleChatbox)}
-               />
-            }
-        } else {
-            html!{
-              <button class="btn-menu" onclick={ctx.link().callback(|_| Msg::ToggleChatbox)}>{"💬 chat" }</button>
-           }
-        }}
+        { self.view_chat(ctx) }
     </div>
 
         }
@@ -9,7 +9,10 @@ use tr::tr;
 use weblog::*;
 
 use crate::api::{Api, ApiBridge};
-use crate::protocol::{Command, Message, TarotVariant, VariantSettings};
+use crate::protocol::{
+    Command, Message, ProtocolError, TarotVariant, VariantSettings,
+    SpectatorVisibility, DEFAULT_SPECTATOR_DELAY, TimeControl,
+};
 use crate::gprotocol::{GameInfo, PlayerInfo, JoinGameCommand};
 use crate::utils::format_join_code;
 
@@ -17,6 +20,7 @@ use crate::utils::format_join_code;
 pub struct Props {
     pub player_info: PlayerInfo,
     pub on_game_joined: Callback<GameInfo>,
+    pub on_show_archives: Callback<()>,
 }
 
 impl PartialEq for Props {
@@ -30,7 +34,16 @@ pub struct MenuPage {
     join_code: Cow<'static, str>,
     player_info: PlayerInfo,
     on_game_joined: Callback<GameInfo>,
+    on_show_archives: Callback<()>,
     error: Option<String>,
+    /// What the spectators of the new game will see
+    spectators: SpectatorVisibility,
+    review_tricks: bool,
+    /// The host of the new game gives the hands
+    training: bool,
+    /// The players of the new game can check its deals
+    provably_fair: bool,
+    time_control: Option<TimeControl>,
 }
 
 pub enum Msg {
@@ -39,18 +52,31 @@ pub enum Msg {
     JoinGame,
     ServerMessage(Message),
     SetJoinCode(String),
+    SetSpectators(SpectatorVisibility),
+    ToggleReviewTricks,
+    ToggleTraining,
+    ToggleProvablyFair,
+    SetTimeControl(Option<TimeControl>),
+    ShowArchives,
 }
 
+/// Time controls the host can choose from, in seconds
+const TIME_CONTROLS: [TimeControl; 3] = [
+    TimeControl { per_action: 15, bank: 60 },
+    TimeControl { per_action: 30, bank: 120 },
+    TimeControl { per_action: 60, bank: 0 },
+];
+
 const TAROT3: TarotVariant = TarotVariant {
-    parameters: VariantSettings { nb_players: 3 }
+    parameters: VariantSettings::new(3)
 };
 
 const TAROT4: TarotVariant = TarotVariant {
-    parameters: VariantSettings { nb_players: 4 }
+    parameters: VariantSettings::new(4)
 };
 
 const TAROT5: TarotVariant = TarotVariant {
-    parameters: VariantSettings { nb_players: 5 }
+    parameters: VariantSettings::new(5)
 };
 
 impl Component for MenuPage {
@@ -64,20 +90,32 @@ impl Component for MenuPage {
             join_code: "".into(),
             player_info: ctx.props().player_info.clone(),
             on_game_joined: ctx.props().on_game_joined.clone(),
+            on_show_archives: ctx.props().on_show_archives.clone(),
             error: None,
+            spectators: SpectatorVisibility::default(),
+            review_tricks: false,
+            training: false,
+            provably_fair: false,
+            time_control: None,
         }
     }
 
     fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
         self.player_info = ctx.props().player_info.clone();
         self.on_game_joined = ctx.props().on_game_joined.clone();
+        self.on_show_archives = ctx.props().on_show_archives.clone();
         true
     }
 
     fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
-            Msg::NewGame(variant) => {
+            Msg::NewGame(mut variant) => {
                 console_log!("New Game");
+                variant.parameters.spectators = self.spectators;
+                variant.parameters.review_tricks = self.review_tricks;
+                variant.parameters.training = self.training;
+                variant.parameters.provably_fair = self.provably_fair;
+                variant.parameters.time_control = self.time_control;
                 self.api.send(Command::NewGame(variant));
             }
             Msg::JoinGame => {
@@ -91,19 +129,71 @@ impl Component for MenuPage {
                     self.on_game_joined.emit(data);
                 }
                 Message::Error(err) => {
-                    self.error = Some(err.message().to_string());
+                    self.error = Some(ProtocolError::from(&err).message().to_string());
                 }
                 _ => {}
             },
             Msg::SetJoinCode(join_code) => {
                 self.join_code = format_join_code(&join_code).into();
             }
+            Msg::SetSpectators(spectators) => {
+                self.spectators = spectators;
+            }
+            Msg::ToggleReviewTricks => {
+                self.review_tricks = !self.review_tricks;
+            }
+            Msg::ToggleTraining => {
+                self.training = !self.training;
+            }
+            Msg::ToggleProvablyFair => {
+                self.provably_fair = !self.provably_fair;
+            }
+            Msg::SetTimeControl(time_control) => {
+                self.time_control = time_control;
+            }
+            Msg::ShowArchives => {
+                self.on_show_archives.emit(());
+            }
             Msg::Ignore => {}
         }
         true
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
+        // Clicking selects the next visibility
+        let (spectators_info, next_spectators) = match self.spectators {
+            SpectatorVisibility::Hidden => (tr!("spectators don't see the hands"), SpectatorVisibility::Delayed(DEFAULT_SPECTATOR_DELAY)),
+            SpectatorVisibility::Delayed(delay) => (tr!("spectators see the hands {0} tricks late", delay), SpectatorVisibility::All),
+            SpectatorVisibility::All => (tr!("spectators see all the hands"), SpectatorVisibility::Hidden),
+        };
+        let review_info = if self.review_tricks {
+            tr!("previous tricks can be reviewed")
+        } else {
+            tr!("previous tricks can't be reviewed")
+        };
+        let training_info = if self.training {
+            tr!("training table, the host gives the hands")
+        } else {
+            tr!("shuffled hands")
+        };
+        let fair_info = if self.provably_fair {
+            tr!("provably fair deals")
+        } else {
+            tr!("deals shuffled by the server")
+        };
+        let time_info = match self.time_control {
+            None => tr!("no time limit"),
+            Some(control) if control.bank > 0 => tr!("{0} seconds per move, {1} more seconds per deal", control.per_action, control.bank),
+            Some(control) => tr!("{0} seconds per move", control.per_action),
+        };
+        // Clicking selects the next time control, then no limit after the last one
+        let next_time_control = match self.time_control {
+            None => Some(TIME_CONTROLS[0]),
+            Some(control) => TIME_CONTROLS.iter()
+                .position(|c| *c == control)
+                .and_then(|idx| TIME_CONTROLS.get(idx + 1))
+                .copied(),
+        };
         html! {
             <div class="wrapper">
                 <h1>{tr!("Hello {0}!", &self.player_info.nickname)}</h1>
@@ -131,6 +221,26 @@ impl Component for MenuPage {
                     <button class="primary" onclick={ctx.link().callback(|_| Msg::NewGame(TAROT4))}>{ tr!("New 4 players Game")}</button>
                     <button class="primary" onclick={ctx.link().callback(|_| Msg::NewGame(TAROT5))}>{ tr!("New 5 players Game")}</button>
                 </div>
+                <div class="toolbar">
+                    <button class="btn-link" title={ tr!("What the spectators see") } onclick={ctx.link().callback(move |_| Msg::SetSpectators(next_spectators))}>
+                        { spectators_info }
+                    </button>
+                    <button class="btn-link" onclick={ctx.link().callback(|_| Msg::ToggleReviewTricks)}>
+                        { review_info }
+                    </button>
+                    <button class="btn-link" onclick={ctx.link().callback(|_| Msg::ToggleTraining)}>
+                        { training_info }
+                    </button>
+                    <button class="btn-link" title={ tr!("The players add their own entropy to the shuffle and can check the deals") } onclick={ctx.link().callback(|_| Msg::ToggleProvablyFair)}>
+                        { fair_info }
+                    </button>
+                    <button class="btn-link" title={ tr!("Time to play") } onclick={ctx.link().callback(move |_| Msg::SetTimeControl(next_time_control))}>
+                        { time_info }
+                    </button>
+                </div>
+                <div class="toolbar">
+                    <button onclick={ctx.link().callback(|_| Msg::ShowArchives)}>{ tr!("Past games") }</button>
+                </div>
                 {
                     if let Some(ref error) = self.error {
                         html! {
@@ -1,3 +1,4 @@
+pub mod archives;
 pub mod game;
 pub mod menu;
 pub mod start;
@@ -8,7 +8,7 @@ use yew::{
 use web_sys::{HtmlInputElement, KeyboardEvent};
 
 use crate::api::{Api, ApiBridge};
-use crate::protocol::{Command, Message};
+use crate::protocol::{Command, Message, ProtocolError};
 use crate::gprotocol::{AuthenticateCommand, PlayerInfo};
 
 #[derive(Clone, Properties)]
@@ -67,7 +67,7 @@ impl Component for StartPage {
                     self.on_authenticate.emit(data);
                 }
                 Message::Error(err) => {
-                    self.error = Some(err.message().to_string());
+                    self.error = Some(ProtocolError::from(&err).message().to_string());
                 }
                 _ => {}
             },
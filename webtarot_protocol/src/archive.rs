@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use tarotgame::{bid, cards, deal, trick};
+use uuid::Uuid;
+
+use crate::deal::DealSummary;
+use crate::update::GameEvent;
+
+/// A finished deal, kept so that it can be replayed trick by trick
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DealArchive {
+    pub game_id: Uuid,
+    /// Number of the deal in the game, from 1
+    pub deal: usize,
+    /// When the deal ended, in seconds since the unix epoch
+    pub finished_at: u64,
+    /// Seed the cards were shuffled with, none when they were given otherwise
+    pub seed: Option<[u8; 32]>,
+    /// Nicknames of the players, by position
+    pub players: Vec<String>,
+    pub summary: DealSummary,
+    /// Tricks in the order they were played
+    pub tricks: Vec<trick::Trick>,
+    /// Events of the deal, from the first bid to the summary
+    pub events: Vec<GameEvent>,
+    /// Scores of the game, one line per deal up to this one
+    pub scores: Vec<Vec<f32>>,
+}
+
+impl DealArchive {
+    /// Hands of the players before the trick number `trick`, from 0 : the taker has made the
+    /// dog and the cards of the previous tricks are gone
+    pub fn hands_before(&self, trick: usize) -> Vec<cards::Hand> {
+        let mut hands = self.summary.hands.clone();
+        if let Some(taker) = hands.get_mut(self.summary.contract.author.to_n()) {
+            taker.merge(self.summary.dog);
+            for card in self.summary.discard.list() {
+                taker.remove(card);
+            }
+        }
+        for played in self.tricks.iter().take(trick) {
+            for (hand, card) in hands.iter_mut().zip(played.cards.iter()) {
+                if let Some(card) = card {
+                    hand.remove(*card);
+                }
+            }
+        }
+        hands
+    }
+
+    /// Line of the archives index for this deal
+    pub fn entry(&self) -> ArchiveEntry {
+        let scores = match &self.summary.result {
+            deal::DealResult::GameOver { scores, .. } => scores.clone(),
+            deal::DealResult::Nothing => vec![],
+        };
+        ArchiveEntry {
+            game_id: self.game_id,
+            deal: self.deal,
+            finished_at: self.finished_at,
+            players: self.players.clone(),
+            contract: self.summary.contract.clone(),
+            scores,
+        }
+    }
+}
+
+/// Archived deal, as listed in the archives index
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ArchiveEntry {
+    pub game_id: Uuid,
+    pub deal: usize,
+    /// When the deal ended, in seconds since the unix epoch
+    pub finished_at: u64,
+    /// Nicknames of the players, by position
+    pub players: Vec<String>,
+    pub contract: bid::Contract,
+    /// Scores of the players for this deal
+    pub scores: Vec<f32>,
+}
@@ -0,0 +1,27 @@
+use std::{env, fs, path::Path};
+
+use webtarot_protocol::schema::schemas;
+
+/// Writes the JSON Schema of the protocol types in the given directory, or prints them all
+/// as a single JSON object when no directory is given.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    match args.get(1) {
+        Some(dir) => {
+            fs::create_dir_all(dir).expect("Could not create the output directory");
+            for (name, schema) in schemas() {
+                let file_path = Path::new(dir).join(format!("{name}.schema.json"));
+                let json = serde_json::to_string_pretty(&schema).expect("Error serializing schema");
+                fs::write(&file_path, json).expect("Could not write schema file");
+                println!("{}", file_path.display());
+            }
+        }
+        None => {
+            let all: serde_json::Map<String, serde_json::Value> = schemas()
+                .into_iter()
+                .map(|(name, schema)| (name.to_string(), serde_json::to_value(schema).unwrap()))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&all).expect("Error serializing schemas"));
+        }
+    }
+}
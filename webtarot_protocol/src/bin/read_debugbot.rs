@@ -1,16 +1,121 @@
-use std::{env, fs};
+//! Replays a deal from a saved game state (`ShowState` and `debugbot` dumps, the games store) or
+//! from an archived deal.
+//!
+//! Without option, the deal is played again step by step, the commands being read on the
+//! standard input. `--print` prints the game state, `--notation` the deal in text notation and
+//! `--views` the deal as seen by each player.
+use std::io::{self, BufRead};
+use std::{env, fs, process};
 
-use webtarot_protocol::TarotGameState;
+use tarotgame::pos;
+use webtarot_protocol::{DealArchive, DealReplay, TarotGameState};
+
+const USAGE: &str = "usage: read_debugbot [--print | --notation | --views] FILE...";
+
+const COMMANDS: &str = "commands:
+  n or enter  next card
+  p           previous card
+  t           end of the next trick
+  T           end of the previous trick
+  g N         after N cards
+  q           quit";
+
+enum Mode {
+    Replay,
+    Print,
+    Notation,
+    Views,
+}
+
+/// Reads a saved game state, with the deal it is playing, or an archived deal
+fn load(file_path: &str) -> (Option<TarotGameState>, Option<DealReplay>) {
+    let error = format!("Could not read file {file_path}...");
+    let json_str = fs::read_to_string(file_path).expect(&error);
+    if let Ok(archive) = serde_json::from_str::<DealArchive>(&json_str) {
+        return (None, Some(DealReplay::from_archive(&archive)));
+    }
+    let game: TarotGameState = serde_json::from_str(&json_str).expect("Error parsing JSON");
+    let replay = game.deal_replay();
+    (Some(game), replay)
+}
+
+fn replay_interactively(mut replay: DealReplay) {
+    println!("{COMMANDS}\n");
+    print!("{replay}");
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let mut words = line.split_whitespace();
+        match words.next() {
+            None | Some("n") => replay.forward(),
+            Some("p") => replay.back(),
+            Some("t") => replay.next_trick(),
+            Some("T") => replay.previous_trick(),
+            Some("g") => match words.next().and_then(|step| step.parse().ok()) {
+                Some(step) => replay.goto(step),
+                None => {
+                    println!("{COMMANDS}");
+                    continue;
+                }
+            },
+            Some("q") => break,
+            Some(_) => {
+                println!("{COMMANDS}");
+                continue;
+            }
+        }
+        println!();
+        print!("{replay}");
+    }
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let mut i = 1;
-    while i < args.len() {
-        let file_path = args[i].as_str();
-        let error = format!("Could not read file {file_path}...");
-        let json_str = fs::read_to_string(file_path).expect(&error);
-        let game: TarotGameState = serde_json::from_str(&json_str).expect("Error parsing JSON");
-        println!("{game}");
-        i += 1;
+    let mut mode = Mode::Replay;
+    let mut files = vec![];
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--print" => mode = Mode::Print,
+            "--notation" => mode = Mode::Notation,
+            "--views" => mode = Mode::Views,
+            "-h" | "--help" => {
+                println!("{USAGE}\n\n{COMMANDS}");
+                return;
+            }
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        eprintln!("{USAGE}");
+        process::exit(1);
+    }
+
+    for file_path in files {
+        let (game, replay) = load(&file_path);
+        if let (Mode::Print, Some(game)) = (&mode, &game) {
+            println!("{game}");
+            continue;
+        }
+        let replay = match replay {
+            Some(replay) => replay,
+            None => {
+                eprintln!("{file_path}: no deal is being played");
+                continue;
+            }
+        };
+        match mode {
+            Mode::Replay => replay_interactively(replay),
+            Mode::Print | Mode::Notation => print!("{}", replay.to_notation()),
+            Mode::Views => {
+                let count = replay.hands().len();
+                for n in 0..count {
+                    println!(
+                        "{}",
+                        replay.player_view(pos::PlayerPos::from_n(n, count as u8))
+                    );
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,122 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tarotgame::pos;
+
+/// Time the players have to act
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TimeControl {
+    /// Seconds for each bid, call, dog or card
+    pub per_action: u32,
+    /// Seconds each player can spend over the limit of his actions during a deal
+    #[serde(default)]
+    pub bank: u32,
+}
+
+/// Time left to the player who must act, as sent to the clients
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ClockSnapshot {
+    /// Seat of the player who must act
+    pub pos: pos::PlayerPos,
+    /// Milliseconds left for the current action, before the bank is used
+    pub remaining: u64,
+    /// Milliseconds left in the bank of each seat
+    pub banks: Vec<u64>,
+}
+
+/// Running clock of a game with time controls
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Clock {
+    /// Seat of the player who must act
+    pos: Option<pos::PlayerPos>,
+    /// When the current action started, in milliseconds since the unix epoch
+    started: u64,
+    /// Milliseconds left in the bank of each seat
+    banks: Vec<u64>,
+}
+
+impl Clock {
+    /// Fills the banks for a new deal
+    pub fn reset(&mut self, control: &TimeControl, nb_players: u8) {
+        self.banks = vec![control.bank as u64 * 1000; nb_players as usize];
+    }
+
+    /// The previous player pays for the time he used over the limit, and `pos` starts to think
+    pub fn start(&mut self, control: &TimeControl, pos: Option<pos::PlayerPos>, now: u64) {
+        if let Some(previous) = self.pos {
+            let over = now
+                .saturating_sub(self.started)
+                .saturating_sub(control.per_action as u64 * 1000);
+            if let Some(bank) = self.banks.get_mut(previous.to_n()) {
+                *bank = bank.saturating_sub(over);
+            }
+        }
+        self.pos = pos;
+        self.started = now;
+    }
+
+    /// Seat of the player who must act and the time when he runs out of time
+    pub fn deadline(&self, control: &TimeControl) -> Option<(pos::PlayerPos, u64)> {
+        let pos = self.pos?;
+        let bank = self.banks.get(pos.to_n()).copied().unwrap_or(0);
+        Some((pos, self.started + control.per_action as u64 * 1000 + bank))
+    }
+
+    pub fn snapshot(&self, control: &TimeControl, now: u64) -> Option<ClockSnapshot> {
+        let pos = self.pos?;
+        let limit = self.started + control.per_action as u64 * 1000;
+        // the bank of the player who must act is running once his time is over
+        let mut banks = self.banks.clone();
+        if let Some(bank) = banks.get_mut(pos.to_n()) {
+            *bank = bank.saturating_sub(now.saturating_sub(limit));
+        }
+        Some(ClockSnapshot {
+            pos,
+            remaining: limit.saturating_sub(now),
+            banks,
+        })
+    }
+}
+
+/// Milliseconds since the unix epoch
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bank() {
+        let control = TimeControl {
+            per_action: 10,
+            bank: 30,
+        };
+        let mut clock = Clock::default();
+        clock.reset(&control, 4);
+        let pos0 = pos::PlayerPos::from_n(0, 4);
+        let pos1 = pos::PlayerPos::from_n(1, 4);
+        clock.start(&control, Some(pos0), 1000);
+        assert_eq!(Some((pos0, 41_000)), clock.deadline(&control));
+
+        // five seconds over the limit
+        let snapshot = clock.snapshot(&control, 16_000).unwrap();
+        assert_eq!(0, snapshot.remaining);
+        assert_eq!(vec![25_000, 30_000, 30_000, 30_000], snapshot.banks);
+        clock.start(&control, Some(pos1), 16_000);
+        let snapshot = clock.snapshot(&control, 20_000).unwrap();
+        assert_eq!(pos1, snapshot.pos);
+        assert_eq!(6000, snapshot.remaining);
+        assert_eq!(vec![25_000, 30_000, 30_000, 30_000], snapshot.banks);
+
+        // the banks are full again for the next deal
+        clock.reset(&control, 4);
+        clock.start(&control, Some(pos0), 50_000);
+        assert_eq!(Some((pos0, 90_000)), clock.deadline(&control));
+    }
+}
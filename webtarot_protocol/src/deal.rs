@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-use tarotgame::{bid, cards, deal, pos, trick, AnnounceType};
+use tarotgame::fair::FairProof;
+use tarotgame::{bid, cards, deal, points, pos, trick, AnnounceType};
 
 /// Describe a single deal.
 #[derive(Clone, Serialize, Deserialize)]
@@ -46,6 +47,13 @@ impl Deal {
         }
     }
 
+    pub fn dog(&self) -> cards::Hand {
+        match self {
+            Deal::Bidding(auction) => auction.dog(),
+            Deal::Playing(deal) => deal.dog(),
+        }
+    }
+
     pub fn deal_contract(&self) -> Option<&bid::Contract> {
         match self {
             Deal::Bidding(auction) => auction.current_contract(),
@@ -83,6 +91,7 @@ impl Deal {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DealSnapshot {
     pub hand: cards::Hand,
     pub current: pos::PlayerPos,
@@ -94,7 +103,9 @@ pub struct DealSnapshot {
     pub initial_dog: cards::Hand,
     pub dog: cards::Hand, // set to empty hand until the deal is over
     pub taker_diff: f32,
-    // pub tricks: Vec<trick::Trick>,
+    /// Finished tricks of the deal, when the game rules let the players review them
+    #[serde(default)]
+    pub tricks: Vec<trick::Trick>,
     pub announces: Vec<Vec<AnnounceType>>,
 }
 
@@ -108,3 +119,75 @@ impl DealSnapshot {
         // }
     }
 }
+
+/// Everything about a finished deal, revealed to all the players
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DealSummary {
+    pub result: deal::DealResult,
+    pub contract: bid::Contract,
+    pub king: Option<cards::Card>,
+    pub partner: pos::PlayerPos,
+    /// Hands of the players as they were dealt, by position
+    pub hands: Vec<cards::Hand>,
+    /// Dog as it was dealt
+    pub dog: cards::Hand,
+    /// Cards put aside by the taker, the dog itself when it wasn't taken
+    pub discard: cards::Hand,
+    /// Seeds the cards were shuffled with, on provably fair tables
+    #[serde(default)]
+    pub fair: Option<FairProof>,
+}
+
+impl DealSummary {
+    /// Points the taker needed, given the oudlers of his team
+    pub fn needed_points(&self) -> f32 {
+        match self.result {
+            deal::DealResult::GameOver { oudlers_count, .. } => {
+                points::needed_points(oudlers_count)
+            }
+            deal::DealResult::Nothing => 0.0,
+        }
+    }
+
+    /// Points made by the taker's team
+    pub fn taker_points(&self) -> f32 {
+        match self.result {
+            deal::DealResult::GameOver { taker_diff, .. } => self.needed_points() + taker_diff,
+            deal::DealResult::Nothing => 0.0,
+        }
+    }
+
+    /// Contract value before the multiplier : 25 points plus the difference, and the petit au
+    /// bout bonus
+    pub fn contract_points(&self) -> f32 {
+        match self.result {
+            deal::DealResult::GameOver {
+                taker_diff,
+                petit_bonus,
+                ..
+            } => {
+                let won = if taker_diff < 0.0 {
+                    taker_diff - 25.0
+                } else {
+                    taker_diff + 25.0
+                };
+                won + petit_bonus
+            }
+            deal::DealResult::Nothing => 0.0,
+        }
+    }
+
+    /// Points each defender pays to the taker's team, negative when the contract failed
+    pub fn deal_points(&self) -> f32 {
+        match self.result {
+            deal::DealResult::GameOver {
+                multiplier,
+                slam_bonus,
+                poignees_bonus,
+                ..
+            } => multiplier as f32 * self.contract_points() + slam_bonus + poignees_bonus,
+            deal::DealResult::Nothing => 0.0,
+        }
+    }
+}
@@ -1,19 +1,25 @@
 use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use uuid::Uuid;
 
 use crate::{PlayCommand, ProtocolError};
+use tarotgame::fair::{FairDeal, FairProof, Seed};
 use tarotgame::{bid, cards, deal, deal::DealState, pos, trick, Announce};
 pub use webgame_protocol::GameEventsListener;
 use webgame_protocol::{GameManager, GameState, PlayerInfo, ProtocolErrorKind};
 
+use crate::archive::DealArchive;
+use crate::clock::{now_millis, Clock, ClockSnapshot, TimeControl};
 use crate::deal::Deal;
-pub use crate::deal::DealSnapshot;
-use crate::message::{DebugOperation, TarotVariant};
-use crate::player::{GamePlayerState, PlayerRole};
+pub use crate::deal::{DealSnapshot, DealSummary};
+use crate::message::{DebugOperation, ErrorCode, OperationOutcome, TarotVariant};
+use crate::player::{AbsentPlayer, GamePlayerState, PlayerRole};
+use crate::replay::DealReplay;
 use crate::turn::Turn;
+use crate::update::{EventLog, GameEvent, GameView, SentSnapshots, StateUpdate};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct TarotGameState {
@@ -25,6 +31,48 @@ pub struct TarotGameState {
     pub deal_history: Vec<(Uuid, PlayCommand)>,
     first: pos::PlayerPos,
     scores: Vec<Vec<f32>>,
+    #[serde(default)]
+    replace_absent_after: u32,
+    #[serde(default = "default_bot_delay")]
+    bot_delay: u32,
+    /// Seats of the players who left during the game
+    #[serde(default)]
+    absent: Vec<AbsentPlayer>,
+    /// Players watching the game without a seat
+    #[serde(default)]
+    spectators: BTreeMap<Uuid, PlayerInfo>,
+    #[serde(default)]
+    visibility: SpectatorVisibility,
+    #[serde(default)]
+    review_tricks: bool,
+    /// No limit when none
+    #[serde(default)]
+    time_control: Option<TimeControl>,
+    #[serde(default)]
+    clock: Clock,
+    /// Seed the cards of the deal were shuffled with, none when they were given otherwise
+    #[serde(default)]
+    seed: Option<[u8; 32]>,
+    #[serde(default)]
+    training: bool,
+    /// Hands and dog given for the next deal
+    #[serde(default)]
+    next_hands: Option<(Vec<cards::Hand>, cards::Hand)>,
+    #[serde(default)]
+    provably_fair: bool,
+    /// Secret of the next deal, committed to before the players give their entropy
+    #[serde(default)]
+    fair: Option<FairDeal>,
+    /// What the current deal was shuffled with, revealed at its end
+    #[serde(default)]
+    proof: Option<FairProof>,
+    /// Token of the seats offered to bots, by id of the absent player
+    #[serde(skip)]
+    seat_tokens: BTreeMap<Uuid, Uuid>,
+    #[serde(skip)]
+    sent: SentSnapshots,
+    #[serde(skip)]
+    events: EventLog,
 }
 //
 // pub struct TarotGameManager {
@@ -53,11 +101,11 @@ impl<'a, Listener: GameEventsListener<(PlayEvent, TarotGameState)> + PartialEq>
     }
 
     pub fn set_play(&mut self, pid: Uuid, card: cards::Card) -> Result<(), ProtocolError> {
-        let pos = self.state.players.get(&pid).map(|p| p.pos).unwrap();
-        let state = self.state.deal.deal_state_mut().ok_or(ProtocolError::new(
-            ProtocolErrorKind::InternalError,
-            "Unknown deal state",
-        ))?;
+        let pos = self.state.seated_pos(pid)?;
+        let state = self.state.deal.deal_state_mut().ok_or(
+            ProtocolError::new(ProtocolErrorKind::InternalError, "Unknown deal state")
+                .with_code(ErrorCode::UnknownDealState),
+        )?;
         match state.play_card(pos, card)? {
             deal::TrickResult::Nothing => {}
             deal::TrickResult::TrickOver(_winner, deal::DealResult::Nothing) => {
@@ -83,7 +131,11 @@ impl<'a, Listener: GameEventsListener<(PlayEvent, TarotGameState)> + PartialEq>
                         .unwrap()
                         .revert_trick();
                     self.state.end_last_trick();
+                    let summary = self.state.deal_summary(result.clone());
                     self.emit((PlayEvent::EndDeal(result), self.state.clone()));
+                    if let Some(summary) = summary {
+                        self.emit((PlayEvent::DealSummary(summary), self.state.clone()));
+                    }
                     self.state.next_deal();
                 }
             }
@@ -102,6 +154,107 @@ impl<'a, Listener: GameEventsListener<(PlayEvent, TarotGameState)> + PartialEq>
         Ok(())
     }
 
+    pub fn set_bid(
+        &mut self,
+        pid: Uuid,
+        target: bid::Target,
+        slam: bool,
+    ) -> Result<(), ProtocolError> {
+        self.state.set_bid(pid, target, slam)?;
+        self.emit((PlayEvent::Bid(pid, target), self.state.clone()));
+        if slam {
+            self.emit((PlayEvent::SlamDeclared(pid), self.state.clone()));
+        }
+        Ok(())
+    }
+
+    pub fn set_pass(&mut self, pid: Uuid) -> Result<(), ProtocolError> {
+        if self.state.pass(pid)? {
+            // the proof goes with the cancelled deal, before the cards are dealt again
+            self.emit((PlayEvent::Pass(pid), self.state.clone()));
+            let proof = self.state.proof.clone();
+            self.emit((PlayEvent::DealCancelled(proof), self.state.clone()));
+            self.state.next_deal();
+            self.state.update_turn();
+        } else {
+            self.state.update_turn();
+            self.emit((PlayEvent::Pass(pid), self.state.clone()));
+        }
+        Ok(())
+    }
+
+    pub fn call_king(&mut self, pid: Uuid, card: cards::Card) -> Result<(), ProtocolError> {
+        if self.state.turn != Turn::CallingKing {
+            return Err(ProtocolError::new(
+                ProtocolErrorKind::BadState,
+                "not the time to call a king",
+            )
+            .with_code(ErrorCode::NotCallingKing));
+        }
+        self.state.call_king(pid, card);
+        if self.state.deal.deal_state().and_then(|s| s.king()) != Some(card) {
+            return Err(
+                ProtocolError::new(ProtocolErrorKind::BadState, "can't call this card")
+                    .with_code(ErrorCode::InvalidKing(card)),
+            );
+        }
+        self.emit((PlayEvent::KingCalled(pid, card), self.state.clone()));
+        Ok(())
+    }
+
+    pub fn make_dog(
+        &mut self,
+        pid: Uuid,
+        cards: cards::Hand,
+        slam: bool,
+    ) -> Result<(), ProtocolError> {
+        self.state.make_dog(pid, cards, slam)?;
+        // Only the trumps put in the dog are shown to the other players
+        let mut public = cards::Hand::new();
+        for card in cards.list() {
+            if card.suit() == cards::Suit::Trump {
+                public.add(card);
+            }
+        }
+        self.emit((PlayEvent::DogMade(pid, public), self.state.clone()));
+        if slam {
+            self.emit((PlayEvent::SlamDeclared(pid), self.state.clone()));
+        }
+        Ok(())
+    }
+
+    /// Acts for the player who ran out of time at `now` : he passes during the auction, and
+    /// plays his lowest cards otherwise. Returns false when nobody ran out of time.
+    pub fn play_timeout(&mut self, now: u64) -> Result<bool, ProtocolError> {
+        let pid = match self.state.timed_out(now) {
+            Some(pid) => pid,
+            None => return Ok(false),
+        };
+        let pos = self.state.seated_pos(pid)?;
+        let unknown_state = || {
+            ProtocolError::new(ProtocolErrorKind::InternalError, "Unknown deal state")
+                .with_code(ErrorCode::UnknownDealState)
+        };
+        self.emit((PlayEvent::TimedOut(pid), self.state.clone()));
+        match self.state.turn {
+            Turn::Bidding(_) => self.set_pass(pid)?,
+            Turn::CallingKing => {
+                let card = self.state.timeout_king(pos).ok_or_else(unknown_state)?;
+                self.call_king(pid, card)?
+            }
+            Turn::MakingDog => {
+                let dog = self.state.timeout_dog(pos).ok_or_else(unknown_state)?;
+                self.make_dog(pid, dog, false)?
+            }
+            _ => {
+                let card = self.state.timeout_card(pos).ok_or_else(unknown_state)?;
+                self.set_play(pid, card)?;
+                self.state.deal_history.push((pid, PlayCommand { card }));
+            }
+        }
+        Ok(true)
+    }
+
     pub fn get_game(&self) -> &TarotGameState {
         self.state
     }
@@ -140,6 +293,23 @@ impl Default for TarotGameState {
             deal_history: vec![],
             first: pos::PlayerPos::from_n(0, 5),
             scores: vec![],
+            replace_absent_after: DEFAULT_REPLACE_ABSENT_AFTER,
+            bot_delay: DEFAULT_BOT_DELAY,
+            absent: vec![],
+            spectators: BTreeMap::new(),
+            visibility: SpectatorVisibility::default(),
+            review_tricks: false,
+            time_control: None,
+            clock: Clock::default(),
+            seed: None,
+            training: false,
+            next_hands: None,
+            provably_fair: false,
+            fair: None,
+            proof: None,
+            seat_tokens: BTreeMap::new(),
+            sent: SentSnapshots::default(),
+            events: EventLog::default(),
         }
     }
 }
@@ -199,18 +369,27 @@ impl GameState for TarotGameState {
     type PlayerRole = PlayerRole;
 
     type GamePlayerState = GamePlayerState;
-    type Snapshot = GameStateSnapshot;
+    type Snapshot = StateUpdate;
     type Operation = DebugOperation;
     type VariantParameters = VariantSettings;
 
     fn set_variant(&mut self, variant: TarotVariant) {
         self.nb_players = variant.parameters.nb_players;
-        self.deal = Deal::new(pos::PlayerPos::from_n(0, self.nb_players));
+        self.replace_absent_after = variant.parameters.replace_absent_after;
+        self.bot_delay = variant.parameters.bot_delay;
+        self.visibility = variant.parameters.spectators;
+        self.review_tricks = variant.parameters.review_tricks;
+        self.time_control = variant.parameters.time_control;
+        self.training = variant.parameters.training;
+        // the host of a training table chooses the cards
+        self.provably_fair = variant.parameters.provably_fair && !self.training;
         self.first = pos::PlayerPos::from_n(0, self.nb_players);
+        self.new_deal();
     }
 
     fn is_joinable(&self) -> bool {
-        self.turn == Turn::Pregame
+        // newcomers watch the game when there is no free seat
+        true
     }
 
     fn get_players(&self) -> &BTreeMap<Uuid, GamePlayerState> {
@@ -218,10 +397,36 @@ impl GameState for TarotGameState {
     }
 
     fn add_player(&mut self, player_info: PlayerInfo) -> pos::PlayerPos {
+        // the player gets the whole game state first
+        self.sent.forget(player_info.id);
+        if let Some(idx) = self
+            .absent
+            .iter()
+            .position(|absent| absent.player.id == player_info.id)
+        {
+            // The player is back, he takes his seat from the bot if there is one
+            let absent = self.absent.remove(idx);
+            self.withdraw_seat(absent.player.id);
+            let seated = absent.substitute.map_or(absent.player.id, |bot| bot.id);
+            self.seat(seated, player_info);
+            return absent.pos;
+        }
+
         if self.players.contains_key(&player_info.id) {
             return self.players.get(&player_info.id).unwrap().pos;
         }
 
+        if self.is_in_progress() {
+            // The player can only watch the game, the seats of the absent players are taken by
+            // the bots they are offered to (see `take_seat`)
+            self.spectators.insert(player_info.id, player_info);
+            return self.first;
+        }
+        if self.players.len() >= self.nb_players as usize {
+            self.spectators.insert(player_info.id, player_info);
+            return self.first;
+        }
+
         //Default pos
         let nb_players = self.players.len();
         let mut newpos = pos::PlayerPos::from_n(nb_players, self.nb_players);
@@ -246,7 +451,40 @@ impl GameState for TarotGameState {
     }
 
     fn remove_player(&mut self, player_id: Uuid) -> bool {
-        self.players.remove(&player_id).is_some()
+        self.sent.forget(player_id);
+        if self.spectators.remove(&player_id).is_some() {
+            return true;
+        }
+        if !self.is_in_progress() || !self.players.contains_key(&player_id) {
+            return self.players.remove(&player_id).is_some();
+        }
+
+        // The seat is kept until the player comes back or a bot replaces him
+        if let Some(idx) = self
+            .absent
+            .iter()
+            .position(|absent| absent.substitute.as_ref().map(|bot| bot.id) == Some(player_id))
+        {
+            // The bot left, the seat waits for a new one
+            let absent = &mut self.absent[idx];
+            absent.substitute = None;
+            absent.invited_by = None;
+            absent.votes.clear();
+            let player_info = absent.player.clone();
+            self.withdraw_seat(player_info.id);
+            self.seat(player_id, player_info);
+        } else {
+            let state = &self.players[&player_id];
+            self.absent.push(AbsentPlayer {
+                player: state.player.clone(),
+                pos: state.pos,
+                since: now(),
+                votes: vec![],
+                invited_by: None,
+                substitute: None,
+            });
+        }
+        true
     }
 
     fn get_player_role(&self, player_id: Uuid) -> Option<PlayerRole> {
@@ -266,21 +504,424 @@ impl GameState for TarotGameState {
             .map(|p| p.1)
     }
 
-    // Creates a view of the game for a player
-    fn make_snapshot(&self, player_id: Uuid) -> GameStateSnapshot {
-        let contract = self.deal.deal_contract().cloned();
-        let mut players = vec![];
-        for (&_other_player_id, player_state) in self.players.iter() {
-            players.push(player_state.clone());
+    fn make_snapshot(&self, player_id: Uuid) -> StateUpdate {
+        // sent by the game server when somebody joins or leaves : the next updates are based on it
+        self.sent.full(player_id, self.view(player_id))
+    }
+
+    fn set_player_ready(&mut self, player_id: Uuid) -> bool {
+        if self.is_in_progress() {
+            // A player coming back or replacing an absent one keeps his role
+            if let Some(player_state) = self.players.get_mut(&player_id) {
+                player_state.ready = true;
+            }
+            return false;
+        }
+        if let Some(player_state) = self.players.get_mut(&player_id) {
+            player_state.ready = true;
+            player_state.role = PlayerRole::PreDeal;
+
+            // Check if we start the next deal
+            let mut count = 0;
+            for player in self.players.values() {
+                if player.role == PlayerRole::PreDeal {
+                    count = count + 1;
+                }
+            }
+            // println!("set_player_ready, count = {} ; nb_players = {}", count, self.nb_players);
+            if count == self.nb_players {
+                if self.provably_fair && self.proof.is_none() {
+                    // deal the cards committed to, now that the players gave their entropy
+                    self.new_deal();
+                }
+                self.turn =
+                    Turn::Bidding((bid::AuctionState::Bidding, pos::PlayerPos::from_n(0, count)));
+                self.reset_clock();
+                self.restart_clock();
+                return true;
+            }
         }
-        players.sort_by(|a, b| a.pos.to_n().cmp(&b.pos.to_n()));
-        let pos = self.players[&player_id].pos;
+        false
+    }
+
+    fn set_player_not_ready(&mut self, player_id: Uuid) {
+        if let Some(player_state) = self.players.get_mut(&player_id) {
+            player_state.ready = false;
+        }
+    }
+
+    fn update_init_state(&mut self) -> bool {
+        // self.next();
+        false
+    }
+
+    fn manage_operation(&mut self, operation: Self::Operation) -> Option<String> {
+        let outcome = match operation {
+            Self::Operation::SetSeed(_) if self.provably_fair => OperationOutcome::Failed {
+                // the players were committed to the deal they got
+                error: ProtocolError::new(
+                    ProtocolErrorKind::BadState,
+                    "the deals of a provably fair game can't be changed",
+                ),
+            },
+            Self::Operation::SetSeed(seed) => {
+                let (hands, dog) = tarotgame::deal_seeded_hands(seed, self.nb_players as usize);
+                if let Some(auction) = self.deal.deal_auction_mut() {
+                    auction.set_hands(hands, dog);
+                    self.seed = Some(seed);
+                    self.proof = None;
+                    OperationOutcome::Done
+                } else {
+                    OperationOutcome::Failed {
+                        error: ProtocolError::new(
+                            ProtocolErrorKind::BadState,
+                            "the cards can only be dealt again during the auction",
+                        ),
+                    }
+                }
+            }
+            Self::Operation::SetHands(hands, dog) => match self.give_hands(hands, dog) {
+                Ok(()) => OperationOutcome::Done,
+                Err(error) => OperationOutcome::Failed { error },
+            },
+            Self::Operation::ShowState => self.state_outcome(),
+        };
+        Some(outcome.to_json())
+    }
+
+    fn debug_bychat(&mut self, operation: &str) -> Option<String> {
+        let outcome = if operation == "debugbot" {
+            self.state_outcome()
+        } else {
+            OperationOutcome::Failed {
+                error: ProtocolError::new(ProtocolErrorKind::InvalidCommand, "unknown command"),
+            }
+        };
+        Some(outcome.to_json())
+    }
+}
+
+impl TarotGameState {
+    pub fn get_turn(&self) -> Turn {
+        self.turn
+    }
+
+    /// Whole state of the game, for the debug operations. The secret of the next deal is left
+    /// out, as well as the proof of the current one until it is revealed at its end.
+    fn state_outcome(&self) -> OperationOutcome {
+        match serde_json::to_value(self) {
+            Ok(mut state) => {
+                if let Some(fields) = state.as_object_mut() {
+                    fields.remove("fair");
+                    if self.is_in_progress() {
+                        fields.remove("proof");
+                    }
+                }
+                OperationOutcome::State { state }
+            }
+            Err(e) => OperationOutcome::Failed {
+                error: ProtocolError::new(ProtocolErrorKind::InternalError, e.to_string()),
+            },
+        }
+    }
+
+    /// Position of a player sitting at the table
+    fn seated_pos(&self, pid: Uuid) -> Result<pos::PlayerPos, ProtocolError> {
+        self.players.get(&pid).map(|p| p.pos).ok_or(
+            ProtocolError::new(ProtocolErrorKind::BadState, "not seated at the table")
+                .with_code(ErrorCode::NotSeated),
+        )
+    }
+
+    /// A seat is free for a newcomer : before the game starts, or when a player left
+    pub fn has_free_seat(&self) -> bool {
+        (self.turn == Turn::Pregame && self.players.len() < self.nb_players as usize)
+            || !self.absent.is_empty()
+    }
+
+    pub fn is_spectator(&self, pid: Uuid) -> bool {
+        self.spectators.contains_key(&pid)
+    }
+
+    /// Everybody viewing the game, seated or spectating
+    pub fn viewers(&self) -> Vec<Uuid> {
+        self.players
+            .keys()
+            .chain(self.spectators.keys())
+            .copied()
+            .collect()
+    }
+
+    /// The players have started the game and not finished it yet
+    pub fn is_in_progress(&self) -> bool {
+        self.turn != Turn::Pregame && self.turn != Turn::Endgame
+    }
+
+    /// `pid` had a seat and left it, his hand waits for him
+    pub fn is_absent(&self, pid: Uuid) -> bool {
+        self.absent.iter().any(|absent| absent.player.id == pid)
+    }
+
+    /// Gives the seat of the player `seated` to `player_info`
+    fn seat(&mut self, seated: Uuid, player_info: PlayerInfo) {
+        if let Some(state) = self.players.remove(&seated) {
+            self.players.insert(
+                player_info.id,
+                GamePlayerState {
+                    player: player_info,
+                    ..state
+                },
+            );
+        }
+    }
+
+    /// Game loaded back after a restart of the server : nobody is connected anymore, the
+    /// players get their seats back when they join again, and the bots they had can be voted
+    /// in again.
+    pub fn restored(mut self) -> Self {
+        self.spectators.clear();
+        for absent in self.absent.iter_mut() {
+            if let Some(bot) = absent.substitute.take() {
+                if let Some(state) = self.players.remove(&bot.id) {
+                    self.players.insert(
+                        absent.player.id,
+                        GamePlayerState {
+                            player: absent.player.clone(),
+                            ..state
+                        },
+                    );
+                }
+            }
+            absent.invited_by = None;
+            absent.votes.clear();
+        }
+        self.seat_tokens.clear();
+        if self.is_in_progress() {
+            let since = now();
+            for state in self.players.values() {
+                if !self.absent.iter().any(|absent| absent.pos == state.pos) {
+                    self.absent.push(AbsentPlayer {
+                        player: state.player.clone(),
+                        pos: state.pos,
+                        since,
+                        votes: vec![],
+                        invited_by: None,
+                        substitute: None,
+                    });
+                }
+            }
+        }
+        // the time spent while the server was down is not counted
+        self.restart_clock();
+        self
+    }
+
+    /// Vote of `pid` for a bot to replace the absent player at `pos`. The player is given the
+    /// time of the game settings to come back, after which a single vote replaces him. When
+    /// the settings give no time, most of the players must vote.
+    ///
+    /// Once accepted, the seat is offered with a one-time token, returned to be given to the bot
+    /// started for it.
+    pub fn replace_absent(
+        &mut self,
+        pid: Uuid,
+        pos: pos::PlayerPos,
+    ) -> Result<Option<Uuid>, ProtocolError> {
+        let voters = self.players.len()
+            - self
+                .absent
+                .iter()
+                .filter(|absent| absent.substitute.is_none())
+                .count();
+        if !self.players.contains_key(&pid) || self.absent.iter().any(|a| a.player.id == pid) {
+            return Err(
+                ProtocolError::new(ProtocolErrorKind::BadState, "only players can vote")
+                    .with_code(ErrorCode::NotPlayer),
+            );
+        }
+        let delay = self.replace_absent_after;
+        let absent = self
+            .absent
+            .iter_mut()
+            .find(|absent| absent.pos == pos)
+            .ok_or(
+                ProtocolError::new(
+                    ProtocolErrorKind::NotFound,
+                    "no absent player at this position",
+                )
+                .with_code(ErrorCode::NoAbsentPlayer),
+            )?;
+        if absent.substitute.is_some() || absent.invited_by.is_some() {
+            // already accepted
+            return Ok(None);
+        }
+        if !absent.votes.contains(&pid) {
+            absent.votes.push(pid);
+        }
+        let accepted = if delay > 0 {
+            absent.grace_expired(delay)
+        } else {
+            absent.votes.len() * 2 > voters
+        };
+        if !accepted {
+            return Ok(None);
+        }
+        absent.invited_by = Some(pid);
+        let token = Uuid::new_v4();
+        self.seat_tokens.insert(absent.player.id, token);
+        Ok(Some(token))
+    }
+
+    /// The bot `pid`, watching the game, takes the seat offered with `token`
+    pub fn take_seat(&mut self, pid: Uuid, token: Uuid) -> Result<(), ProtocolError> {
+        let absent_id = self
+            .seat_tokens
+            .iter()
+            .find(|(_, offered)| **offered == token)
+            .map(|(absent_id, _)| *absent_id)
+            .ok_or(
+                ProtocolError::new(
+                    ProtocolErrorKind::BadState,
+                    "no seat offered with this token",
+                )
+                .with_code(ErrorCode::SeatNotOffered),
+            )?;
+        let player_info = self.spectators.remove(&pid).ok_or(
+            ProtocolError::new(
+                ProtocolErrorKind::BadState,
+                "only spectators can take a seat",
+            )
+            .with_code(ErrorCode::NotSpectator),
+        )?;
+        self.withdraw_seat(absent_id);
+        if let Some(absent) = self
+            .absent
+            .iter_mut()
+            .find(|absent| absent.player.id == absent_id)
+        {
+            absent.substitute = Some(player_info.clone());
+        }
+        // the bot gets the whole game state with its hand
+        self.sent.forget(pid);
+        self.seat(absent_id, player_info);
+        Ok(())
+    }
+
+    /// The seat of `absent_id` is no longer offered
+    fn withdraw_seat(&mut self, absent_id: Uuid) {
+        self.seat_tokens.remove(&absent_id);
+    }
+
+    /// Creates a view of the game for a player
+    pub fn player_snapshot(&self, player_id: Uuid) -> GameStateSnapshot {
+        // Bots given back the seat of the player they replaced don't see any hand
+        let seat = self
+            .players
+            .get(&player_id)
+            .map(|state| state.pos.pos as usize);
+        GameStateSnapshot {
+            nb_players: self.nb_players,
+            players: self.sorted_players(),
+            scores: self.scores.clone(),
+            turn: self.turn,
+            deal: self.deal_snapshot(seat),
+            replace_absent_after: self.replace_absent_after,
+            bot_delay: Some(self.bot_delay),
+            training: self.training,
+            fair: self.fair.as_ref().map(|fair| FairSnapshot {
+                commitment: fair.commitment(),
+                contributed: fair.contributors(),
+            }),
+            absent: self.absent.clone(),
+            spectators: self.spectators.values().cloned().collect(),
+            clock: self.clock_snapshot(),
+            next_event: self.events.next_seq(),
+        }
+    }
+
+    /// Creates a view of the game for a spectator
+    pub fn spectator_snapshot(&self) -> SpectatorSnapshot {
+        let mut deal = self.deal_snapshot(None);
+        if self.visibility == SpectatorVisibility::All && self.is_in_progress() {
+            deal.dog = self.deal.dog();
+        }
+        SpectatorSnapshot {
+            nb_players: self.nb_players,
+            players: self.sorted_players(),
+            scores: self.scores.clone(),
+            turn: self.turn,
+            deal,
+            hands: self.visible_hands(),
+            visibility: self.visibility,
+            absent: self.absent.clone(),
+            spectators: self.spectators.values().cloned().collect(),
+            clock: self.clock_snapshot(),
+            next_event: self.events.next_seq(),
+        }
+    }
+
+    fn clock_snapshot(&self) -> Option<ClockSnapshot> {
+        let control = self.time_control.as_ref()?;
+        self.clock.snapshot(control, now_millis())
+    }
+
+    /// View of the game for someone at the table or watching it
+    pub fn view(&self, player_id: Uuid) -> GameView {
+        if self.is_spectator(player_id) {
+            GameView::Spectator(self.spectator_snapshot())
+        } else {
+            GameView::Player(self.player_snapshot(player_id))
+        }
+    }
+
+    fn sorted_players(&self) -> Vec<GamePlayerState> {
+        let mut players: Vec<GamePlayerState> = self.players.values().cloned().collect();
+        players.sort_by_key(|state| state.pos.to_n());
+        players
+    }
+
+    /// Hands of the players shown to the spectators, empty when they are hidden
+    fn visible_hands(&self) -> Vec<cards::Hand> {
+        if !self.is_in_progress() {
+            return vec![];
+        }
+        match self.visibility {
+            SpectatorVisibility::Hidden => vec![],
+            SpectatorVisibility::All => self.deal.hands().clone(),
+            SpectatorVisibility::Delayed(delay) => {
+                let state = match self.deal.deal_state() {
+                    Some(state) => state,
+                    None => return vec![],
+                };
+                let nb_players = self.nb_players as usize;
+                let tricks = state.tricks();
+                let done = state.completed_tricks().len();
+                if done < delay as usize {
+                    return vec![];
+                }
+                // The hands as they were `delay` tricks ago
+                let mut hands = state.hands().clone();
+                for trick in &tricks[done - delay as usize..] {
+                    for (pos, card) in trick.cards[0..nb_players].iter().enumerate() {
+                        if let Some(card) = card {
+                            hands[pos].add(*card);
+                        }
+                    }
+                }
+                hands
+            }
+        }
+    }
+
+    /// Current deal, as seen from the given seat
+    fn deal_snapshot(&self, seat: Option<usize>) -> DealSnapshot {
+        let contract = self.deal.deal_contract().cloned();
         let mut scores = vec![0.0; self.nb_players as usize];
         let mut dog = cards::Hand::new();
         let mut taker_diff = 0.0;
         let mut announces = vec![vec![]; self.nb_players as usize];
         let mut trick_count = 0;
-        let deal = match self.deal.deal_state() {
+        match self.deal.deal_state() {
             Some(state) => {
                 // In Playing phase
                 announces = state.announces.clone();
@@ -296,6 +937,11 @@ impl GameState for TarotGameState {
                     dog = state.dog();
                 };
                 let last_trick = state.current_trick().clone();
+                let tricks = if self.review_tricks {
+                    state.completed_tricks().to_vec()
+                } else {
+                    vec![]
+                };
                 let initial_dog = if self.turn == Turn::MakingDog {
                     state.dog()
                 } else {
@@ -320,7 +966,7 @@ impl GameState for TarotGameState {
                 }
 
                 DealSnapshot {
-                    hand: state.hands()[pos.pos as usize],
+                    hand: seat.map_or(cards::Hand::new(), |pos| state.hands()[pos]),
                     current: state.next_player(),
                     contract,
                     king: state.king(),
@@ -331,12 +977,13 @@ impl GameState for TarotGameState {
                     initial_dog,
                     dog,
                     taker_diff,
+                    tricks,
                     announces,
                 }
             }
             None => DealSnapshot {
                 // In bidding phase
-                hand: self.deal.hands()[pos.pos as usize],
+                hand: seat.map_or(cards::Hand::new(), |pos| self.deal.hands()[pos]),
                 current: self.deal.next_player(),
                 contract,
                 king: None,
@@ -346,80 +993,107 @@ impl GameState for TarotGameState {
                 initial_dog: cards::Hand::new(),
                 dog,
                 taker_diff,
+                tricks: vec![],
                 announces,
             },
-        };
-        GameStateSnapshot {
-            nb_players: self.nb_players,
-            players,
-            scores: self.scores.clone(),
-            turn: self.turn,
-            deal,
         }
     }
 
-    fn set_player_ready(&mut self, player_id: Uuid) -> bool {
-        if let Some(player_state) = self.players.get_mut(&player_id) {
-            player_state.ready = true;
-            player_state.role = PlayerRole::PreDeal;
+    /// Numbers an event before it is broadcasted, and keeps it for the players who reconnect
+    pub fn log_event(&self, event: PlayEvent) -> GameEvent {
+        self.events.push(event)
+    }
 
-            // Check if we start the next deal
-            let mut count = 0;
-            for player in self.players.values() {
-                if player.role == PlayerRole::PreDeal {
-                    count = count + 1;
-                }
-            }
-            // println!("set_player_ready, count = {} ; nb_players = {}", count, self.nb_players);
-            if count == self.nb_players {
-                self.turn =
-                    Turn::Bidding((bid::AuctionState::Bidding, pos::PlayerPos::from_n(0, count)));
-                return true;
-            }
-        }
-        false
+    pub fn events_since(&self, seq: u64) -> Vec<GameEvent> {
+        self.events.since(seq)
     }
 
-    fn set_player_not_ready(&mut self, player_id: Uuid) {
-        if let Some(player_state) = self.players.get_mut(&player_id) {
-            player_state.ready = false;
-        }
+    /// Changes of `view` since the last update sent to the player, who must receive this one
+    pub fn next_update(&self, player_id: Uuid, view: GameView) -> StateUpdate {
+        self.sent.update(player_id, view)
     }
 
-    fn update_init_state(&mut self) -> bool {
-        // self.next();
-        false
+    /// Whole view of the game for a player who missed some updates
+    pub fn resync(&mut self, player_id: Uuid) -> StateUpdate {
+        let view = self.view(player_id);
+        self.sent.full(player_id, view)
     }
 
-    fn manage_operation(&mut self, operation: Self::Operation) -> Option<String> {
-        match operation {
-            Self::Operation::SetSeed(seed) => {
-                let (hands, dog) = tarotgame::deal_seeded_hands(seed, self.nb_players as usize);
-                self.deal
-                    .deal_auction_mut()
-                    .map(|auction| auction.set_hands(hands, dog));
-                None
-            }
-            Self::Operation::ShowState => {
-                // println!("Debug state : {}", serde_json::to_string(self).unwrap());
-                serde_json::to_string(self).ok()
-            }
+    /// The table host, who can change the game settings, is the player on the first seat
+    pub fn is_host(&self, pid: Uuid) -> bool {
+        self.player_by_pos(pos::PlayerPos::from_n(0, self.nb_players))
+            .map_or(false, |state| state.player.id == pid)
+    }
+
+    /// Sets the time, in milliseconds, the bots wait before playing
+    pub fn set_bot_delay(&mut self, pid: Uuid, delay: u32) -> Result<(), ProtocolError> {
+        if !self.is_host(pid) {
+            return Err(ProtocolError::new(
+                ProtocolErrorKind::BadState,
+                "only the host can change the game settings",
+            )
+            .with_code(ErrorCode::NotHost));
         }
+        self.bot_delay = delay.min(MAX_BOT_DELAY);
+        Ok(())
     }
 
-    fn debug_bychat(&mut self, operation: &str) -> Option<String> {
-        // println!("Debug state : {}", serde_json::to_string(self).unwrap());
-        if operation == "debugbot" {
-            serde_json::to_string(self).ok()
-        } else {
-            Some("unknown command".into())
+    /// Gives the hands and the dog of the next deal, on training tables
+    pub fn set_hands(
+        &mut self,
+        pid: Uuid,
+        hands: Vec<cards::Hand>,
+        dog: cards::Hand,
+    ) -> Result<(), ProtocolError> {
+        if !self.training {
+            return Err(ProtocolError::new(
+                ProtocolErrorKind::BadState,
+                "the hands can only be given on training tables",
+            )
+            .with_code(ErrorCode::NotTraining));
         }
+        if !self.is_host(pid) {
+            return Err(ProtocolError::new(
+                ProtocolErrorKind::BadState,
+                "only the host can give the hands",
+            )
+            .with_code(ErrorCode::NotHost));
+        }
+        self.give_hands(hands, dog)
     }
-}
 
-impl TarotGameState {
-    pub fn get_turn(&self) -> Turn {
-        self.turn
+    /// Entropy of `pid` for the next deal of a provably fair table
+    pub fn add_entropy(&mut self, pid: Uuid, entropy: Seed) -> Result<(), ProtocolError> {
+        if !self.provably_fair {
+            return Err(ProtocolError::new(
+                ProtocolErrorKind::BadState,
+                "the deals of this table are not provably fair",
+            )
+            .with_code(ErrorCode::NotProvablyFair));
+        }
+        let pos = self.seated_pos(pid)?;
+        if let Some(fair) = self.fair.as_mut() {
+            fair.contribute(pos.to_n(), entropy);
+        }
+        Ok(())
+    }
+
+    /// Keeps the hands for the next deal, they replace the current ones when its auction has not
+    /// started yet
+    fn give_hands(
+        &mut self,
+        hands: Vec<cards::Hand>,
+        dog: cards::Hand,
+    ) -> Result<(), ProtocolError> {
+        tarotgame::check_hands(&hands, dog, self.nb_players as usize).map_err(|e| {
+            ProtocolError::new(ProtocolErrorKind::InvalidCommand, format!("hands: {}", e))
+                .with_code(ErrorCode::InvalidHands)
+        })?;
+        self.next_hands = Some((hands, dog));
+        if self.turn == Turn::Pregame || self.turn == Turn::Interdeal {
+            self.new_deal();
+        }
+        Ok(())
     }
 
     fn position_taken(&self, position: pos::PlayerPos) -> bool {
@@ -435,10 +1109,121 @@ impl TarotGameState {
     }
 
     pub fn update_turn(&mut self) {
-        if self.turn == Turn::CallingKing || self.turn == Turn::MakingDog {
-            return ();
+        if self.turn != Turn::CallingKing && self.turn != Turn::MakingDog {
+            self.turn = Turn::from_deal(&self.deal);
+        }
+        self.restart_clock();
+    }
+
+    /// Seat of the player who must act
+    fn actor(&self) -> Option<pos::PlayerPos> {
+        match self.turn {
+            Turn::Bidding((_, pos)) | Turn::Playing(pos) => Some(pos),
+            Turn::CallingKing | Turn::MakingDog => {
+                self.deal.deal_contract().map(|contract| contract.author)
+            }
+            _ => None,
+        }
+    }
+
+    /// Fills the time banks of the players for a new deal
+    fn reset_clock(&mut self) {
+        if let Some(control) = self.time_control {
+            self.clock.reset(&control, self.nb_players);
+        }
+    }
+
+    /// Starts the time of the player who must act
+    fn restart_clock(&mut self) {
+        if let Some(control) = self.time_control {
+            let pos = self.actor();
+            self.clock.start(&control, pos, now_millis());
+        }
+    }
+
+    /// Time, in milliseconds since the unix epoch, when the player who must act runs out of
+    /// time
+    pub fn clock_deadline(&self) -> Option<u64> {
+        let control = self.time_control.as_ref()?;
+        self.clock
+            .deadline(control)
+            .map(|(_pos, deadline)| deadline)
+    }
+
+    /// Player who ran out of time at `now`
+    pub fn timed_out(&self, now: u64) -> Option<Uuid> {
+        let control = self.time_control.as_ref()?;
+        match self.clock.deadline(control) {
+            Some((pos, deadline)) if now >= deadline && self.actor() == Some(pos) => {
+                self.player_by_pos(pos).map(|state| state.player.id)
+            }
+            _ => None,
+        }
+    }
+
+    /// King called for a taker who ran out of time : a king he doesn't have, or a queen if he
+    /// has all the kings, or a jack if he also has all the queens
+    fn timeout_king(&self, pos: pos::PlayerPos) -> Option<cards::Card> {
+        let hand = self.deal.deal_state()?.hands()[pos.to_n()];
+        let rank = if !hand.has_all_rank(cards::Rank::RankK) {
+            cards::Rank::RankK
+        } else if !hand.has_all_rank(cards::Rank::RankQ) {
+            cards::Rank::RankQ
+        } else {
+            cards::Rank::RankJ
+        };
+        [
+            cards::Suit::Heart,
+            cards::Suit::Spade,
+            cards::Suit::Diamond,
+            cards::Suit::Club,
+        ]
+        .iter()
+        .map(|suit| cards::Card::new(*suit, rank))
+        .find(|card| !hand.has(*card))
+    }
+
+    /// Dog made for a taker who ran out of time : his lowest cards, trumps only when he has
+    /// nothing else to put
+    fn timeout_dog(&self, pos: pos::PlayerPos) -> Option<cards::Hand> {
+        let state = self.deal.deal_state()?;
+        let mut pool = state.hands()[pos.to_n()];
+        pool.merge(state.dog());
+        let mut candidates: Vec<cards::Card> = pool
+            .list()
+            .into_iter()
+            .filter(|card| !card.is_oudler() && card.rank() != cards::Rank::RankK)
+            .collect();
+        candidates.sort_by_key(|card| {
+            (
+                card.suit() == cards::Suit::Trump,
+                tarotgame::points::strength(*card),
+            )
+        });
+        let mut dog = cards::Hand::new();
+        for card in candidates
+            .into_iter()
+            .take(tarotgame::dog_size(self.nb_players as usize))
+        {
+            dog.add(card);
         }
-        self.turn = Turn::from_deal(&self.deal)
+        Some(dog)
+    }
+
+    /// Card played for a player who ran out of time : his lowest legal card, the excuse only
+    /// when he has nothing else to play
+    fn timeout_card(&self, pos: pos::PlayerPos) -> Option<cards::Card> {
+        let state = self.deal.deal_state()?;
+        state.hands()[pos.to_n()]
+            .list()
+            .into_iter()
+            .filter(|card| state.clone().play_card(pos, *card).is_ok())
+            .min_by_key(|card| {
+                (
+                    *card == cards::Card::excuse(),
+                    tarotgame::points::strength(*card),
+                )
+            })
     }
 
     pub fn set_bid(
@@ -447,7 +1232,7 @@ impl TarotGameState {
         target: bid::Target,
         slam: bool,
     ) -> Result<(), ProtocolError> {
-        let pos = self.players.get(&pid).map(|p| p.pos).unwrap(); // TODO -> Result<..>
+        let pos = self.seated_pos(pid)?;
         let auction = self.deal.deal_auction_mut().unwrap();
         if Ok(bid::AuctionState::Over) == auction.bid(pos, target, slam) {
             self.complete_auction()?;
@@ -456,31 +1241,40 @@ impl TarotGameState {
         Ok(())
     }
 
-    pub fn set_pass(&mut self, pid: Uuid) -> Result<(), ProtocolError> {
-        let pos = self
-            .players
-            .get(&pid)
-            .map(|p| p.pos)
-            .ok_or(ProtocolError::new(
-                ProtocolErrorKind::InternalError,
-                "unknown position",
-            ))?;
-        let auction = self.deal.deal_auction_mut().ok_or(ProtocolError::new(
-            ProtocolErrorKind::InternalError,
-            "unknown auction",
-        ))?;
-        let pass_result = auction.pass(pos);
-        match pass_result {
+    /// Returns true when everybody passed : the cards are dealt again
+    pub fn set_pass(&mut self, pid: Uuid) -> Result<bool, ProtocolError> {
+        let cancelled = self.pass(pid)?;
+        if cancelled {
+            self.next_deal();
+        }
+        self.update_turn();
+        Ok(cancelled)
+    }
+
+    /// Returns true when everybody passed : the auction is cancelled, and the cards must be
+    /// dealt again. Nobody has to act meanwhile.
+    fn pass(&mut self, pid: Uuid) -> Result<bool, ProtocolError> {
+        let pos = self.seated_pos(pid)?;
+        let auction = self.deal.deal_auction_mut().ok_or(
+            ProtocolError::new(ProtocolErrorKind::InternalError, "unknown auction")
+                .with_code(ErrorCode::UnknownAuction),
+        )?;
+        match auction.pass(pos) {
             Ok(bid::AuctionState::Over) => self.complete_auction()?,
-            Ok(bid::AuctionState::Cancelled) => self.next_deal(),
+            Ok(bid::AuctionState::Cancelled) => {
+                self.turn = Turn::Interdeal;
+                return Ok(true);
+            }
             _ => (),
         };
-        self.update_turn();
-        Ok(())
+        Ok(false)
     }
 
     pub fn call_king(&mut self, pid: Uuid, card: cards::Card) {
-        let pos = self.players.get(&pid).map(|p| p.pos).unwrap(); // TODO -> Result<..>
+        let pos = match self.seated_pos(pid) {
+            Ok(pos) => pos,
+            Err(_) => return,
+        };
         let deal_state = self.deal.deal_state_mut().unwrap();
         if deal_state.call_king(pos, card) {
             // Next step : do we need to make a dog ?
@@ -492,6 +1286,7 @@ impl TarotGameState {
                 //Dog
                 self.turn = Turn::MakingDog;
             }
+            self.restart_clock();
         }
     }
 
@@ -501,21 +1296,22 @@ impl TarotGameState {
         cards: cards::Hand,
         slam: bool,
     ) -> Result<(), ProtocolError> {
-        let pos = self.players.get(&pid).map(|p| p.pos).unwrap();
+        let pos = self.seated_pos(pid)?;
         self.deal
             .deal_state_mut()
             .unwrap()
             .make_dog(pos, cards, slam)?;
         self.turn = Turn::from_deal(&self.deal);
+        self.restart_clock();
         Ok(())
     }
 
     pub fn set_announce(&mut self, pid: Uuid, announce: Announce) -> Result<(), ProtocolError> {
-        let pos = self.players.get(&pid).map(|p| p.pos).unwrap();
-        let state = self.deal.deal_state_mut().ok_or(ProtocolError::new(
-            ProtocolErrorKind::InternalError,
-            "Unknown deal state",
-        ))?;
+        let pos = self.seated_pos(pid)?;
+        let state = self.deal.deal_state_mut().ok_or(
+            ProtocolError::new(ProtocolErrorKind::InternalError, "Unknown deal state")
+                .with_code(ErrorCode::UnknownDealState),
+        )?;
         state.announce(pos, announce)?;
         Ok(())
     }
@@ -559,10 +1355,102 @@ impl TarotGameState {
         }
     }
 
+    /// The finished deal, with the hands as they were dealt
+    fn deal_summary(&self, result: deal::DealResult) -> Option<DealSummary> {
+        let initial = self.deal_ini.as_ref()?;
+        let state = self.deal.deal_state()?;
+        Some(DealSummary {
+            result,
+            contract: state.contract().clone(),
+            king: state.king(),
+            partner: state.partner(),
+            hands: initial.hands().clone(),
+            dog: initial.dog(),
+            discard: state.dog(),
+            fair: self.proof.clone(),
+        })
+    }
+
+    /// Nicknames of the seated players, by position
+    fn nicknames(&self) -> Vec<String> {
+        let mut nicknames = vec![String::new(); self.nb_players as usize];
+        for player in self.players.values() {
+            if player.role == PlayerRole::Spectator {
+                continue;
+            }
+            if let Some(nickname) = nicknames.get_mut(player.pos.to_n()) {
+                *nickname = player.player.nickname.clone();
+            }
+        }
+        nicknames
+    }
+
+    /// The deal being played, to replay it card by card
+    pub fn deal_replay(&self) -> Option<DealReplay> {
+        let initial = self.deal_ini.as_ref()?;
+        let state = self.deal.deal_state()?;
+        Some(DealReplay::new(
+            self.nicknames(),
+            initial.hands().clone(),
+            initial.dog(),
+            state.dog(),
+            state.contract().clone(),
+            state.king(),
+            state.tricks(),
+        ))
+    }
+
+    /// Archive of the deal which just ended with `summary`, to be called before the next deal
+    /// is dealt, once the events of the deal are logged
+    pub fn deal_archive(&self, game_id: Uuid, summary: DealSummary) -> Option<DealArchive> {
+        let state = self.deal.deal_state()?;
+        Some(DealArchive {
+            game_id,
+            deal: self.scores.len(),
+            finished_at: now_millis() / 1000,
+            seed: self.seed,
+            players: self.nicknames(),
+            summary,
+            tricks: state.completed_tricks().to_vec(),
+            events: self.events.last_deal(),
+            scores: self.scores.clone(),
+        })
+    }
+
     pub fn next_deal(&mut self) {
         self.first = self.first.next();
-        let auction = bid::Auction::new(self.first);
-        self.deal = Deal::Bidding(auction);
+        self.new_deal();
+        self.reset_clock();
+    }
+
+    /// Deals the cards given for this deal, or from a random seed kept so that the deal can be
+    /// replayed. On provably fair tables the seed is the one committed to, combined with the
+    /// entropy of the players, and the secret of the following deal is drawn.
+    fn new_deal(&mut self) {
+        self.proof = None;
+        if let Some((hands, dog)) = self.next_hands.take() {
+            self.deal = Deal::Bidding(bid::Auction::new_dealt(self.first, hands, dog));
+            self.seed = None;
+            return;
+        }
+        let count = self.nb_players as usize;
+        let seed = match self.fair.take() {
+            Some(fair) if self.provably_fair => {
+                self.proof = Some(fair.proof());
+                fair.seed()
+            }
+            _ => tarotgame::random_deal_seed(count),
+        };
+        self.deal = Deal::Bidding(bid::Auction::new_seeded(self.first, seed));
+        self.seed = Some(seed);
+        if self.provably_fair {
+            self.fair = Some(FairDeal::new(count));
+        }
+    }
+
+    /// Seed the cards of the current deal were shuffled with
+    pub fn seed(&self) -> Option<[u8; 32]> {
+        self.seed
     }
 
     pub fn get_deal(&self) -> &Deal {
@@ -574,29 +1462,194 @@ impl TarotGameState {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum PlayEvent {
     Play(Uuid, cards::Card),
     Announce(Uuid, Announce),
+    Bid(Uuid, bid::Target),
+    Pass(Uuid),
+    KingCalled(Uuid, cards::Card),
+    /// Cards of the dog which can be shown to the other players
+    DogMade(Uuid, cards::Hand),
+    SlamDeclared(Uuid),
     EndTrick,
     EndDeal(deal::DealResult),
+    /// Sent after `EndDeal`, with the hands and the dog as they were dealt
+    DealSummary(DealSummary),
+    /// Everybody passed and the cards are dealt again, with what the cancelled deal was
+    /// shuffled with on provably fair tables
+    DealCancelled(Option<FairProof>),
+    /// The player ran out of time, the server acts for him
+    TimedOut(Uuid),
+}
+
+/// Seconds an absent player has to come back, before a single vote replaces him by a bot
+pub const DEFAULT_REPLACE_ABSENT_AFTER: u32 = 60;
+
+fn default_replace_absent_after() -> u32 {
+    DEFAULT_REPLACE_ABSENT_AFTER
+}
+
+pub const DEFAULT_BOT_DELAY: u32 = 6000;
+pub const MAX_BOT_DELAY: u32 = 30_000;
+
+fn default_bot_delay() -> u32 {
+    DEFAULT_BOT_DELAY
+}
+
+/// Seconds since the unix epoch
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct VariantSettings {
     pub nb_players: u8,
+    /// Seconds an absent player has to come back, after which a single vote replaces him. With
+    /// 0 he can be replaced at once, by a majority vote.
+    #[serde(default = "default_replace_absent_after")]
+    pub replace_absent_after: u32,
+    /// Milliseconds the bots wait before playing
+    #[serde(default = "default_bot_delay")]
+    pub bot_delay: u32,
+    /// What the spectators see of the hands
+    #[serde(default)]
+    pub spectators: SpectatorVisibility,
+    /// The players can look at the tricks already played in the deal
+    #[serde(default)]
+    pub review_tricks: bool,
+    /// Time limits of the players, none by default
+    #[serde(default)]
+    pub time_control: Option<TimeControl>,
+    /// The host can give the hands of the next deal, for teaching
+    #[serde(default)]
+    pub training: bool,
+    /// The players give entropy to the shuffle and can check the deals afterwards
+    #[serde(default)]
+    pub provably_fair: bool,
 }
 
+impl VariantSettings {
+    pub const fn new(nb_players: u8) -> Self {
+        VariantSettings {
+            nb_players,
+            replace_absent_after: DEFAULT_REPLACE_ABSENT_AFTER,
+            bot_delay: DEFAULT_BOT_DELAY,
+            spectators: SpectatorVisibility::Hidden,
+            review_tricks: false,
+            time_control: None,
+            training: false,
+            provably_fair: false,
+        }
+    }
+}
+
+/// What the spectators of a game see
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum SpectatorVisibility {
+    /// Only what the players see of each other
+    #[default]
+    Hidden,
+    /// All the hands, as they were the given number of tricks ago
+    Delayed(u8),
+    /// All the hands and the dog, for teaching
+    All,
+}
+
+/// Commitment of the server to the seed of the next deal, on provably fair tables
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FairSnapshot {
+    pub commitment: Seed,
+    /// Whether the player at each position gave his entropy
+    pub contributed: Vec<bool>,
+}
+
+/// Default delay, in tricks, before the spectators see the hands
+pub const DEFAULT_SPECTATOR_DELAY: u8 = 2;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GameStateSnapshot {
     pub nb_players: u8,
     pub players: Vec<GamePlayerState>,
     pub turn: Turn,
     pub deal: DealSnapshot,
     pub scores: Vec<Vec<f32>>,
+    #[serde(default)]
+    pub replace_absent_after: u32,
+    /// Milliseconds the bots wait before playing, not sent by older servers
+    #[serde(default)]
+    pub bot_delay: Option<u32>,
+    /// The host can give the hands of the next deal
+    #[serde(default)]
+    pub training: bool,
+    /// On provably fair tables, the commitment for the next deal
+    #[serde(default)]
+    pub fair: Option<FairSnapshot>,
+    /// Seats of the players who left
+    #[serde(default)]
+    pub absent: Vec<AbsentPlayer>,
+    /// Players watching the game
+    #[serde(default)]
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<crate::schema::PlayerInfo>"))]
+    pub spectators: Vec<PlayerInfo>,
+    /// Time left to the player who must act, when the game has time controls
+    #[serde(default)]
+    pub clock: Option<ClockSnapshot>,
+    /// Number of the next event
+    #[serde(default)]
+    pub next_event: u64,
+}
+
+impl webgame_protocol::GameStateSnapshot for GameStateSnapshot {}
+
+/// View of the game for a spectator, who has no seat at the table
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SpectatorSnapshot {
+    pub nb_players: u8,
+    pub players: Vec<GamePlayerState>,
+    pub turn: Turn,
+    /// Current deal, without any hand
+    pub deal: DealSnapshot,
+    pub scores: Vec<Vec<f32>>,
+    /// Hands of the players by position, empty when the visibility hides them
+    pub hands: Vec<cards::Hand>,
+    pub visibility: SpectatorVisibility,
+    pub absent: Vec<AbsentPlayer>,
+    #[cfg_attr(feature = "schema", schemars(with = "Vec<crate::schema::PlayerInfo>"))]
+    pub spectators: Vec<PlayerInfo>,
+    #[serde(default)]
+    pub clock: Option<ClockSnapshot>,
+    pub next_event: u64,
+}
+
+impl SpectatorSnapshot {
+    /// The table as shown to the players, to share their rendering
+    pub fn table(&self) -> GameStateSnapshot {
+        GameStateSnapshot {
+            nb_players: self.nb_players,
+            players: self.players.clone(),
+            turn: self.turn,
+            deal: self.deal.clone(),
+            scores: self.scores.clone(),
+            replace_absent_after: 0,
+            bot_delay: None,
+            training: false,
+            fair: None,
+            absent: self.absent.clone(),
+            spectators: self.spectators.clone(),
+            clock: self.clock.clone(),
+            next_event: self.next_event,
+        }
+    }
 }
 
-impl webgame_protocol::GameStateSnapshot for GameStateSnapshot {}
-
 impl GameStateSnapshot {
     // pub fn get_current_player(self) -> Option<PlayerInfo> {
     //     let player_info;
@@ -615,6 +1668,12 @@ impl GameStateSnapshot {
         }
     }
 
+    pub fn absent_at(&self, pos: pos::PlayerPos) -> Option<&AbsentPlayer> {
+        self.absent
+            .iter()
+            .find(|absent| absent.pos == pos && absent.substitute.is_none())
+    }
+
     pub fn pos_player_name(&self, pos: pos::PlayerPos) -> String {
         self.players
             .iter()
@@ -651,6 +1710,14 @@ impl Default for GameStateSnapshot {
             players: vec![],
             scores: vec![],
             turn: Turn::Pregame,
+            replace_absent_after: 0,
+            bot_delay: None,
+            training: false,
+            fair: None,
+            absent: vec![],
+            spectators: vec![],
+            clock: None,
+            next_event: 0,
             deal: DealSnapshot {
                 hand: cards::Hand::new(),
                 current: pos,
@@ -662,6 +1729,7 @@ impl Default for GameStateSnapshot {
                 initial_dog: cards::Hand::new(),
                 dog: cards::Hand::new(),
                 taker_diff: 0.0,
+                tricks: vec![],
                 announces: vec![],
             },
         }
@@ -671,6 +1739,7 @@ impl Default for GameStateSnapshot {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::SnapshotTracker;
     use tarotgame::{bid, cards, deal_hands, deal_seeded_hands};
     // use tarotgame::{bid, cards, pos, deal, trick};
 
@@ -721,7 +1790,7 @@ mod tests {
         game.set_player_ready(id2);
         game.set_player_ready(id3);
         game.set_player_ready(id4);
-        assert_eq!(false, game.is_joinable());
+        assert_eq!(false, game.has_free_seat());
         assert_eq!(
             game.get_turn(),
             Turn::Bidding((bid::AuctionState::Bidding, pos0))
@@ -729,11 +1798,11 @@ mod tests {
 
         let hands_deal1 = game.deal.hands();
 
-        game.set_pass(id0).unwrap();
-        game.set_pass(id1).unwrap();
-        game.set_pass(id2).unwrap();
-        game.set_pass(id3).unwrap();
-        game.set_pass(id4).unwrap();
+        assert!(!game.set_pass(id0).unwrap());
+        assert!(!game.set_pass(id1).unwrap());
+        assert!(!game.set_pass(id2).unwrap());
+        assert!(!game.set_pass(id3).unwrap());
+        assert!(game.set_pass(id4).unwrap());
 
         //5 passes : should start a new deal
         assert_eq!(
@@ -779,7 +1848,7 @@ mod tests {
         game.set_player_ready(id2);
         game.set_player_ready(id3);
         game.set_player_ready(id4);
-        assert_eq!(false, game.is_joinable());
+        assert_eq!(false, game.has_free_seat());
         assert_eq!(
             game.get_turn(),
             Turn::Bidding((bid::AuctionState::Bidding, pos0))
@@ -832,7 +1901,7 @@ mod tests {
         game.set_player_ready(id2);
         game.set_player_ready(id3);
         game.set_player_ready(id4);
-        assert_eq!(false, game.is_joinable());
+        assert_eq!(false, game.has_free_seat());
         assert_eq!(
             game.get_turn(),
             Turn::Bidding((bid::AuctionState::Bidding, pos0))
@@ -1507,6 +2576,23 @@ mod tests {
             deal_history: vec![],
             first: pos::PlayerPos::from_n(0, variant as u8),
             scores: vec![],
+            replace_absent_after: DEFAULT_REPLACE_ABSENT_AFTER,
+            bot_delay: DEFAULT_BOT_DELAY,
+            absent: vec![],
+            spectators: BTreeMap::new(),
+            visibility: SpectatorVisibility::default(),
+            review_tricks: false,
+            time_control: None,
+            clock: Clock::default(),
+            seed: None,
+            training: false,
+            next_hands: None,
+            provably_fair: false,
+            fair: None,
+            proof: None,
+            seat_tokens: BTreeMap::new(),
+            sent: SentSnapshots::default(),
+            events: EventLog::default(),
         };
 
         for v in 0..variant {
@@ -1517,7 +2603,7 @@ mod tests {
             game.set_player_ready(game.player_by_pos(pos).unwrap().player.id);
         }
 
-        assert_eq!(false, game.is_joinable());
+        assert_eq!(false, game.has_free_seat());
 
         let seed = [
             7, 32, 3, 32, 54, 1, 84, 3, 32, 54, 1, 84, 3, 32, 65, 1, 84, 3, 32, 64, 1, 44, 3, 32,
@@ -1615,6 +2701,23 @@ mod tests {
             deal_history: vec![],
             first: pos::PlayerPos::from_n(0, variant as u8),
             scores: vec![],
+            replace_absent_after: DEFAULT_REPLACE_ABSENT_AFTER,
+            bot_delay: DEFAULT_BOT_DELAY,
+            absent: vec![],
+            spectators: BTreeMap::new(),
+            visibility: SpectatorVisibility::default(),
+            review_tricks: false,
+            time_control: None,
+            clock: Clock::default(),
+            seed: None,
+            training: false,
+            next_hands: None,
+            provably_fair: false,
+            fair: None,
+            proof: None,
+            seat_tokens: BTreeMap::new(),
+            sent: SentSnapshots::default(),
+            events: EventLog::default(),
         };
 
         for v in 0..variant {
@@ -1684,6 +2787,1009 @@ mod tests {
         // println!("scores: {:?}", game.scores);
     }
 
+    #[derive(PartialEq)]
+    struct EventsCollector {
+        events: Vec<PlayEvent>,
+    }
+
+    impl GameEventsListener<(PlayEvent, TarotGameState)> for EventsCollector {
+        fn notify(&mut self, event: &(PlayEvent, TarotGameState)) {
+            self.events.push(event.0.clone());
+        }
+    }
+
+    #[test]
+    fn test_auction_events() {
+        let mut game = TarotGameState::default();
+        let ids: Vec<Uuid> = (0..5)
+            .map(|n| {
+                let id = Uuid::new_v4();
+                game.add_player(PlayerInfo {
+                    id,
+                    nickname: format!("player{}", n),
+                });
+                id
+            })
+            .collect();
+        for id in &ids {
+            game.set_player_ready(*id);
+        }
+        let seed = [
+            3, 32, 3, 32, 54, 1, 84, 3, 32, 54, 1, 84, 3, 32, 65, 1, 84, 3, 32, 64, 1, 44, 3, 32,
+            54, 1, 84, 3, 32, 65, 1, 44,
+        ];
+        let (hands, dog) = deal_seeded_hands(seed, 5);
+        game.deal.deal_auction_mut().unwrap().set_hands(hands, dog);
+
+        let king = cards::Card::new(cards::Suit::Club, cards::Rank::RankK);
+        let mut dog = cards::Hand::new();
+        dog.add(cards::Card::new(cards::Suit::Heart, cards::Rank::RankC));
+        dog.add(cards::Card::new(cards::Suit::Spade, cards::Rank::Rank2));
+        dog.add(cards::Card::new(cards::Suit::Spade, cards::Rank::RankQ));
+
+        let mut collector = EventsCollector { events: vec![] };
+        let mut game_manager = TarotGameManager::new(&mut game);
+        game_manager.register_listener(&mut collector);
+        game_manager
+            .set_bid(ids[0], bid::Target::Garde, true)
+            .unwrap();
+        for id in &ids[1..] {
+            game_manager.set_pass(*id).unwrap();
+        }
+        assert!(game_manager.call_king(ids[1], king).is_err());
+        game_manager.call_king(ids[0], king).unwrap();
+        assert!(game_manager.call_king(ids[0], king).is_err());
+        game_manager.make_dog(ids[0], dog, false).unwrap();
+        drop(game_manager);
+
+        assert_eq!(
+            collector.events,
+            vec![
+                PlayEvent::Bid(ids[0], bid::Target::Garde),
+                PlayEvent::SlamDeclared(ids[0]),
+                PlayEvent::Pass(ids[1]),
+                PlayEvent::Pass(ids[2]),
+                PlayEvent::Pass(ids[3]),
+                PlayEvent::Pass(ids[4]),
+                PlayEvent::KingCalled(ids[0], king),
+                PlayEvent::DogMade(ids[0], cards::Hand::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_absent_player() {
+        let mut game = TarotGameState::default();
+        let ids: Vec<Uuid> = (0..5)
+            .map(|n| {
+                let id = Uuid::new_v4();
+                game.add_player(PlayerInfo {
+                    id,
+                    nickname: format!("player{}", n),
+                });
+                id
+            })
+            .collect();
+        for id in &ids {
+            game.set_player_ready(*id);
+        }
+        assert_eq!(false, game.has_free_seat());
+
+        // The player keeps his seat while he is away
+        let pos2 = game.players.get(&ids[2]).unwrap().pos;
+        assert_eq!(true, game.remove_player(ids[2]));
+        assert_eq!(5, game.players.len());
+        assert_eq!(true, game.has_free_seat());
+        assert!(game.player_snapshot(ids[0]).absent_at(pos2).is_some());
+
+        // A newcomer can't take the seat without the agreement of the table
+        let watcher = PlayerInfo {
+            id: Uuid::new_v4(),
+            nickname: String::from("watcher"),
+        };
+        assert_eq!(true, game.is_joinable());
+        game.add_player(watcher.clone());
+        assert_eq!(false, game.players.contains_key(&watcher.id));
+        assert!(game.is_spectator(watcher.id));
+        assert!(game.player_snapshot(watcher.id).deal.hand.is_empty());
+
+        // Before the grace period is over, even most of the players can't replace him
+        assert!(game.replace_absent(ids[2], pos2).is_err());
+        for id in ids.iter().filter(|id| **id != ids[2]) {
+            assert_eq!(None, game.replace_absent(*id, pos2).unwrap());
+        }
+        assert_eq!(None, game.absent[0].invited_by);
+
+        // Without grace period, most of the players must vote
+        game.replace_absent_after = 0;
+        game.absent[0].votes.clear();
+        game.replace_absent(ids[0], pos2).unwrap();
+        game.replace_absent(ids[1], pos2).unwrap();
+        assert_eq!(None, game.absent[0].invited_by);
+        let token = game.replace_absent(ids[3], pos2).unwrap().unwrap();
+        assert_eq!(Some(ids[3]), game.absent[0].invited_by);
+        // the token is only given once
+        assert_eq!(None, game.replace_absent(ids[4], pos2).unwrap());
+
+        // A newcomer can't take the seat offered to the bot
+        let intruder = PlayerInfo {
+            id: Uuid::new_v4(),
+            nickname: String::from("intruder"),
+        };
+        game.add_player(intruder.clone());
+        assert!(game.is_spectator(intruder.id));
+        assert!(game.player_snapshot(intruder.id).deal.hand.is_empty());
+        assert_eq!(
+            Some(ErrorCode::SeatNotOffered),
+            game.take_seat(intruder.id, Uuid::new_v4())
+                .unwrap_err()
+                .code()
+        );
+
+        // The bot takes the seat and the hand of the player with the token
+        let hand = game.deal.hands()[pos2.to_n()];
+        let bot = PlayerInfo {
+            id: Uuid::new_v4(),
+            nickname: String::from("bot"),
+        };
+        game.add_player(bot.clone());
+        assert!(game.player_snapshot(bot.id).deal.hand.is_empty());
+        game.take_seat(bot.id, token).unwrap();
+        assert_eq!(pos2, game.players.get(&bot.id).unwrap().pos);
+        assert_eq!(hand, game.player_snapshot(bot.id).deal.hand);
+        assert!(game.player_snapshot(ids[0]).absent_at(pos2).is_none());
+        assert!(game.take_seat(intruder.id, token).is_err());
+        assert!(game.player_snapshot(intruder.id).deal.hand.is_empty());
+
+        // The player comes back and the bot leaves
+        let player2 = PlayerInfo {
+            id: ids[2],
+            nickname: String::from("player2"),
+        };
+        assert_eq!(pos2, game.add_player(player2));
+        assert_eq!(false, game.players.contains_key(&bot.id));
+        assert!(game.absent.is_empty());
+        assert_eq!(false, game.has_free_seat());
+        assert!(game.player_snapshot(bot.id).deal.hand.is_empty());
+    }
+
+    #[test]
+    fn test_bot_delay() {
+        let mut game = TarotGameState::default();
+        let host = Uuid::new_v4();
+        let guest = Uuid::new_v4();
+        for (id, nickname) in &[(host, "host"), (guest, "guest")] {
+            game.add_player(PlayerInfo {
+                id: *id,
+                nickname: nickname.to_string(),
+            });
+        }
+        assert_eq!(
+            Some(DEFAULT_BOT_DELAY),
+            game.player_snapshot(guest).bot_delay
+        );
+
+        assert!(game.set_bot_delay(guest, 1000).is_err());
+        game.set_bot_delay(host, 1000).unwrap();
+        assert_eq!(Some(1000), game.player_snapshot(guest).bot_delay);
+        game.set_bot_delay(host, 3_600_000).unwrap();
+        assert_eq!(Some(MAX_BOT_DELAY), game.player_snapshot(guest).bot_delay);
+    }
+
+    #[test]
+    fn test_set_hands() {
+        let mut game = TarotGameState::default();
+        let host = Uuid::new_v4();
+        let guest = Uuid::new_v4();
+        for (id, nickname) in &[(host, "host"), (guest, "guest")] {
+            game.add_player(PlayerInfo {
+                id: *id,
+                nickname: nickname.to_string(),
+            });
+        }
+        let (hands, dog) = deal_seeded_hands([4; 32], 5);
+        assert_eq!(
+            Some(ErrorCode::NotTraining),
+            game.set_hands(host, hands.clone(), dog).unwrap_err().code()
+        );
+        game.training = true;
+        assert!(game.player_snapshot(guest).training);
+        assert_eq!(
+            Some(ErrorCode::NotHost),
+            game.set_hands(guest, hands.clone(), dog)
+                .unwrap_err()
+                .code()
+        );
+        assert_eq!(
+            Some(ErrorCode::InvalidHands),
+            game.set_hands(host, hands[..4].to_vec(), dog)
+                .unwrap_err()
+                .code()
+        );
+        let outcome = game
+            .manage_operation(DebugOperation::SetHands(hands.clone(), cards::Hand::new()))
+            .unwrap();
+        match serde_json::from_str(&outcome).unwrap() {
+            OperationOutcome::Failed { error } => {
+                assert_eq!(Some(ErrorCode::InvalidHands), error.code())
+            }
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+
+        // before the game starts, the hands are dealt at once
+        game.set_hands(host, hands.clone(), dog).unwrap();
+        assert_eq!(&hands, game.deal.hands());
+        assert_eq!(None, game.seed());
+
+        // during a deal, they are kept for the next one
+        game.turn = Turn::Playing(pos::PlayerPos::from_n(0, 5));
+        let (next_hands, next_dog) = deal_seeded_hands([5; 32], 5);
+        assert_eq!(
+            Some(String::from(r#"{"outcome":"done"}"#)),
+            game.manage_operation(DebugOperation::SetHands(next_hands.clone(), next_dog))
+        );
+        assert_eq!(&hands, game.deal.hands());
+        game.next_deal();
+        assert_eq!(&next_hands, game.deal.hands());
+        assert_eq!(None, game.seed());
+        game.next_deal();
+        assert!(game.seed().is_some());
+    }
+
+    #[test]
+    fn test_debug_outcomes() {
+        let mut game = TarotGameState::default();
+        let state: serde_json::Value =
+            serde_json::from_str(&game.manage_operation(DebugOperation::ShowState).unwrap())
+                .unwrap();
+        assert_eq!("state", state["outcome"]);
+        assert_eq!(game.nb_players, state["state"]["nb_players"]);
+
+        // the secrets of the deals are left out
+        let mut parameters = VariantSettings::new(4);
+        parameters.provably_fair = true;
+        game.set_variant(TarotVariant { parameters });
+        for n in 0..4 {
+            let id = Uuid::new_v4();
+            game.add_player(PlayerInfo {
+                id,
+                nickname: format!("player{}", n),
+            });
+            game.set_player_ready(id);
+        }
+        assert!(game.fair.is_some() && game.proof.is_some());
+        let state: serde_json::Value =
+            serde_json::from_str(&game.debug_bychat("debugbot").unwrap()).unwrap();
+        assert!(state["state"].get("fair").is_none());
+        assert!(state["state"].get("proof").is_none());
+
+        // the host can't replace the deal the players were committed to
+        let hands = game.deal.hands().clone();
+        let proof = game.proof.clone();
+        let refused: serde_json::Value = serde_json::from_str(
+            &game
+                .manage_operation(DebugOperation::SetSeed([9; 32]))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!("failed", refused["outcome"]);
+        assert_eq!(&hands, game.deal.hands());
+        assert_eq!(proof, game.proof);
+
+        let unknown: serde_json::Value =
+            serde_json::from_str(&game.debug_bychat("unknown").unwrap()).unwrap();
+        assert_eq!("failed", unknown["outcome"]);
+        assert_eq!("unknown command", unknown["error"]["message"]);
+    }
+
+    #[test]
+    fn test_provably_fair() {
+        let mut game = TarotGameState::default();
+        let mut parameters = VariantSettings::new(4);
+        game.set_variant(TarotVariant {
+            parameters: parameters.clone(),
+        });
+        let ids: Vec<Uuid> = (0..4)
+            .map(|n| {
+                let id = Uuid::new_v4();
+                game.add_player(PlayerInfo {
+                    id,
+                    nickname: format!("player{}", n),
+                });
+                id
+            })
+            .collect();
+        assert_eq!(None, game.player_snapshot(ids[0]).fair);
+        assert_eq!(
+            Some(ErrorCode::NotProvablyFair),
+            game.add_entropy(ids[0], [1; 32]).unwrap_err().code()
+        );
+
+        parameters.provably_fair = true;
+        game.set_variant(TarotVariant { parameters });
+        let commitment = game.player_snapshot(ids[0]).fair.unwrap().commitment;
+        game.add_entropy(ids[1], [2; 32]).unwrap();
+        game.add_entropy(ids[3], [4; 32]).unwrap();
+        assert_eq!(
+            vec![false, true, false, true],
+            game.player_snapshot(ids[0]).fair.unwrap().contributed
+        );
+        assert_eq!(
+            Some(ErrorCode::NotSeated),
+            game.add_entropy(Uuid::new_v4(), [5; 32])
+                .unwrap_err()
+                .code()
+        );
+
+        // the cards are dealt once everybody is ready, and the next deal is committed to
+        for id in &ids {
+            game.set_player_ready(*id);
+        }
+        let next = game.player_snapshot(ids[0]).fair.unwrap();
+        assert_ne!(commitment, next.commitment);
+        assert_eq!(vec![false; 4], next.contributed);
+
+        let proof = game.proof.clone().unwrap();
+        assert_eq!(Some([2; 32]), proof.entropies[1]);
+        assert_eq!(
+            Ok(()),
+            proof.verify(
+                &commitment,
+                Some((3, [4; 32])),
+                game.deal.hands(),
+                game.deal.dog()
+            )
+        );
+
+        // the next deal is dealt from the new commitment
+        game.add_entropy(ids[0], [6; 32]).unwrap();
+        game.next_deal();
+        game.update_turn();
+        let proof = game.proof.clone().unwrap();
+        let (hands, dog) = (game.deal.hands().clone(), game.deal.dog());
+        assert_eq!(
+            Ok(()),
+            proof.verify(&next.commitment, Some((0, [6; 32])), &hands, dog)
+        );
+
+        // when everybody passes, the proof of the cancelled deal is revealed
+        let mut collector = EventsCollector { events: vec![] };
+        let mut game_manager = TarotGameManager::new(&mut game);
+        game_manager.register_listener(&mut collector);
+        for _ in 0..4 {
+            let pos = match game_manager.get_game().get_turn() {
+                Turn::Bidding((_, pos)) => pos,
+                turn => panic!("unexpected turn {:?}", turn),
+            };
+            let pid = game_manager
+                .get_game()
+                .player_by_pos(pos)
+                .unwrap()
+                .player
+                .id;
+            game_manager.set_pass(pid).unwrap();
+        }
+        match collector.events.last() {
+            Some(PlayEvent::DealCancelled(Some(revealed))) => {
+                assert_eq!(&proof, revealed);
+                assert_eq!(
+                    Ok((hands, dog)),
+                    revealed.dealt(&next.commitment, Some((0, [6; 32])))
+                );
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert_ne!(Some(proof), game.proof);
+    }
+
+    /// Plays the first valid card of the next players
+    fn play_cards(game: &mut TarotGameState, count: usize) {
+        let mut listener = TarotEventsListener {};
+        let mut game_manager = TarotGameManager::new(game);
+        game_manager.register_listener(&mut listener);
+        for _ in 0..count {
+            let game = game_manager.get_game();
+            let pos = game.deal.next_player();
+            let player_id = game.player_by_pos(pos).unwrap().player.id;
+            let hand = game.deal.hands()[pos.to_n()];
+            for card in hand.list() {
+                if game_manager.set_play(player_id, card).is_ok() {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_spectators() {
+        let mut game = TarotGameState::default();
+        let ids: Vec<Uuid> = (0..5)
+            .map(|n| {
+                let id = Uuid::new_v4();
+                game.add_player(PlayerInfo {
+                    id,
+                    nickname: format!("player{}", n),
+                });
+                id
+            })
+            .collect();
+        // the table is full, the newcomer watches
+        let watcher = PlayerInfo {
+            id: Uuid::new_v4(),
+            nickname: String::from("watcher"),
+        };
+        game.add_player(watcher.clone());
+        assert!(game.is_spectator(watcher.id));
+        assert_eq!(5, game.players.len());
+        for id in &ids {
+            game.set_player_ready(*id);
+        }
+        let (hands, dog) = deal_seeded_hands([3; 32], 5);
+        game.deal.deal_auction_mut().unwrap().set_hands(hands, dog);
+
+        let mut tracker = SnapshotTracker::new();
+        let view = game.view(watcher.id);
+        match game.next_update(watcher.id, view) {
+            StateUpdate::Spectator { seq, snapshot } => {
+                assert!(snapshot.hands.is_empty());
+                assert!(snapshot.deal.hand.is_empty());
+                assert_eq!(vec![watcher.clone()], snapshot.spectators);
+                assert_eq!(
+                    Some(GameView::Spectator(snapshot.clone())),
+                    tracker.apply_view(StateUpdate::Spectator { seq, snapshot })
+                );
+            }
+            update => panic!("unexpected update {:?}", update),
+        }
+        assert_eq!(
+            vec![watcher.clone()],
+            game.player_snapshot(ids[0]).spectators
+        );
+
+        // teaching mode
+        game.visibility = SpectatorVisibility::All;
+        assert_eq!(*game.deal.hands(), game.spectator_snapshot().hands);
+        assert_eq!(dog, game.spectator_snapshot().deal.dog);
+
+        // the hands are shown once the given number of tricks have been played
+        game.visibility = SpectatorVisibility::Delayed(1);
+        game.set_bid(ids[0], bid::Target::GardeContre, false)
+            .unwrap();
+        let hands = game.deal.hands().clone();
+        assert!(game.spectator_snapshot().hands.is_empty());
+        play_cards(&mut game, 3);
+        assert!(game.spectator_snapshot().hands.is_empty());
+        play_cards(&mut game, 3);
+        assert_eq!(hands, game.spectator_snapshot().hands);
+        assert_eq!(cards::Hand::new(), game.spectator_snapshot().deal.dog);
+        let view = game.view(watcher.id);
+        let update = game.next_update(watcher.id, view);
+        assert!(matches!(update, StateUpdate::Patch { .. }));
+        assert_eq!(
+            Some(GameView::Spectator(game.spectator_snapshot())),
+            tracker.apply_view(update)
+        );
+
+        // the game server sends a whole snapshot when somebody joins, the next patch is based on it
+        let newcomer = PlayerInfo {
+            id: Uuid::new_v4(),
+            nickname: String::from("newcomer"),
+        };
+        game.add_player(newcomer.clone());
+        assert!(tracker.apply_view(game.make_snapshot(watcher.id)).is_some());
+        play_cards(&mut game, 1);
+        let view = game.view(watcher.id);
+        let update = game.next_update(watcher.id, view);
+        assert!(matches!(update, StateUpdate::Patch { .. }));
+        assert_eq!(
+            Some(GameView::Spectator(game.spectator_snapshot())),
+            tracker.apply_view(update)
+        );
+        assert!(game.remove_player(newcomer.id));
+
+        assert!(game.remove_player(watcher.id));
+        assert_eq!(false, game.is_spectator(watcher.id));
+        assert_eq!(5, game.players.len());
+        assert!(game.absent.is_empty());
+    }
+
+    #[test]
+    fn test_review_tricks() {
+        let mut game = TarotGameState::default();
+        game.review_tricks = true;
+        let ids: Vec<Uuid> = (0..5)
+            .map(|n| {
+                let id = Uuid::new_v4();
+                game.add_player(PlayerInfo {
+                    id,
+                    nickname: format!("player{}", n),
+                });
+                game.set_player_ready(id);
+                id
+            })
+            .collect();
+        let (hands, dog) = deal_seeded_hands([5; 32], 5);
+        game.deal.deal_auction_mut().unwrap().set_hands(hands, dog);
+        game.set_bid(ids[0], bid::Target::GardeContre, false)
+            .unwrap();
+        assert!(game.player_snapshot(ids[1]).deal.tricks.is_empty());
+
+        play_cards(&mut game, 7);
+        let tricks = game.player_snapshot(ids[1]).deal.tricks;
+        assert_eq!(1, tricks.len());
+        assert!(tricks[0].cards.iter().take(5).all(|card| card.is_some()));
+        // the winner leads the next trick
+        let current = game.deal.deal_state().unwrap().current_trick();
+        assert_eq!(tricks[0].winner, current.first);
+        assert_eq!(tricks, game.spectator_snapshot().deal.tricks);
+
+        // the rule is off by default
+        game.review_tricks = false;
+        assert!(game.player_snapshot(ids[1]).deal.tricks.is_empty());
+    }
+
+    #[test]
+    fn test_deal_summary() {
+        let mut game = TarotGameState::default();
+        let ids: Vec<Uuid> = (0..5)
+            .map(|n| {
+                let id = Uuid::new_v4();
+                game.add_player(PlayerInfo {
+                    id,
+                    nickname: format!("player{}", n),
+                });
+                game.set_player_ready(id);
+                id
+            })
+            .collect();
+        let (hands, dog) = deal_seeded_hands([9; 32], 5);
+        game.deal
+            .deal_auction_mut()
+            .unwrap()
+            .set_hands(hands.clone(), dog);
+        game.set_bid(ids[0], bid::Target::GardeContre, false)
+            .unwrap();
+
+        let mut collector = EventsCollector { events: vec![] };
+        let mut game_manager = TarotGameManager::new(&mut game);
+        game_manager.register_listener(&mut collector);
+        for _ in 0..hands[0].size() * 5 {
+            let game = game_manager.get_game();
+            let pos = game.deal.next_player();
+            let player_id = game.player_by_pos(pos).unwrap().player.id;
+            let hand = game.deal.hands()[pos.to_n()];
+            for card in hand.list() {
+                if game_manager.set_play(player_id, card).is_ok() {
+                    break;
+                }
+            }
+        }
+        drop(game_manager);
+
+        let end = collector.events.len();
+        let summary = match &collector.events[end - 2..] {
+            [PlayEvent::EndDeal(result), PlayEvent::DealSummary(summary)] => {
+                assert_eq!(*result, summary.result);
+                summary.clone()
+            }
+            events => panic!("unexpected events {:?}", events),
+        };
+        assert_eq!(hands, summary.hands);
+        assert_eq!(dog, summary.dog);
+        // a garde contre leaves the dog to the defense
+        assert_eq!(dog, summary.discard);
+        assert_eq!(bid::Target::GardeContre, summary.contract.target);
+        if let deal::DealResult::GameOver { scores, .. } = &summary.result {
+            let defender = (0..5)
+                .find(|n| *n != summary.contract.author.to_n() && *n != summary.partner.to_n())
+                .unwrap();
+            assert_eq!(-summary.deal_points(), scores[defender]);
+        } else {
+            panic!("the deal is not over");
+        }
+    }
+
+    /// Logs the events like the server, and archives the finished deals
+    #[derive(PartialEq)]
+    struct Archiver {
+        archives: Vec<DealArchive>,
+    }
+
+    impl GameEventsListener<(PlayEvent, TarotGameState)> for Archiver {
+        fn notify(&mut self, (event, state): &(PlayEvent, TarotGameState)) {
+            state.log_event(event.clone());
+            if let PlayEvent::DealSummary(summary) = event {
+                self.archives
+                    .extend(state.deal_archive(Uuid::nil(), summary.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_deal_archive() {
+        let mut game = TarotGameState::default();
+        let ids: Vec<Uuid> = (0..5)
+            .map(|n| {
+                let id = Uuid::new_v4();
+                game.add_player(PlayerInfo {
+                    id,
+                    nickname: format!("player{}", n),
+                });
+                game.set_player_ready(id);
+                id
+            })
+            .collect();
+        game.manage_operation(DebugOperation::SetSeed([9; 32]));
+        let (hands, _dog) = deal_seeded_hands([9; 32], 5);
+
+        let mut archiver = Archiver { archives: vec![] };
+        let mut game_manager = TarotGameManager::new(&mut game);
+        game_manager.register_listener(&mut archiver);
+        game_manager
+            .set_bid(ids[0], bid::Target::GardeContre, false)
+            .unwrap();
+        for _ in 0..hands[0].size() * 5 {
+            let game = game_manager.get_game();
+            let pos = game.deal.next_player();
+            let player_id = game.player_by_pos(pos).unwrap().player.id;
+            let hand = game.deal.hands()[pos.to_n()];
+            for card in hand.list() {
+                if game_manager.set_play(player_id, card).is_ok() {
+                    break;
+                }
+            }
+        }
+        drop(game_manager);
+
+        assert_eq!(1, archiver.archives.len());
+        let archive = &archiver.archives[0];
+        assert_eq!(1, archive.deal);
+        assert_eq!(Some([9; 32]), archive.seed);
+        assert_eq!("player3", archive.players[3]);
+        assert_eq!(hands, archive.summary.hands);
+        assert_eq!(hands[0].size(), archive.tricks.len());
+        assert!(matches!(
+            archive.events.first().map(|e| &e.event),
+            Some(PlayEvent::Bid(_, bid::Target::GardeContre))
+        ));
+        assert!(matches!(
+            archive.events.last().map(|e| &e.event),
+            Some(PlayEvent::DealSummary(_))
+        ));
+        assert_eq!(bid::Target::GardeContre, archive.entry().contract.target);
+
+        // the taker keeps his hand with a garde contre, then one card goes with each trick
+        assert_eq!(hands, archive.hands_before(0));
+        assert!(archive
+            .hands_before(1)
+            .iter()
+            .all(|hand| hand.size() == hands[0].size() - 1));
+        assert!(archive
+            .hands_before(archive.tricks.len())
+            .iter()
+            .all(|hand| hand.is_empty()));
+
+        // the archive replays the whole deal
+        let mut replay = DealReplay::from_archive(archive);
+        assert_eq!(hands[0].size() * 5, replay.len());
+        replay.goto(replay.len());
+        assert_eq!(None, replay.next_player());
+        assert!(replay.legal_moves().is_empty());
+        if let deal::DealResult::GameOver { points, .. } = &archive.summary.result {
+            assert_eq!(points.as_slice(), replay.points());
+        }
+
+        // the events of the next deal start after the summary
+        game.log_event(PlayEvent::Pass(ids[1]));
+        game.log_event(PlayEvent::DealSummary(archive.summary.clone()));
+        assert_eq!(2, game.events.last_deal().len());
+        // and after a cancelled deal
+        game.log_event(PlayEvent::Pass(ids[2]));
+        game.log_event(PlayEvent::DealCancelled(None));
+        game.log_event(PlayEvent::Pass(ids[3]));
+        game.log_event(PlayEvent::DealSummary(archive.summary.clone()));
+        assert_eq!(2, game.events.last_deal().len());
+        // a new deal is dealt from a new seed
+        assert!(game.seed().is_some());
+        assert_ne!(Some([9; 32]), game.seed());
+    }
+
+    #[test]
+    fn test_deal_replay() {
+        let mut game = TarotGameState::default();
+        let ids: Vec<Uuid> = (0..5)
+            .map(|n| {
+                let id = Uuid::new_v4();
+                game.add_player(PlayerInfo {
+                    id,
+                    nickname: format!("player{}", n),
+                });
+                game.set_player_ready(id);
+                id
+            })
+            .collect();
+        let (hands, dog) = deal_seeded_hands([5; 32], 5);
+        game.deal
+            .deal_auction_mut()
+            .unwrap()
+            .set_hands(hands.clone(), dog);
+        game.set_bid(ids[0], bid::Target::GardeContre, false)
+            .unwrap();
+        play_cards(&mut game, 7);
+
+        let mut replay = game.deal_replay().unwrap();
+        assert_eq!(7, replay.len());
+        assert_eq!(&hands, replay.hands());
+        let next = replay.next_player().unwrap();
+        let moves = replay.legal_moves();
+        assert!(!moves.is_empty());
+        assert!(moves.iter().all(|card| hands[next.to_n()].has(*card)));
+
+        replay.goto(7);
+        assert_eq!(game.deal.hands(), replay.hands());
+        assert_eq!(2, replay.trick().cards.iter().flatten().count());
+        replay.previous_trick();
+        assert_eq!(5, replay.step());
+        assert_eq!(5, replay.trick().cards.iter().flatten().count());
+        assert!(replay.taker_points() >= 0.0);
+        replay.back();
+        replay.forward();
+        replay.next_trick();
+        assert_eq!(7, replay.step());
+
+        // the dealt hands, then a line per trick
+        let notation = replay.to_notation();
+        assert_eq!(11, notation.lines().count());
+        assert!(notation.lines().last().unwrap().starts_with("2. "));
+        let view = replay.player_view(next);
+        assert!(view.contains(&format!(
+            "hand: {}",
+            hands[next.to_n()]
+                .list()
+                .into_iter()
+                .map(crate::replay::notation)
+                .collect::<Vec<String>>()
+                .join(" ")
+        )));
+        // nobody sees the dog of a garde contre
+        assert!(!view.contains("dog:"));
+    }
+
+    #[test]
+    fn test_time_control() {
+        let mut game = TarotGameState::default();
+        game.time_control = Some(TimeControl {
+            per_action: 10,
+            bank: 5,
+        });
+        let ids: Vec<Uuid> = (0..5)
+            .map(|n| {
+                let id = Uuid::new_v4();
+                game.add_player(PlayerInfo {
+                    id,
+                    nickname: format!("player{}", n),
+                });
+                game.set_player_ready(id);
+                id
+            })
+            .collect();
+        let (hands, dog) = deal_seeded_hands([3; 32], 5);
+        game.deal.deal_auction_mut().unwrap().set_hands(hands, dog);
+
+        let clock = game.player_snapshot(ids[1]).clock.unwrap();
+        let bidder = game.deal.next_player();
+        assert_eq!(bidder, clock.pos);
+        assert!(clock.remaining <= 10_000);
+        assert_eq!(vec![5000; 5], clock.banks);
+        // the bank is used before the player runs out of time
+        let now = now_millis();
+        assert_eq!(None, game.timed_out(now + 12_000));
+        let late = now + 16_000;
+        let bidder_id = game.player_by_pos(bidder).unwrap().player.id;
+        assert_eq!(Some(bidder_id), game.timed_out(late));
+
+        let mut collector = EventsCollector { events: vec![] };
+        let mut game_manager = TarotGameManager::new(&mut game);
+        game_manager.register_listener(&mut collector);
+        assert!(game_manager.play_timeout(late).unwrap());
+        let taker = game_manager.get_game().deal.next_player();
+        let taker_id = game_manager
+            .get_game()
+            .player_by_pos(taker)
+            .unwrap()
+            .player
+            .id;
+        game_manager
+            .set_bid(taker_id, bid::Target::Garde, false)
+            .unwrap();
+        // the others pass, the taker calls a king and makes the dog
+        while game_manager.play_timeout(now_millis() + 16_000).unwrap() {
+            if matches!(game_manager.get_game().turn, Turn::Playing(_)) {
+                break;
+            }
+        }
+        assert_eq!(
+            vec![PlayEvent::TimedOut(bidder_id), PlayEvent::Pass(bidder_id)],
+            collector.events[0..2]
+        );
+        assert!(collector.events.contains(&PlayEvent::TimedOut(taker_id)));
+
+        // a legal card is played
+        let mut game_manager = TarotGameManager::new(&mut game);
+        game_manager.register_listener(&mut collector);
+        assert!(game_manager.play_timeout(now_millis() + 16_000).unwrap());
+        assert_eq!(1, game.deal_history.len());
+        let (player_id, cmd) = game.deal_history[0].clone();
+        let pos = game.players[&player_id].pos;
+        assert!(!game.deal.hands()[pos.to_n()].has(cmd.card));
+        assert_eq!(
+            Some(pos.next()),
+            game.clock_snapshot().map(|clock| clock.pos)
+        );
+
+        // no clock without time controls
+        game.time_control = None;
+        assert_eq!(None, game.player_snapshot(ids[0]).clock);
+        assert_eq!(None, game.timed_out(now_millis() + 3_600_000));
+    }
+
+    #[test]
+    fn test_restart() {
+        let mut game = TarotGameState::default();
+        let ids: Vec<Uuid> = (0..5)
+            .map(|n| {
+                let id = Uuid::new_v4();
+                game.add_player(PlayerInfo {
+                    id,
+                    nickname: format!("player{}", n),
+                });
+                game.set_player_ready(id);
+                id
+            })
+            .collect();
+        let (hands, dog) = deal_seeded_hands([4; 32], 5);
+        game.deal.deal_auction_mut().unwrap().set_hands(hands, dog);
+        game.set_bid(ids[0], bid::Target::GardeContre, false)
+            .unwrap();
+        play_cards(&mut game, 7);
+        // a bot plays for the player who left
+        let pos3 = game.players[&ids[3]].pos;
+        game.remove_player(ids[3]);
+        game.replace_absent_after = 0;
+        for id in &ids[0..3] {
+            game.replace_absent(*id, pos3).unwrap();
+        }
+        let bot = PlayerInfo {
+            id: Uuid::new_v4(),
+            nickname: String::from("bot"),
+        };
+        game.add_player(bot.clone());
+
+        // the server is stopped in the middle of the deal, and started again
+        let turn = game.turn;
+        let before = game.player_snapshot(ids[0]);
+        let saved = serde_json::to_string(&game).unwrap();
+        let mut game = serde_json::from_str::<TarotGameState>(&saved)
+            .unwrap()
+            .restored();
+        assert_eq!(5, game.absent.len());
+        assert!(!game.players.contains_key(&bot.id));
+        assert_eq!(turn, game.turn);
+
+        // the players come back to their seats, with their hands
+        for id in &ids {
+            let pos = game.add_player(PlayerInfo {
+                id: *id,
+                nickname: String::from("back"),
+            });
+            assert_eq!(
+                game.deal.hands()[pos.to_n()],
+                game.player_snapshot(*id).deal.hand
+            );
+        }
+        assert!(game.absent.is_empty());
+        assert_eq!(pos3, game.players[&ids[3]].pos);
+        assert_eq!(before.deal, game.player_snapshot(ids[0]).deal);
+
+        // and finish the deal
+        let remaining = game.deal.hands().iter().map(|hand| hand.size()).sum();
+        play_cards(&mut game, remaining);
+        assert_eq!(1, game.scores.len());
+    }
+
+    #[test]
+    fn test_error_codes() {
+        use std::str::FromStr;
+        use webgame_protocol::ProtocolError as GenericProtocolError;
+
+        let mut game = TarotGameState::default();
+        game.add_player(PlayerInfo {
+            id: Uuid::new_v4(),
+            nickname: "host".to_string(),
+        });
+        let guest = Uuid::new_v4();
+        assert_eq!(
+            Some(ErrorCode::NotHost),
+            game.set_bot_delay(guest, 1000).unwrap_err().code()
+        );
+
+        // the code goes through the generic error sent to the clients
+        let king = cards::Card::from_str("K♥").unwrap();
+        let error: GenericProtocolError =
+            ProtocolError::from(deal::PlayError::DogKing(king)).into();
+        let error = ProtocolError::from(&error);
+        assert_eq!(Some(ErrorCode::DogKing(king)), error.code());
+        assert_eq!("play: Can't put a king in the dog", error.message());
+
+        // errors without code are sent as they are
+        let error: GenericProtocolError =
+            ProtocolError::new(ProtocolErrorKind::BadState, "not in a game").into();
+        assert_eq!("not in a game", error.message());
+        assert_eq!(None, ProtocolError::from(&error).code());
+    }
+
+    #[test]
+    fn test_updates_bandwidth() {
+        let variant: usize = 4;
+        let mut game = TarotGameState::default();
+        game.set_variant(TarotVariant {
+            parameters: VariantSettings::new(variant as u8),
+        });
+        let ids: Vec<Uuid> = (0..variant)
+            .map(|n| {
+                let id = Uuid::new_v4();
+                game.add_player(PlayerInfo {
+                    id,
+                    nickname: format!("player{}", n),
+                });
+                id
+            })
+            .collect();
+        for id in &ids {
+            game.set_player_ready(*id);
+        }
+        let seed = [
+            7, 32, 3, 32, 54, 1, 84, 3, 32, 54, 1, 84, 3, 32, 65, 1, 84, 3, 32, 64, 1, 44, 3, 32,
+            54, 1, 84, 3, 32, 65, 1, 44,
+        ];
+        let (hands, dog) = deal_seeded_hands(seed, variant);
+        game.deal.deal_auction_mut().unwrap().set_hands(hands, dog);
+        game.set_bid(ids[0], bid::Target::GardeContre, false)
+            .unwrap();
+
+        let mut trackers: Vec<SnapshotTracker> =
+            ids.iter().map(|_| SnapshotTracker::new()).collect();
+        let sent = SentSnapshots::default();
+        let mut full_size = 0;
+        let mut updates_size = 0;
+        let deal_size = game.deal.hands()[0].size();
+        let mut listener = TarotEventsListener {};
+        let mut game_manager = TarotGameManager::new(&mut game);
+        game_manager.register_listener(&mut listener);
+        for _ in 0..deal_size * variant {
+            let game = game_manager.get_game();
+            let pos = game.deal.next_player();
+            let player_id = game.player_by_pos(pos).unwrap().player.id;
+            let hand = game.deal.hands()[pos.to_n()];
+            for card in hand.list() {
+                if game_manager.set_play(player_id, card).is_ok() {
+                    break;
+                }
+            }
+            // what the server broadcasts after each card
+            let game = game_manager.get_game();
+            for (id, tracker) in ids.iter().zip(trackers.iter_mut()) {
+                let snapshot = game.player_snapshot(*id);
+                let update = sent.update(*id, game.view(*id));
+                full_size += serde_json::to_string(&snapshot).unwrap().len();
+                updates_size += serde_json::to_string(&update).unwrap().len();
+                assert_eq!(Some(snapshot), tracker.apply(update));
+            }
+        }
+        assert_eq!(1, game_manager.get_game().scores.len());
+        assert!(updates_size * 3 < full_size);
+    }
+
     #[test]
     fn test_display() {
         let game = TarotGameState::default();
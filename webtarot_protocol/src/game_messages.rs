@@ -1,15 +1,51 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::message::ProtocolError;
+use crate::message::{ErrorCode, ProtocolError};
 use webgame_protocol::ProtocolErrorKind;
-use tarotgame::{cards, bid, deal, Announce};
+use tarotgame::{cards, bid, deal, pos, Announce};
+
+impl From<&deal::PlayError> for ErrorCode {
+    fn from(error: &deal::PlayError) -> Self {
+        use deal::PlayError::*;
+        match *error {
+            TurnError => ErrorCode::TurnError,
+            CardMissing => ErrorCode::CardMissing,
+            IncorrectSuit => ErrorCode::IncorrectSuit,
+            InvalidPiss => ErrorCode::InvalidPiss,
+            NonRaisedTrump => ErrorCode::NonRaisedTrump,
+            CallKingSuit => ErrorCode::CallKingSuit,
+            NoLastTrick => ErrorCode::NoLastTrick,
+            DogNotTaker => ErrorCode::DogNotTaker,
+            DogWrongNumberOfCards(wrong, right) => ErrorCode::DogWrongNumberOfCards(wrong, right),
+            DogSameCardTwice(card) => ErrorCode::DogSameCardTwice(card),
+            DogCardNotFound(card) => ErrorCode::DogCardNotFound(card),
+            DogOudler(card) => ErrorCode::DogOudler(card),
+            DogKing(card) => ErrorCode::DogKing(card),
+            DogTrump(card) => ErrorCode::DogTrump(card),
+            InvalidAnnounce => ErrorCode::InvalidAnnounce,
+        }
+    }
+}
+
+impl From<&bid::BidError> for ErrorCode {
+    fn from(error: &bid::BidError) -> Self {
+        match *error {
+            bid::BidError::AuctionClosed => ErrorCode::AuctionClosed,
+            bid::BidError::TurnError => ErrorCode::TurnError,
+            bid::BidError::NonRaisedTarget => ErrorCode::NonRaisedTarget,
+            bid::BidError::AuctionRunning => ErrorCode::AuctionRunning,
+            bid::BidError::NoContract => ErrorCode::NoContract,
+        }
+    }
+}
 
 impl From<deal::PlayError> for ProtocolError {
     fn from(error: deal::PlayError) -> Self {
         ProtocolError::new(
             ProtocolErrorKind::BadState,
             format!("play: {}", error)
-       )
+       ).with_code(ErrorCode::from(&error))
     }
 }
 
@@ -18,11 +54,12 @@ impl From<bid::BidError> for ProtocolError {
         ProtocolError::new(
             ProtocolErrorKind::BadState,
             format!("bid: {}", error)
-        )
+        ).with_code(ErrorCode::from(&error))
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "gcmd", rename_all = "snake_case")]
 pub enum GamePlayCommand {
     Bid(BidCommand),
@@ -31,31 +68,102 @@ pub enum GamePlayCommand {
     Pass,
     CallKing(CallKingCommand),
     MakeDog(MakeDogCommand),
+    ReplaceAbsent(ReplaceAbsentCommand),
+    /// Sent by a bot invited to replace an absent player
+    TakeSeat(TakeSeatCommand),
+    SetBotDelay(SetBotDelayCommand),
+    /// Sent by the host of a training table
+    SetHands(SetHandsCommand),
+    /// Sent by the players of a provably fair table, for the next deal
+    Entropy(EntropyCommand),
+    /// Asks for the whole game state after missing some updates
+    Resync,
+    Replay(ReplayCommand),
+    /// Sent by the client when joining a game, to check that it speaks the server's protocol
+    Hello(HelloCommand),
+    /// Sent when the countdown of the player who must act is over : the server acts for him if
+    /// his time really ran out
+    Timeout,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct BidCommand {
     pub target: bid::Target,
     pub slam: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct AnnounceCommand {
     pub announce: Announce,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct PlayCommand {
     pub card: cards::Card,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct CallKingCommand {
     pub card: cards::Card,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct MakeDogCommand {
     pub cards: cards::Hand,
     pub slam: bool,
 }
+
+/// Vote for a bot to take the seat of the absent player at `pos`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ReplaceAbsentCommand {
+    pub pos: pos::PlayerPos,
+}
+
+/// Token of the seat offered to the bot, given by the server when it starts it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TakeSeatCommand {
+    pub token: Uuid,
+}
+
+/// Change of the time, in milliseconds, the bots wait before playing
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SetBotDelayCommand {
+    pub delay: u32,
+}
+
+/// Hands and dog of the next deal, given by the host of a training table
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SetHandsCommand {
+    /// Hands of the players, by position
+    pub hands: Vec<cards::Hand>,
+    pub dog: cards::Hand,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EntropyCommand {
+    pub entropy: [u8; 32],
+}
+
+/// Asks for the events from number `since`, after a reconnection
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ReplayCommand {
+    pub since: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct HelloCommand {
+    /// `PROTOCOL_VERSION` of the client
+    pub version: u32,
+}
@@ -4,10 +4,20 @@ mod game_messages;
 mod player;
 mod turn;
 mod deal;
+mod update;
+mod clock;
+mod archive;
+mod replay;
+#[cfg(feature = "schema")]
+pub mod schema;
 
 pub use crate::game::*;
 pub use crate::message::*;
 pub use crate::game_messages::*;
 pub use crate::player::*;
 pub use crate::turn::*;
+pub use crate::archive::{ArchiveEntry, DealArchive};
+pub use crate::replay::{notation, DealReplay};
+pub use crate::clock::{now_millis, ClockSnapshot, TimeControl};
+pub use crate::update::{GameEvent, GameView, SnapshotTracker, StateUpdate, EVENTS_BUFFER_SIZE};
 // pub(crate) use webgame_protocol;
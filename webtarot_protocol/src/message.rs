@@ -5,19 +5,110 @@ use webgame_protocol::{
     Command as GenericCommand, Message as GenericMessage, ProtocolErrorKind, Variant,
 };
 
-use crate::game::{GameStateSnapshot, PlayEvent, VariantSettings};
+use tarotgame::cards::{Card, Hand};
+
+use crate::game::VariantSettings;
 use crate::game_messages::GamePlayCommand;
 use crate::player::{GamePlayerState, PlayerRole};
+use crate::update::{GameEvent, StateUpdate};
+
+/// Version of the messages exchanged with the server, to increase on every incompatible change
+pub const PROTOCOL_VERSION: u32 = 2;
 
+/// The generic error only has a message : when there is a code, the whole error is sent in it
+/// as JSON
 impl From<ProtocolError> for GenericProtocolError {
     fn from(error: ProtocolError) -> Self {
-        GenericProtocolError::new(error.kind, error.message)
+        let message = match error.code {
+            Some(_) => serde_json::to_string(&error).unwrap_or_else(|_| error.message.clone()),
+            None => error.message,
+        };
+        GenericProtocolError::new(error.kind, message)
+    }
+}
+
+impl From<&GenericProtocolError> for ProtocolError {
+    fn from(error: &GenericProtocolError) -> Self {
+        serde_json::from_str(error.message())
+            .unwrap_or_else(|_| ProtocolError::new(error.kind(), error.message()))
+    }
+}
+
+/// What went wrong, for the clients to translate and the bots to react
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ErrorCode {
+    /// Not the turn of the player
+    TurnError,
+    AuctionClosed,
+    NonRaisedTarget,
+    AuctionRunning,
+    NoContract,
+    CardMissing,
+    IncorrectSuit,
+    InvalidPiss,
+    NonRaisedTrump,
+    CallKingSuit,
+    NoLastTrick,
+    DogNotTaker,
+    /// Number of cards given, number expected
+    DogWrongNumberOfCards(usize, usize),
+    DogSameCardTwice(Card),
+    DogCardNotFound(Card),
+    DogOudler(Card),
+    DogKing(Card),
+    DogTrump(Card),
+    InvalidAnnounce,
+    NotCallingKing,
+    InvalidKing(Card),
+    NotSeated,
+    NotPlayer,
+    NoAbsentPlayer,
+    /// The token doesn't match any seat offered to bots
+    SeatNotOffered,
+    NotSpectator,
+    NotHost,
+    /// The game is not a training table
+    NotTraining,
+    /// The hands given don't share the 78 cards
+    InvalidHands,
+    /// The game doesn't take the entropy of the players
+    NotProvablyFair,
+    UnknownDealState,
+    UnknownAuction,
+    IncompatibleProtocol,
+}
+
+impl ErrorCode {
+    /// The bid, king, dog, announce or card played is not allowed by the rules
+    pub fn is_illegal_move(&self) -> bool {
+        matches!(
+            self,
+            ErrorCode::NonRaisedTarget
+                | ErrorCode::CardMissing
+                | ErrorCode::IncorrectSuit
+                | ErrorCode::InvalidPiss
+                | ErrorCode::NonRaisedTrump
+                | ErrorCode::CallKingSuit
+                | ErrorCode::DogWrongNumberOfCards(..)
+                | ErrorCode::DogSameCardTwice(_)
+                | ErrorCode::DogCardNotFound(_)
+                | ErrorCode::DogOudler(_)
+                | ErrorCode::DogKing(_)
+                | ErrorCode::DogTrump(_)
+                | ErrorCode::InvalidAnnounce
+                | ErrorCode::InvalidKing(_)
+        )
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ProtocolError {
+    #[cfg_attr(feature = "schema", schemars(with = "crate::schema::ProtocolErrorKind"))]
     kind: ProtocolErrorKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code: Option<ErrorCode>,
     message: String,
 }
 
@@ -25,38 +116,310 @@ impl ProtocolError {
     pub fn new<S: Into<String>>(kind: ProtocolErrorKind, s: S) -> ProtocolError {
         ProtocolError {
             kind,
+            code: None,
             message: s.into(),
         }
     }
 
+    pub fn with_code(mut self, code: ErrorCode) -> ProtocolError {
+        self.code = Some(code);
+        self
+    }
+
     pub fn kind(&self) -> ProtocolErrorKind {
         self.kind
     }
 
+    pub fn code(&self) -> Option<ErrorCode> {
+        self.code
+    }
+
     pub fn message(&self) -> &str {
         &self.message
     }
 }
 
+/// Answer to the `Hello` of a client which doesn't speak the server's protocol
+pub fn check_protocol_version(version: u32) -> Result<(), ProtocolError> {
+    if version == PROTOCOL_VERSION {
+        Ok(())
+    } else {
+        Err(ProtocolError::new(
+            ProtocolErrorKind::InvalidCommand,
+            "incompatible protocol version, please reload the page",
+        )
+        .with_code(ErrorCode::IncompatibleProtocol))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SetPlayerRoleCommand {
     pub role: PlayerRole,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum DebugOperation {
     SetSeed([u8; 32]),
+    /// Hands, by position, and dog of the next deal
+    SetHands(Vec<Hand>, Hand),
     ShowState,
 }
 
 impl webgame_protocol::DebugOperation for DebugOperation {}
 
-pub type Message = GenericMessage<GamePlayerState, GameStateSnapshot, DebugOperation, PlayEvent>;
+/// Outcome of a debug operation, sent back as JSON tagged with `outcome`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum OperationOutcome {
+    Done,
+    /// Whole state of the game, asked with `ShowState`
+    State {
+        state: serde_json::Value,
+    },
+    Failed {
+        error: ProtocolError,
+    },
+}
+
+impl OperationOutcome {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("debug outcomes are serializable")
+    }
+}
+
+pub type Message = GenericMessage<GamePlayerState, StateUpdate, DebugOperation, GameEvent>;
 pub type TarotVariant = Variant<VariantSettings>;
 pub type Command = GenericCommand<
     GamePlayCommand,
     SetPlayerRoleCommand,
-    GameStateSnapshot,
+    StateUpdate,
     DebugOperation,
     TarotVariant,
 >;
+
+/// Every variant of the commands and messages, checked against the schema mirrors
+#[cfg(all(test, feature = "schema"))]
+pub(crate) mod tests {
+    use std::str::FromStr;
+
+    use serde_json::json;
+    use tarotgame::fair::FairProof;
+    use tarotgame::{bid, cards, deal, pos, Announce, AnnounceType};
+    use uuid::Uuid;
+    use webgame_protocol::{
+        AuthenticateCommand, ChatMessage, DebugGameCommand, DebugUiCommand, GameExtendedInfo,
+        GameInfo, JoinGameCommand, PlayerDisconnectedMessage, PlayerInfo, ProtocolErrorKind,
+        SendTextCommand, ServerStatus,
+    };
+
+    use crate::*;
+
+    fn game_play_commands() -> Vec<GamePlayCommand> {
+        let card = cards::Card::from_str("K♥").unwrap();
+        vec![
+            GamePlayCommand::Bid(BidCommand {
+                target: bid::Target::Garde,
+                slam: false,
+            }),
+            GamePlayCommand::Announce(AnnounceCommand {
+                announce: Announce {
+                    atype: AnnounceType::Poignee,
+                    proof: None,
+                },
+            }),
+            GamePlayCommand::Play(PlayCommand { card }),
+            GamePlayCommand::Pass,
+            GamePlayCommand::CallKing(CallKingCommand { card }),
+            GamePlayCommand::MakeDog(MakeDogCommand {
+                cards: cards::Hand::new(),
+                slam: true,
+            }),
+            GamePlayCommand::ReplaceAbsent(ReplaceAbsentCommand {
+                pos: pos::PlayerPos::from_n(2, 4),
+            }),
+            GamePlayCommand::TakeSeat(TakeSeatCommand {
+                token: Uuid::from_u128(7),
+            }),
+            GamePlayCommand::SetBotDelay(SetBotDelayCommand { delay: 1000 }),
+            GamePlayCommand::SetHands(SetHandsCommand {
+                hands: vec![cards::Hand::new(); 4],
+                dog: cards::Hand::new(),
+            }),
+            GamePlayCommand::Entropy(EntropyCommand { entropy: [7; 32] }),
+            GamePlayCommand::Resync,
+            GamePlayCommand::Replay(ReplayCommand { since: 12 }),
+            GamePlayCommand::Hello(HelloCommand {
+                version: PROTOCOL_VERSION,
+            }),
+            GamePlayCommand::Timeout,
+        ]
+    }
+
+    fn play_events() -> Vec<PlayEvent> {
+        let id = Uuid::new_v4();
+        let card = cards::Card::from_str("1T").unwrap();
+        vec![
+            PlayEvent::Play(id, card),
+            PlayEvent::Announce(
+                id,
+                Announce {
+                    atype: AnnounceType::DoublePoignee,
+                    proof: Some(cards::Hand::new()),
+                },
+            ),
+            PlayEvent::Bid(id, bid::Target::Prise),
+            PlayEvent::Pass(id),
+            PlayEvent::KingCalled(id, card),
+            PlayEvent::DogMade(id, cards::Hand::new()),
+            PlayEvent::SlamDeclared(id),
+            PlayEvent::EndTrick,
+            PlayEvent::EndDeal(deal::DealResult::Nothing),
+            PlayEvent::EndDeal(deal::DealResult::GameOver {
+                points: vec![51.5, 39.5],
+                taker_diff: 10.5,
+                oudlers_count: 2,
+                petit_bonus: 0.0,
+                multiplier: 2,
+                slam_bonus: 0.0,
+                poignees_bonus: 20.0,
+                scores: vec![-82.0, 246.0, -82.0, -82.0],
+            }),
+            PlayEvent::DealSummary(DealSummary {
+                result: deal::DealResult::Nothing,
+                contract: bid::Contract {
+                    author: pos::PlayerPos::from_n(1, 4),
+                    target: bid::Target::Garde,
+                    slam: false,
+                },
+                king: None,
+                partner: pos::PlayerPos::from_n(1, 4),
+                hands: vec![cards::Hand::new(); 4],
+                dog: cards::Hand::new(),
+                discard: cards::Hand::new(),
+                fair: Some(FairProof {
+                    server_seed: [1; 32],
+                    entropies: vec![Some([2; 32]), None, None, Some([3; 32])],
+                }),
+            }),
+            PlayEvent::DealCancelled(None),
+            PlayEvent::DealCancelled(Some(FairProof {
+                server_seed: [4; 32],
+                entropies: vec![None, Some([5; 32]), None],
+            })),
+            PlayEvent::TimedOut(id),
+        ]
+    }
+
+    /// Commands of every kind
+    pub(crate) fn commands() -> Vec<Command> {
+        let mut commands = vec![
+            Command::Ping,
+            Command::Authenticate(AuthenticateCommand {
+                nickname: "bot".into(),
+            }),
+            Command::SendText(SendTextCommand {
+                text: "hello".into(),
+            }),
+            Command::ShowUuid,
+            Command::ShowServerStatus,
+            Command::NewGame(TarotVariant {
+                parameters: VariantSettings::new(5),
+            }),
+            Command::JoinGame(JoinGameCommand {
+                join_code: "ABCD".into(),
+            }),
+            Command::LeaveGame,
+            Command::MarkReady,
+            Command::Continue,
+            Command::InviteBot,
+            Command::SetPlayerRole(SetPlayerRoleCommand {
+                role: PlayerRole::Spectator,
+            }),
+            Command::DebugUi(DebugUiCommand {
+                player_id: Uuid::new_v4(),
+                snapshot: GameStateSnapshot::default().into(),
+            }),
+            Command::DebugGame(DebugGameCommand {
+                game_id: Uuid::new_v4(),
+                operation: DebugOperation::SetSeed([7; 32]),
+            }),
+            Command::DebugGame(DebugGameCommand {
+                game_id: Uuid::new_v4(),
+                operation: DebugOperation::SetHands(
+                    vec![cards::Hand::new(); 3],
+                    cards::Hand::new(),
+                ),
+            }),
+        ];
+        commands.extend(game_play_commands().into_iter().map(Command::GamePlay));
+        commands
+    }
+
+    /// Messages of every kind but `Message::Text`, which can't be serialized with the internal
+    /// tag, even in JSON
+    pub(crate) fn messages() -> Vec<Message> {
+        let player = PlayerInfo {
+            id: Uuid::new_v4(),
+            nickname: "bot".into(),
+        };
+        let game = GameInfo {
+            game_id: Uuid::new_v4(),
+            join_code: "ABCD".into(),
+        };
+        let mut messages = vec![
+            Message::Connected,
+            Message::Pong,
+            Message::ServerStatus(ServerStatus {
+                games: vec![GameExtendedInfo {
+                    game: game.clone(),
+                    players: vec![player.id],
+                }],
+            }),
+            Message::Chat(ChatMessage {
+                player_id: player.id,
+                text: "hello".into(),
+            }),
+            Message::PlayerConnected(GamePlayerState {
+                player: player.clone(),
+                pos: pos::PlayerPos::from_n(0, 5),
+                role: PlayerRole::Taker,
+                ready: true,
+            }),
+            Message::PlayerDisconnected(PlayerDisconnectedMessage {
+                player_id: player.id,
+            }),
+            Message::Authenticated(player),
+            Message::GameJoined(game),
+            Message::GameLeft,
+            Message::GameStateSnapshot(GameStateSnapshot::default().into()),
+            Message::GameStateSnapshot(TarotGameState::default().spectator_snapshot().into()),
+            Message::GameStateSnapshot(StateUpdate::Patch {
+                base: 3,
+                seq: 4,
+                patch: json!({"turn": "Intertrick", "deal": {"king": null}, "scores": [[1.5]]}),
+            }),
+            Message::Error(
+                ProtocolError::new(ProtocolErrorKind::BadState, "bad state")
+                    .with_code(ErrorCode::NotHost)
+                    .into(),
+            ),
+            Message::DebugGame(DebugOperation::SetSeed([7; 32])),
+        ];
+        messages.extend(play_events().into_iter().enumerate().map(|(seq, event)| {
+            Message::PlayEvent(GameEvent {
+                seq: seq as u64,
+                event,
+            })
+        }));
+        messages
+    }
+
+    #[test]
+    fn test_text_message() {
+        // a newtype variant holding a string can't be written with the internal tag of the
+        // messages, so the server never sends it
+        assert!(serde_json::to_string(&Message::Text("hello".into())).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::turn::Turn;
 
@@ -6,6 +7,7 @@ use tarotgame::pos;
 use webgame_protocol::{PlayerInfo, PlayerState};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum PlayerRole {
     Taker,
@@ -17,6 +19,7 @@ pub enum PlayerRole {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum PlayerAction {
     Bid,
     CallKing,
@@ -25,13 +28,40 @@ pub enum PlayerAction {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct GamePlayerState {
+    #[cfg_attr(feature = "schema", schemars(with = "crate::schema::PlayerInfo"))]
     pub player: PlayerInfo,
     pub pos: pos::PlayerPos,
     pub role: PlayerRole,
     pub ready: bool,
 }
 
+/// Seat of a player who left during a game. A bot can take it over until the player comes back.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AbsentPlayer {
+    #[cfg_attr(feature = "schema", schemars(with = "crate::schema::PlayerInfo"))]
+    pub player: PlayerInfo,
+    pub pos: pos::PlayerPos,
+    /// Time when the player left, in seconds since the unix epoch
+    pub since: u64,
+    /// Players who asked for a bot to replace him
+    pub votes: Vec<Uuid>,
+    /// Player who must invite the bot, once the replacement has been accepted
+    pub invited_by: Option<Uuid>,
+    /// Bot playing instead of him
+    #[cfg_attr(feature = "schema", schemars(with = "Option<crate::schema::PlayerInfo>"))]
+    pub substitute: Option<PlayerInfo>,
+}
+
+impl AbsentPlayer {
+    /// Whether the player has been away for more than `delay` seconds
+    pub fn grace_expired(&self, delay: u32) -> bool {
+        crate::game::now() >= self.since + delay as u64
+    }
+}
+
 impl PlayerState for GamePlayerState {
     fn player(self) -> PlayerInfo {
         self.player
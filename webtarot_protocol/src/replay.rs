@@ -0,0 +1,343 @@
+use std::fmt;
+
+use tarotgame::{bid, cards, deal, pos, trick};
+
+use crate::archive::DealArchive;
+
+/// A deal played again card by card, from a saved game state or an archived deal
+pub struct DealReplay {
+    /// Nicknames of the players, by position
+    players: Vec<String>,
+    /// Hands and dog as they were dealt
+    dealt: (Vec<cards::Hand>, cards::Hand),
+    /// The deal once the dog is made and the king called, before the first card
+    start: deal::DealState,
+    /// Cards in the order they were played
+    cards: Vec<(pos::PlayerPos, cards::Card)>,
+    /// Number of cards played at the current step
+    step: usize,
+    current: deal::DealState,
+}
+
+impl DealReplay {
+    /// `hands` are the hands as they were dealt, `discard` the cards put aside by the taker
+    pub(crate) fn new(
+        players: Vec<String>,
+        hands: Vec<cards::Hand>,
+        dog: cards::Hand,
+        discard: cards::Hand,
+        contract: bid::Contract,
+        king: Option<cards::Card>,
+        tricks: &[trick::Trick],
+    ) -> Self {
+        let taker = contract.author;
+        let mut playing = hands.clone();
+        if let Some(hand) = playing.get_mut(taker.to_n()) {
+            hand.merge(dog);
+            for card in discard.list() {
+                hand.remove(card);
+            }
+        }
+        let first = tricks.first().map_or(taker, |trick| trick.first);
+        let mut start = deal::DealState::new(first, playing, discard, contract, taker);
+        if let Some(king) = king {
+            start.call_king(taker, king);
+        }
+
+        let count = hands.len();
+        let mut cards = vec![];
+        for trick in tricks {
+            for player in trick.first.until_n(count) {
+                match trick.card_played(player) {
+                    Some(card) => cards.push((player, card)),
+                    None => break,
+                }
+            }
+        }
+
+        DealReplay {
+            players,
+            dealt: (hands, dog),
+            current: start.clone(),
+            start,
+            cards,
+            step: 0,
+        }
+    }
+
+    pub fn from_archive(archive: &DealArchive) -> Self {
+        let summary = &archive.summary;
+        DealReplay::new(
+            archive.players.clone(),
+            summary.hands.clone(),
+            summary.dog,
+            summary.discard,
+            summary.contract.clone(),
+            summary.king,
+            &archive.tricks,
+        )
+    }
+
+    /// Number of cards played in the deal
+    pub fn len(&self) -> usize {
+        self.cards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+
+    /// Number of cards played at the current step
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// Goes to the step where `step` cards have been played
+    pub fn goto(&mut self, step: usize) {
+        self.step = step.min(self.cards.len());
+        self.current = self.start.clone();
+        for (player, card) in &self.cards[..self.step] {
+            // the cards were checked when they were played
+            let _ = self.current.play_card(*player, *card);
+        }
+    }
+
+    pub fn forward(&mut self) {
+        self.goto(self.step + 1);
+    }
+
+    pub fn back(&mut self) {
+        self.goto(self.step.saturating_sub(1));
+    }
+
+    /// Goes to the end of the next trick
+    pub fn next_trick(&mut self) {
+        let count = self.players.len();
+        self.goto((self.step / count + 1) * count);
+    }
+
+    /// Goes to the end of the previous trick
+    pub fn previous_trick(&mut self) {
+        let count = self.players.len();
+        self.goto((self.step.saturating_sub(1) / count) * count);
+    }
+
+    pub fn hands(&self) -> &Vec<cards::Hand> {
+        self.current.hands()
+    }
+
+    /// Trick of the last card played, the first one before any card
+    pub fn trick(&self) -> &trick::Trick {
+        let idx = self.step.saturating_sub(1) / self.players.len();
+        let tricks = self.current.tricks();
+        tricks
+            .get(idx)
+            .unwrap_or_else(|| self.current.current_trick())
+    }
+
+    /// Points won by each player so far
+    pub fn points(&self) -> &[f32] {
+        self.current.points()
+    }
+
+    /// Points won so far by the taker and his partner
+    pub fn taker_points(&self) -> f32 {
+        let taker = self.current.contract().author;
+        let partner = self.current.partner();
+        self.points()
+            .iter()
+            .enumerate()
+            .filter(|(n, _)| *n == taker.to_n() || *n == partner.to_n())
+            .map(|(_, points)| points)
+            .sum()
+    }
+
+    /// Player who plays the next card, none at the end of the deal
+    pub fn next_player(&self) -> Option<pos::PlayerPos> {
+        if self.step < self.cards.len() || !self.current.is_over() {
+            Some(self.current.next_player())
+        } else {
+            None
+        }
+    }
+
+    /// Cards the next player is allowed to play
+    pub fn legal_moves(&self) -> Vec<cards::Card> {
+        let player = match self.next_player() {
+            Some(player) => player,
+            None => return vec![],
+        };
+        let hand = self.hands()[player.to_n()];
+        let is_first_trick = self.current.tricks().len() == 1;
+        hand.list()
+            .into_iter()
+            .filter(|card| {
+                deal::can_play(
+                    player,
+                    *card,
+                    hand,
+                    self.current.current_trick(),
+                    self.current.king(),
+                    is_first_trick,
+                )
+                .is_ok()
+            })
+            .collect()
+    }
+
+    fn name(&self, player: pos::PlayerPos) -> &str {
+        self.players
+            .get(player.to_n())
+            .map_or("?", |name| name.as_str())
+    }
+
+    /// The whole deal in text notation : the contract, the hands as they were dealt, then one
+    /// line per trick with the cards in the order they were played and the winner
+    pub fn to_notation(&self) -> String {
+        let contract = self.start.contract();
+        let mut lines = vec![
+            format!("players: {}", self.players.join(", ")),
+            format!(
+                "contract: {} {}",
+                contract.to_string(),
+                self.name(contract.author)
+            ),
+        ];
+        if let Some(king) = self.start.king() {
+            lines.push(format!("king: {}", notation(king)));
+        }
+        lines.push(format!("dog: {}", hand_notation(self.dealt.1)));
+        lines.push(format!("discard: {}", hand_notation(self.start.dog())));
+        for (n, hand) in self.dealt.0.iter().enumerate() {
+            lines.push(format!("{}: {}", self.players[n], hand_notation(*hand)));
+        }
+        lines.extend(self.trick_lines());
+        lines.join("\n") + "\n"
+    }
+
+    /// The deal as the player at `pos` saw it : his own hand, the dog when it was shown, and
+    /// the tricks
+    pub fn player_view(&self, player: pos::PlayerPos) -> String {
+        let contract = self.start.contract();
+        let mut lines = vec![
+            format!("view of {}", self.name(player)),
+            format!("players: {}", self.players.join(", ")),
+            format!(
+                "contract: {} {}",
+                contract.to_string(),
+                self.name(contract.author)
+            ),
+        ];
+        if let Some(king) = self.start.king() {
+            lines.push(format!("king: {}", notation(king)));
+        }
+        let dog_shown =
+            contract.target == bid::Target::Prise || contract.target == bid::Target::Garde;
+        if dog_shown {
+            lines.push(format!("dog: {}", hand_notation(self.dealt.1)));
+        }
+        if player == contract.author && dog_shown {
+            lines.push(format!("discard: {}", hand_notation(self.start.dog())));
+        }
+        lines.push(format!(
+            "hand: {}",
+            hand_notation(self.dealt.0[player.to_n()])
+        ));
+        lines.extend(self.trick_lines());
+        lines.join("\n") + "\n"
+    }
+
+    /// One line per trick
+    fn trick_lines(&self) -> Vec<String> {
+        let count = self.players.len();
+        let mut replay = self.start.clone();
+        let mut lines = vec![];
+        for (idx, cards) in self.cards.chunks(count).enumerate() {
+            let mut line = format!("{}.", idx + 1);
+            for (player, card) in cards {
+                let _ = replay.play_card(*player, *card);
+                line.push_str(&format!(" {} {}", self.name(*player), notation(*card)));
+            }
+            if cards.len() == count {
+                let winner = replay.tricks()[idx].winner;
+                line.push_str(&format!(", won by {}", self.name(winner)));
+            }
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+/// The current step : the hands, the trick, the points and the cards the next player can play
+impl fmt::Display for DealReplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count = self.players.len();
+        writeln!(
+            f,
+            "card {}/{}, trick {}",
+            self.step,
+            self.cards.len(),
+            self.step.saturating_sub(1) / count + 1
+        )?;
+        let taker = self.current.contract().author;
+        let next = self.next_player();
+        for (n, hand) in self.hands().iter().enumerate() {
+            let player = pos::PlayerPos::from_n(n, count as u8);
+            let marker = if Some(player) == next { ">" } else { " " };
+            let role = if player == taker {
+                " (taker)"
+            } else if player == self.current.partner() && self.current.king().is_some() {
+                " (partner)"
+            } else {
+                ""
+            };
+            writeln!(
+                f,
+                "{} {}{}: {}",
+                marker,
+                self.name(player),
+                role,
+                hand_notation(*hand)
+            )?;
+        }
+        let trick = self.trick();
+        let played: Vec<String> = trick
+            .first
+            .until_n(count)
+            .map(|player| {
+                let card = trick
+                    .card_played(player)
+                    .map_or(String::from("-"), notation);
+                format!("{} {}", self.name(player), card)
+            })
+            .collect();
+        writeln!(f, "trick: {}", played.join(", "))?;
+        let points: Vec<String> = self
+            .points()
+            .iter()
+            .enumerate()
+            .map(|(n, points)| format!("{} {}", self.players[n], points))
+            .collect();
+        writeln!(
+            f,
+            "points: {}, taker's team {}",
+            points.join(", "),
+            self.taker_points()
+        )?;
+        if let Some(next) = next {
+            let moves: Vec<String> = self.legal_moves().into_iter().map(notation).collect();
+            writeln!(f, "{} can play: {}", self.name(next), moves.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// ASCII notation of a card, as read by `Card::from_str` : 7H, KS, 21T, ET for the excuse
+pub fn notation(card: cards::Card) -> String {
+    card.rank().to_string() + &card.suit().to_safe_string()
+}
+
+fn hand_notation(hand: cards::Hand) -> String {
+    let cards: Vec<String> = hand.list().into_iter().map(notation).collect();
+    cards.join(" ")
+}
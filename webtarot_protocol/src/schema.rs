@@ -0,0 +1,248 @@
+//! JSON Schema of the messages exchanged with the server, for generating third-party clients.
+//!
+//! `Command` and `Message` are generic types defined by `webgame_protocol`, which doesn't
+//! derive `JsonSchema` : they are mirrored here with the tarot types filled in, and the tests
+//! check that the mirrors read and write every variant of the real types the same way.
+use schemars::schema::RootSchema;
+use schemars::{schema_for, JsonSchema};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::game::{PlayEvent, VariantSettings};
+use crate::game_messages::GamePlayCommand;
+use crate::message::{DebugOperation, ProtocolError, SetPlayerRoleCommand};
+use crate::player::GamePlayerState;
+use crate::update::{GameEvent, StateUpdate};
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PlayerInfo {
+    pub id: Uuid,
+    pub nickname: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GameInfo {
+    pub game_id: Uuid,
+    pub join_code: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct GameExtendedInfo {
+    pub game: GameInfo,
+    pub players: Vec<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ServerStatus {
+    pub games: Vec<GameExtendedInfo>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ChatMessage {
+    pub player_id: Uuid,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PlayerDisconnectedMessage {
+    pub player_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtocolErrorKind {
+    NotFound,
+    NotAuthenticated,
+    InvalidCommand,
+    BadState,
+    InternalError,
+}
+
+/// Error sent by the server. When it has a code, `message` holds the whole `ProtocolError` as
+/// JSON.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ErrorMessage {
+    pub kind: ProtocolErrorKind,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct AuthenticateCommand {
+    pub nickname: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct JoinGameCommand {
+    pub join_code: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SendTextCommand {
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DebugGameCommand {
+    pub game_id: Uuid,
+    pub operation: DebugOperation,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DebugUiCommand {
+    pub player_id: Uuid,
+    pub snapshot: StateUpdate,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct TarotVariant {
+    pub parameters: VariantSettings,
+}
+
+/// Command sent by a client
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Command {
+    Ping,
+    Authenticate(AuthenticateCommand),
+    SendText(SendTextCommand),
+    ShowUuid,
+    ShowServerStatus,
+    NewGame(TarotVariant),
+    JoinGame(JoinGameCommand),
+    LeaveGame,
+    MarkReady,
+    Continue,
+    InviteBot,
+    GamePlay(GamePlayCommand),
+    SetPlayerRole(SetPlayerRoleCommand),
+    DebugUi(DebugUiCommand),
+    DebugGame(DebugGameCommand),
+}
+
+/// Message sent by the server
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    Connected,
+    Pong,
+    ServerStatus(ServerStatus),
+    Chat(ChatMessage),
+    PlayerConnected(GamePlayerState),
+    PlayerDisconnected(PlayerDisconnectedMessage),
+    Authenticated(PlayerInfo),
+    GameJoined(GameInfo),
+    GameLeft,
+    GameStateSnapshot(StateUpdate),
+    Error(ErrorMessage),
+    PlayEvent(GameEvent),
+    DebugGame(DebugOperation),
+    Text(String),
+}
+
+/// Schemas of the protocol, by type name
+pub fn schemas() -> Vec<(&'static str, RootSchema)> {
+    vec![
+        ("Command", schema_for!(Command)),
+        ("Message", schema_for!(Message)),
+        ("GamePlayCommand", schema_for!(GamePlayCommand)),
+        ("PlayEvent", schema_for!(PlayEvent)),
+        ("ProtocolError", schema_for!(ProtocolError)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+    use crate::message;
+
+    /// Name of the variant : a variant added to the real types must be mirrored and tested
+    fn command_name(command: &message::Command) -> &'static str {
+        match command {
+            message::Command::Ping => "ping",
+            message::Command::Authenticate(_) => "authenticate",
+            message::Command::SendText(_) => "send_text",
+            message::Command::ShowUuid => "show_uuid",
+            message::Command::ShowServerStatus => "show_server_status",
+            message::Command::NewGame(_) => "new_game",
+            message::Command::JoinGame(_) => "join_game",
+            message::Command::LeaveGame => "leave_game",
+            message::Command::MarkReady => "mark_ready",
+            message::Command::Continue => "continue",
+            message::Command::InviteBot => "invite_bot",
+            message::Command::GamePlay(_) => "game_play",
+            message::Command::SetPlayerRole(_) => "set_player_role",
+            message::Command::DebugUi(_) => "debug_ui",
+            message::Command::DebugGame(_) => "debug_game",
+        }
+    }
+
+    fn message_name(msg: &message::Message) -> &'static str {
+        match msg {
+            message::Message::Connected => "connected",
+            message::Message::Pong => "pong",
+            message::Message::ServerStatus(_) => "server_status",
+            message::Message::Chat(_) => "chat",
+            message::Message::PlayerConnected(_) => "player_connected",
+            message::Message::PlayerDisconnected(_) => "player_disconnected",
+            message::Message::Authenticated(_) => "authenticated",
+            message::Message::GameJoined(_) => "game_joined",
+            message::Message::GameLeft => "game_left",
+            message::Message::GameStateSnapshot(_) => "game_state_snapshot",
+            message::Message::Error(_) => "error",
+            message::Message::PlayEvent(_) => "play_event",
+            message::Message::DebugGame(_) => "debug_game",
+            message::Message::Text(_) => "text",
+        }
+    }
+
+    /// The mirrored type reads the JSON of the real one and writes it back unchanged
+    fn assert_mirrored<Real: Serialize, Mirror: Serialize + DeserializeOwned>(value: &Real) {
+        let json = serde_json::to_value(value).unwrap();
+        let mirrored: Mirror =
+            serde_json::from_value(json.clone()).unwrap_or_else(|e| panic!("{} : {}", json, e));
+        assert_eq!(json, serde_json::to_value(&mirrored).unwrap());
+    }
+
+    #[test]
+    fn test_mirrored_types() {
+        let commands = message::tests::commands();
+        let names: BTreeSet<&str> = commands.iter().map(command_name).collect();
+        assert_eq!(15, names.len(), "untested commands");
+        for command in &commands {
+            assert_mirrored::<message::Command, Command>(command);
+        }
+
+        let messages = message::tests::messages();
+        let mut names: BTreeSet<&str> = messages.iter().map(message_name).collect();
+        // `Message::Text` can't be serialized with the internal tag
+        names.insert(message_name(&message::Message::Text(String::new())));
+        assert_eq!(14, names.len(), "untested messages");
+        for msg in &messages {
+            assert_mirrored::<message::Message, Message>(msg);
+        }
+    }
+    #[test]
+    fn test_schemas() {
+        let names: Vec<&str> = schemas().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(
+            vec![
+                "Command",
+                "Message",
+                "GamePlayCommand",
+                "PlayEvent",
+                "ProtocolError"
+            ],
+            names
+        );
+        let schema = serde_json::to_string(&schema_for!(GamePlayCommand)).unwrap();
+        assert!(schema.contains("\"hello\""));
+        let schema = serde_json::to_value(schema_for!(Message)).unwrap();
+        assert!(schema["definitions"]["PlayEvent"].is_object());
+    }
+}
@@ -5,6 +5,7 @@ use tarotgame::{bid, pos};
 use crate::deal::Deal;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum Turn {
     Pregame,
     Intertrick,
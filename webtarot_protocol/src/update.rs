@@ -0,0 +1,401 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::game::{GameStateSnapshot, PlayEvent, SpectatorSnapshot};
+
+/// Game state sent to a player : the whole snapshot, or the changes since the previous one.
+/// Updates tracked by the server are numbered from 1, a whole snapshot numbered 0 can't be the
+/// base of a patch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum StateUpdate {
+    Full {
+        seq: u64,
+        snapshot: GameStateSnapshot,
+    },
+    /// Whole view of a spectator
+    Spectator {
+        seq: u64,
+        snapshot: SpectatorSnapshot,
+    },
+    /// JSON merge patch (RFC 7396) to apply on the snapshot number `base`
+    Patch { base: u64, seq: u64, patch: Value },
+}
+
+impl webgame_protocol::GameStateSnapshot for StateUpdate {}
+
+impl From<GameStateSnapshot> for StateUpdate {
+    fn from(snapshot: GameStateSnapshot) -> Self {
+        StateUpdate::Full { seq: 0, snapshot }
+    }
+}
+
+impl From<SpectatorSnapshot> for StateUpdate {
+    fn from(snapshot: SpectatorSnapshot) -> Self {
+        StateUpdate::Spectator { seq: 0, snapshot }
+    }
+}
+
+/// View of the game of someone sitting at the table or watching it
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameView {
+    Player(GameStateSnapshot),
+    Spectator(SpectatorSnapshot),
+}
+
+impl GameView {
+    pub fn is_spectator(&self) -> bool {
+        matches!(self, GameView::Spectator(_))
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            GameView::Player(snapshot) => serde_json::to_value(snapshot),
+            GameView::Spectator(snapshot) => serde_json::to_value(snapshot),
+        }
+        .unwrap_or(Value::Null)
+    }
+
+    fn into_update(self, seq: u64) -> StateUpdate {
+        match self {
+            GameView::Player(snapshot) => StateUpdate::Full { seq, snapshot },
+            GameView::Spectator(snapshot) => StateUpdate::Spectator { seq, snapshot },
+        }
+    }
+}
+
+impl From<GameView> for StateUpdate {
+    fn from(view: GameView) -> Self {
+        view.into_update(0)
+    }
+}
+
+impl From<GameStateSnapshot> for GameView {
+    fn from(snapshot: GameStateSnapshot) -> Self {
+        GameView::Player(snapshot)
+    }
+}
+
+impl From<SpectatorSnapshot> for GameView {
+    fn from(snapshot: SpectatorSnapshot) -> Self {
+        GameView::Spectator(snapshot)
+    }
+}
+
+/// Last view sent to each player. The game server sends whole snapshots through an immutable
+/// game state when somebody joins or leaves, they must be tracked as well.
+#[derive(Default)]
+pub(crate) struct SentSnapshots(Mutex<HashMap<Uuid, SentView>>);
+
+impl Clone for SentSnapshots {
+    fn clone(&self) -> Self {
+        SentSnapshots(Mutex::new(self.0.lock().unwrap().clone()))
+    }
+}
+
+#[derive(Clone)]
+struct SentView {
+    seq: u64,
+    spectator: bool,
+    value: Value,
+}
+
+impl SentSnapshots {
+    /// Returns the changes since the last view sent to the player
+    pub fn update(&self, player_id: Uuid, view: impl Into<GameView>) -> StateUpdate {
+        let view = view.into();
+        let value = view.to_value();
+        let mut sent = self.0.lock().unwrap();
+        match sent.get_mut(&player_id) {
+            // a patch can't turn a player view into a spectator one
+            Some(last) if last.spectator == view.is_spectator() => {
+                let patch = diff(&last.value, &value).unwrap_or_else(|| Value::Object(Map::new()));
+                let base = last.seq;
+                last.seq += 1;
+                last.value = value;
+                StateUpdate::Patch {
+                    base,
+                    seq: last.seq,
+                    patch,
+                }
+            }
+            _ => Self::insert(&mut sent, player_id, view, value),
+        }
+    }
+
+    /// Returns the whole view, the next updates are based on it
+    pub fn full(&self, player_id: Uuid, view: impl Into<GameView>) -> StateUpdate {
+        let view = view.into();
+        let value = view.to_value();
+        Self::insert(&mut self.0.lock().unwrap(), player_id, view, value)
+    }
+
+    fn insert(
+        sent: &mut HashMap<Uuid, SentView>,
+        player_id: Uuid,
+        view: GameView,
+        value: Value,
+    ) -> StateUpdate {
+        let seq = sent.get(&player_id).map_or(1, |last| last.seq + 1);
+        sent.insert(
+            player_id,
+            SentView {
+                seq,
+                spectator: view.is_spectator(),
+                value,
+            },
+        );
+        view.into_update(seq)
+    }
+
+    pub fn forget(&self, player_id: Uuid) {
+        self.0.lock().unwrap().remove(&player_id);
+    }
+}
+
+/// Event sent to the players, numbered so that a player can ask for those he missed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GameEvent {
+    pub seq: u64,
+    pub event: PlayEvent,
+}
+
+/// Number of events kept for the players who reconnect
+pub const EVENTS_BUFFER_SIZE: usize = 200;
+
+/// Recent events of a game with the number of the next one, shared by the copies of a game
+/// state
+#[derive(Clone, Default)]
+pub(crate) struct EventLog(Arc<Mutex<(u64, VecDeque<GameEvent>)>>);
+
+impl EventLog {
+    pub fn push(&self, event: PlayEvent) -> GameEvent {
+        let mut log = self.0.lock().unwrap();
+        let event = GameEvent { seq: log.0, event };
+        log.0 += 1;
+        log.1.push_back(event.clone());
+        if log.1.len() > EVENTS_BUFFER_SIZE {
+            log.1.pop_front();
+        }
+        event
+    }
+
+    /// Events from number `seq`, as far as they are kept
+    pub fn since(&self, seq: u64) -> Vec<GameEvent> {
+        let log = self.0.lock().unwrap();
+        log.1.iter().filter(|e| e.seq >= seq).cloned().collect()
+    }
+
+    pub fn next_seq(&self) -> u64 {
+        self.0.lock().unwrap().0
+    }
+
+    /// Events of the last finished deal, from the end of the previous one, played or cancelled,
+    /// to its summary, as far as they are kept
+    pub fn last_deal(&self) -> Vec<GameEvent> {
+        let log = self.0.lock().unwrap();
+        let is_summary = |e: &GameEvent| matches!(e.event, PlayEvent::DealSummary(_));
+        let ends_deal = |e: &GameEvent| {
+            matches!(
+                e.event,
+                PlayEvent::DealSummary(_) | PlayEvent::DealCancelled(_)
+            )
+        };
+        let end = match log.1.iter().rposition(is_summary) {
+            Some(end) => end,
+            None => return vec![],
+        };
+        let start = log
+            .1
+            .iter()
+            .take(end)
+            .rposition(ends_deal)
+            .map_or(0, |idx| idx + 1);
+        log.1.range(start..=end).cloned().collect()
+    }
+}
+
+/// Rebuilds the game state from the updates sent by the server
+#[derive(Default)]
+pub struct SnapshotTracker {
+    seq: Option<u64>,
+    spectator: bool,
+    state: Value,
+}
+
+impl SnapshotTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the new game state of a player, or None if some updates were missed : a full
+    /// snapshot must then be asked to the server with `GamePlayCommand::Resync`. Spectator
+    /// views are ignored.
+    pub fn apply(&mut self, update: StateUpdate) -> Option<GameStateSnapshot> {
+        match self.apply_view(update)? {
+            GameView::Player(snapshot) => Some(snapshot),
+            GameView::Spectator(_) => None,
+        }
+    }
+
+    /// Returns the new view of the game, or None if some updates were missed
+    pub fn apply_view(&mut self, update: StateUpdate) -> Option<GameView> {
+        let (seq, view) = match update {
+            StateUpdate::Full { seq, snapshot } => (seq, GameView::Player(snapshot)),
+            StateUpdate::Spectator { seq, snapshot } => (seq, GameView::Spectator(snapshot)),
+            StateUpdate::Patch { base, seq, patch } => {
+                if self.seq != Some(base) {
+                    self.seq = None;
+                    return None;
+                }
+                merge(&mut self.state, patch);
+                let view = if self.spectator {
+                    serde_json::from_value(self.state.clone()).map(GameView::Spectator)
+                } else {
+                    serde_json::from_value(self.state.clone()).map(GameView::Player)
+                };
+                return match view {
+                    Ok(view) => {
+                        self.seq = Some(seq);
+                        Some(view)
+                    }
+                    Err(_) => {
+                        self.seq = None;
+                        None
+                    }
+                };
+            }
+        };
+        self.seq = Some(seq);
+        self.spectator = view.is_spectator();
+        self.state = view.to_value();
+        Some(view)
+    }
+}
+
+/// Merge patch transforming `old` into `new`, None if they are equal
+fn diff(old: &Value, new: &Value) -> Option<Value> {
+    if old == new {
+        return None;
+    }
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut patch = Map::new();
+            for (key, value) in new {
+                match old.get(key) {
+                    Some(old_value) => {
+                        if let Some(changes) = diff(old_value, value) {
+                            patch.insert(key.clone(), changes);
+                        }
+                    }
+                    None => {
+                        patch.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            for key in old.keys().filter(|key| !new.contains_key(*key)) {
+                patch.insert(key.clone(), Value::Null);
+            }
+            Some(Value::Object(patch))
+        }
+        _ => Some(new.clone()),
+    }
+}
+
+fn merge(target: &mut Value, patch: Value) {
+    match patch {
+        Value::Object(patch) => {
+            if !target.is_object() {
+                *target = Value::Object(Map::new());
+            }
+            let target = target.as_object_mut().unwrap();
+            for (key, value) in patch {
+                if value.is_null() {
+                    target.remove(&key);
+                } else {
+                    merge(target.entry(key).or_insert(Value::Null), value);
+                }
+            }
+        }
+        patch => *target = patch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_patch() {
+        let old = json!({"turn": "Pregame", "deal": {"hand": [1, 2], "king": "K♣"}, "scores": []});
+        let new = json!({"turn": "Intertrick", "deal": {"hand": [1]}, "scores": []});
+        let patch = diff(&old, &new).unwrap();
+        assert_eq!(
+            patch,
+            json!({"turn": "Intertrick", "deal": {"hand": [1], "king": null}})
+        );
+        let mut target = old.clone();
+        merge(&mut target, patch);
+        assert_eq!(target, new);
+        assert_eq!(None, diff(&new, &new));
+    }
+
+    #[test]
+    fn test_tracker_gap() {
+        let sent = SentSnapshots::default();
+        let mut tracker = SnapshotTracker::new();
+        let id = Uuid::new_v4();
+        let mut snapshot = GameStateSnapshot::default();
+
+        let full = sent.update(id, snapshot.clone());
+        assert_eq!(Some(snapshot.clone()), tracker.apply(full));
+
+        snapshot.replace_absent_after = 30;
+        let patch = sent.update(id, snapshot.clone());
+        assert_eq!(Some(snapshot.clone()), tracker.apply(patch));
+
+        // a lost update
+        snapshot.bot_delay = Some(1000);
+        sent.update(id, snapshot.clone());
+        snapshot.nb_players = 3;
+        assert_eq!(None, tracker.apply(sent.update(id, snapshot.clone())));
+
+        // resync
+        assert_eq!(
+            Some(snapshot.clone()),
+            tracker.apply(sent.full(id, snapshot.clone()))
+        );
+        snapshot.nb_players = 4;
+        assert_eq!(
+            Some(snapshot.clone()),
+            tracker.apply(sent.update(id, snapshot.clone()))
+        );
+
+        // untracked snapshot numbered 0
+        tracker.apply(snapshot.clone().into());
+        snapshot.nb_players = 5;
+        assert_eq!(None, tracker.apply(sent.update(id, snapshot.clone())));
+    }
+
+    #[test]
+    fn test_event_log() {
+        let log = EventLog::default();
+        for _ in 0..EVENTS_BUFFER_SIZE + 10 {
+            log.push(PlayEvent::EndTrick);
+        }
+        assert_eq!(EVENTS_BUFFER_SIZE as u64 + 10, log.next_seq());
+        let missed = log.since(EVENTS_BUFFER_SIZE as u64 + 8);
+        assert_eq!(
+            vec![EVENTS_BUFFER_SIZE as u64 + 8, EVENTS_BUFFER_SIZE as u64 + 9],
+            missed.iter().map(|e| e.seq).collect::<Vec<u64>>()
+        );
+        // the oldest events are lost
+        assert_eq!(10, log.since(0)[0].seq);
+    }
+}
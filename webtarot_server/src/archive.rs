@@ -0,0 +1,188 @@
+//! Finished deals kept on disk, and the HTTP endpoint to browse them.
+//!
+//! Each deal is a JSON file `<game id>/<deal number>.json` in the directory given by
+//! `WEBTAROT_ARCHIVES`. The endpoint listens on `WEBTAROT_ARCHIVES_HOST` (127.0.0.1 by default)
+//! and `WEBTAROT_ARCHIVES_PORT` (8003 by default) : the web server in front of webtarot forwards
+//! `/archives` to it, or the client is built with its URL and fetches it from another origin.
+use std::env;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use log::error;
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::Filter;
+
+use crate::tarot_protocol::{ArchiveEntry, DealArchive};
+
+pub const ARCHIVES_ENV: &str = "WEBTAROT_ARCHIVES";
+pub const ARCHIVES_HOST_ENV: &str = "WEBTAROT_ARCHIVES_HOST";
+pub const ARCHIVES_PORT_ENV: &str = "WEBTAROT_ARCHIVES_PORT";
+pub const DEFAULT_ARCHIVES_HOST: [u8; 4] = [127, 0, 0, 1];
+pub const DEFAULT_ARCHIVES_PORT: u16 = 8003;
+
+/// Number of deals listed by the index, the most recent first
+pub const INDEX_SIZE: usize = 100;
+
+lazy_static! {
+    /// Archives of the server, deals are not archived when `WEBTAROT_ARCHIVES` is not set
+    pub static ref ARCHIVES: Option<Archives> = Archives::from_env();
+}
+
+pub struct Archives {
+    dir: PathBuf,
+}
+
+impl Archives {
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Archives {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn from_env() -> Option<Self> {
+        let dir = env::var(ARCHIVES_ENV).ok()?;
+        Archives::open(&dir)
+            .map_err(|e| error!("Can't open archives {}: {}", dir, e))
+            .ok()
+    }
+
+    fn path(&self, game_id: Uuid, deal: usize) -> PathBuf {
+        self.dir
+            .join(game_id.to_string())
+            .join(format!("{}.json", deal))
+    }
+
+    pub fn save(&self, archive: &DealArchive) -> io::Result<()> {
+        fs::create_dir_all(self.dir.join(archive.game_id.to_string()))?;
+        let json = serde_json::to_vec(archive)?;
+        fs::write(self.path(archive.game_id, archive.deal), json)
+    }
+
+    pub fn load(&self, game_id: Uuid, deal: usize) -> io::Result<DealArchive> {
+        let json = fs::read(self.path(game_id, deal))?;
+        serde_json::from_slice(&json).map_err(io::Error::from)
+    }
+
+    /// Most recent deals, the unreadable files are skipped
+    pub fn index(&self) -> io::Result<Vec<ArchiveEntry>> {
+        let mut entries = vec![];
+        for game in fs::read_dir(&self.dir)? {
+            let game = game?.path();
+            if !game.is_dir() {
+                continue;
+            }
+            for deal in fs::read_dir(&game)? {
+                let path = deal?.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                    continue;
+                }
+                let loaded = fs::read(&path).and_then(|json| {
+                    serde_json::from_slice::<DealArchive>(&json).map_err(io::Error::from)
+                });
+                match loaded {
+                    Ok(archive) => entries.push(archive.entry()),
+                    Err(e) => error!("Can't read archive {}: {}", path.display(), e),
+                }
+            }
+        }
+        entries.sort_by(|a, b| b.finished_at.cmp(&a.finished_at).then(b.deal.cmp(&a.deal)));
+        entries.truncate(INDEX_SIZE);
+        Ok(entries)
+    }
+}
+
+/// Serves `GET /archives`, the index, and `GET /archives/<game id>/<deal number>`
+pub async fn serve(archives: &'static Archives) {
+    let host = env::var(ARCHIVES_HOST_ENV)
+        .ok()
+        .and_then(|host| host.parse().ok())
+        .unwrap_or_else(|| IpAddr::from(DEFAULT_ARCHIVES_HOST));
+    let port = env::var(ARCHIVES_PORT_ENV)
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_ARCHIVES_PORT);
+    let index = warp::path!("archives")
+        .and(warp::get())
+        .map(move || match archives.index() {
+            Ok(entries) => warp::reply::with_status(warp::reply::json(&entries), StatusCode::OK),
+            Err(e) => {
+                error!("Can't list archives: {}", e);
+                warp::reply::with_status(
+                    warp::reply::json(&"can't list the archives"),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                )
+            }
+        });
+    let deal = warp::path!("archives" / Uuid / usize)
+        .and(warp::get())
+        .map(move |game_id, deal| match archives.load(game_id, deal) {
+            Ok(archive) => warp::reply::with_status(warp::reply::json(&archive), StatusCode::OK),
+            Err(_) => {
+                warp::reply::with_status(warp::reply::json(&"no such deal"), StatusCode::NOT_FOUND)
+            }
+        });
+    // the client may be served from another origin than the archives
+    let cors = warp::cors().allow_any_origin().allow_method("GET");
+    warp::serve(index.or(deal).with(cors))
+        .run((host, port))
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tarot_protocol::DealSummary;
+    use tarotgame::{bid, cards, deal, pos};
+
+    fn archive(game_id: Uuid, deal: usize, finished_at: u64) -> DealArchive {
+        let first = pos::PlayerPos::from_n(0, 3);
+        DealArchive {
+            game_id,
+            deal,
+            finished_at,
+            seed: Some([1; 32]),
+            players: vec![String::from("a"), String::from("b"), String::from("c")],
+            summary: DealSummary {
+                result: deal::DealResult::Nothing,
+                contract: bid::Contract {
+                    author: first,
+                    target: bid::Target::Garde,
+                    slam: false,
+                },
+                king: None,
+                partner: first,
+                hands: vec![cards::Hand::new(); 3],
+                dog: cards::Hand::new(),
+                discard: cards::Hand::new(),
+                fair: None,
+            },
+            tricks: vec![],
+            events: vec![],
+            scores: vec![],
+        }
+    }
+
+    #[test]
+    fn test_save_and_index() {
+        let dir = env::temp_dir().join(format!("webtarot-archives-{}", Uuid::new_v4()));
+        let archives = Archives::open(&dir).unwrap();
+        let game_id = Uuid::new_v4();
+        archives.save(&archive(game_id, 1, 100)).unwrap();
+        archives.save(&archive(game_id, 2, 200)).unwrap();
+        archives.save(&archive(Uuid::new_v4(), 1, 150)).unwrap();
+        fs::write(dir.join(game_id.to_string()).join("notes.txt"), "no deal").unwrap();
+
+        assert_eq!(archive(game_id, 2, 200), archives.load(game_id, 2).unwrap());
+        assert!(archives.load(game_id, 3).is_err());
+        let index = archives.index().unwrap();
+        let finished: Vec<u64> = index.iter().map(|entry| entry.finished_at).collect();
+        assert_eq!(vec![200, 150, 100], finished);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,57 @@
+//! Bots running in the process of the server.
+//!
+//! They are started through the unix socket the bot listener is given at launch. The server
+//! starts itself the bot replacing an absent player, with the token of the seat offered to it :
+//! the token never leaves the server process.
+use std::io;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use log::error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use uuid::Uuid;
+
+use crate::tarot_bot;
+
+lazy_static! {
+    /// Socket of the bot listener, once started
+    static ref SOCKET: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// Starts the bot listener, see `webtarot_bot::socket_listener::start`
+pub fn start(str_socket: &str, str_websocket: &str) {
+    *SOCKET.lock().unwrap() = Some(String::from(str_socket));
+    tarot_bot::socket_listener::start(str_socket, str_websocket);
+}
+
+/// Starts a bot taking the seat offered with `token` in the game `join_code`
+pub async fn take_seat(join_code: &str, token: Uuid) {
+    let socket = SOCKET.lock().unwrap().clone();
+    let socket = match socket {
+        Some(socket) => socket,
+        None => {
+            error!(
+                "no bot listener to take the seat offered in game {}",
+                join_code
+            );
+            return;
+        }
+    };
+    match send(&socket, &format!("SEAT {} {}", join_code, token)).await {
+        Ok(answer) if answer.starts_with("OK") => {}
+        Ok(answer) => error!("could not start a bot in game {}: {}", join_code, answer),
+        Err(e) => error!("could not reach the bot listener: {}", e),
+    }
+}
+
+/// Sends a command to the bot listener, returns its answer
+async fn send(socket: &str, command: &str) -> io::Result<String> {
+    let mut stream = UnixStream::connect(socket).await?;
+    let (reader, mut writer) = stream.split();
+    writer
+        .write_all(format!("{}\n", command).as_bytes())
+        .await?;
+    let answer = BufReader::new(reader).lines().next_line().await?;
+    Ok(answer.unwrap_or_default())
+}
@@ -1,4 +1,9 @@
-use std::sync::Arc;
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
 use uuid::Uuid;
 
 use crate::protocol::GameState;
@@ -6,31 +11,86 @@ use crate::webgame_server::game::Game;
 use crate::webgame_server::universe::Universe;
 use webgame_protocol::GameManager;
 
-use crate::protocol::{ChatMessage, Message, ProtocolError, ProtocolErrorKind};
+use crate::archive::ARCHIVES;
+use crate::bots;
+use crate::protocol::{Message, ProtocolError, ProtocolErrorKind};
+use crate::store::STORE;
 
 use crate::tarot_protocol::{
-    AnnounceCommand, BidCommand, CallKingCommand, GameEventsListener, GamePlayCommand,
-    MakeDogCommand, PlayCommand, PlayEvent, SetPlayerRoleCommand, TarotGameManager, TarotGameState,
+    check_protocol_version, now_millis, AnnounceCommand, BidCommand, CallKingCommand,
+    EntropyCommand, GameEvent, GameEventsListener, GamePlayCommand, HelloCommand, MakeDogCommand,
+    PlayCommand, PlayEvent, ReplaceAbsentCommand, ReplayCommand, SetBotDelayCommand,
+    SetHandsCommand, SetPlayerRoleCommand, StateUpdate, TakeSeatCommand, TarotGameManager,
+    TarotGameState, PROTOCOL_VERSION,
 };
 
+lazy_static! {
+    /// Timer of each game with time controls : the deadline it waits for and its task
+    static ref TIMERS: Mutex<HashMap<Uuid, (u64, JoinHandle<()>)>> = Mutex::new(HashMap::new());
+}
+
 //see https://users.rust-lang.org/t/how-to-store-async-function-pointer/38343/4
 type DynFut<T> = ::std::pin::Pin<Box<dyn Send + ::std::future::Future<Output = T>>>;
 
 pub fn on_gameplay(
-    universe: Arc<Universe<TarotGameState, PlayEvent>>,
+    universe: Arc<Universe<TarotGameState, GameEvent>>,
     user_id: Uuid,
     cmd: GamePlayCommand,
 ) -> DynFut<Result<(), ProtocolError>> {
     Box::pin(async move {
         if let Some(game) = universe.get_user_game(user_id).await {
-            match cmd {
-                GamePlayCommand::Bid(cmd) => on_player_bid(game, user_id, cmd).await,
-                GamePlayCommand::Announce(cmd) => on_player_announce(game, user_id, cmd).await,
-                GamePlayCommand::Play(cmd) => on_player_play(game, user_id, cmd).await,
-                GamePlayCommand::CallKing(cmd) => on_player_call_king(game, user_id, cmd).await,
-                GamePlayCommand::MakeDog(cmd) => on_player_make_dog(game, user_id, cmd).await,
-                GamePlayCommand::Pass => on_player_pass(game, user_id).await,
+            let read_only = matches!(
+                cmd,
+                GamePlayCommand::Resync | GamePlayCommand::Replay(_) | GamePlayCommand::Hello(_)
+            );
+            let result = match cmd {
+                GamePlayCommand::Bid(cmd) => on_player_bid(game.clone(), user_id, cmd).await,
+                GamePlayCommand::Announce(cmd) => {
+                    on_player_announce(game.clone(), user_id, cmd).await
+                }
+                GamePlayCommand::Play(cmd) => on_player_play(game.clone(), user_id, cmd).await,
+                GamePlayCommand::CallKing(cmd) => {
+                    on_player_call_king(game.clone(), user_id, cmd).await
+                }
+                GamePlayCommand::MakeDog(cmd) => {
+                    on_player_make_dog(game.clone(), user_id, cmd).await
+                }
+                GamePlayCommand::Pass => on_player_pass(game.clone(), user_id).await,
+                GamePlayCommand::ReplaceAbsent(cmd) => {
+                    on_player_replace_absent(game.clone(), user_id, cmd).await
+                }
+                GamePlayCommand::TakeSeat(cmd) => {
+                    on_player_take_seat(game.clone(), user_id, cmd).await
+                }
+                GamePlayCommand::SetBotDelay(cmd) => {
+                    on_player_set_bot_delay(game.clone(), user_id, cmd).await
+                }
+                GamePlayCommand::SetHands(cmd) => {
+                    on_player_set_hands(game.clone(), user_id, cmd).await
+                }
+                GamePlayCommand::Entropy(cmd) => {
+                    on_player_entropy(game.clone(), user_id, cmd).await
+                }
+                GamePlayCommand::Resync => on_player_resync(game.clone(), user_id).await,
+                GamePlayCommand::Replay(cmd) => on_player_replay(game.clone(), user_id, cmd).await,
+                GamePlayCommand::Hello(cmd) => {
+                    let result = on_player_hello(user_id, cmd);
+                    if result.is_ok() {
+                        restore_saved(&game, user_id).await;
+                    }
+                    result
+                }
+                GamePlayCommand::Timeout => {
+                    play_timeout(&game).await;
+                    Ok(())
+                }
+            };
+            if !read_only {
+                persist(&game).await;
+                // the player who must act may have changed
+                schedule_timeout(&game).await;
             }
+            result
         } else {
             Err(ProtocolError::new(
                 ProtocolErrorKind::BadState,
@@ -41,7 +101,7 @@ pub fn on_gameplay(
 }
 
 pub fn on_player_set_role(
-    universe: Arc<Universe<TarotGameState, PlayEvent>>,
+    universe: Arc<Universe<TarotGameState, GameEvent>>,
     user_id: Uuid,
     cmd: SetPlayerRoleCommand,
 ) -> DynFut<Result<(), ProtocolError>> {
@@ -62,7 +122,10 @@ pub fn on_player_set_role(
             game.mark_player_ready(user_id).await;
             // game.set_player_not_ready(user_id).await;
 
-            game.broadcast_current_state().await;
+            send_current_state(&game).await;
+            persist(&game).await;
+            // the game may have started
+            schedule_timeout(&game).await;
             Ok(())
         } else {
             Err(ProtocolError::new(
@@ -74,29 +137,29 @@ pub fn on_player_set_role(
 }
 
 pub async fn on_player_bid(
-    game: Arc<Game<TarotGameState, PlayEvent>>,
+    game: Arc<Game<TarotGameState, GameEvent>>,
     player_id: Uuid,
     cmd: BidCommand,
 ) -> Result<(), ProtocolError> {
-    game.broadcast(&Message::Chat(ChatMessage {
-        player_id,
-        text: format!("{:?}", cmd.target),
-    }))
-    .await;
-
-    let game_state = game.state_handle();
+    let mut listener = TarotEventsListener {
+        game_id: game.id,
+        events_states: vec![],
+    };
     {
         //lock
+        let game_state = game.state_handle();
         let mut game_state = game_state.lock().await;
-        game_state.set_bid(player_id, cmd.target, cmd.slam)?;
+        let mut game_manager = TarotGameManager::new(&mut game_state);
+        game_manager.register_listener(&mut listener);
+        game_manager.set_bid(player_id, cmd.target, cmd.slam)?;
     }
-    game.broadcast_current_state().await;
+    broadcast_events(&game, listener).await;
 
     Ok(())
 }
 
 pub async fn on_player_announce(
-    game: Arc<Game<TarotGameState, PlayEvent>>,
+    game: Arc<Game<TarotGameState, GameEvent>>,
     player_id: Uuid,
     cmd: AnnounceCommand,
 ) -> Result<(), ProtocolError> {
@@ -107,9 +170,9 @@ pub async fn on_player_announce(
         drop(game_state);
         game.send(player_id, &Message::Error(e.into())).await;
     } else {
+        let event = game_state.log_event(PlayEvent::Announce(player_id, ann));
         drop(game_state);
-        game.broadcast(&Message::PlayEvent(PlayEvent::Announce(player_id, ann)))
-            .await;
+        game.broadcast(&Message::PlayEvent(event)).await;
     }
     Ok(())
 }
@@ -133,8 +196,192 @@ impl GameEventsListener<(PlayEvent, TarotGameState)> for TarotEventsListener {
     }
 }
 
+/// Broadcasts the events emitted by the game manager, then the new state
+async fn broadcast_events(game: &Game<TarotGameState, GameEvent>, listener: TarotEventsListener) {
+    for (event, state) in listener.events_states {
+        game.broadcast(&Message::PlayEvent(state.log_event(event)))
+            .await;
+    }
+    send_current_state(&game).await;
+}
+
+/// Sends to each player the changes of his view of `state`, an intermediate state emitted with
+/// an event, since the last update he got
+async fn send_state(game: &Game<TarotGameState, GameEvent>, state: &TarotGameState) {
+    let updates = {
+        let current = game.state_handle().lock().await;
+        next_updates(&current, state)
+    };
+    for (pid, update) in updates {
+        game.send(pid, &Message::GameStateSnapshot(update)).await;
+    }
+}
+
+/// Sends to each player the changes of the game since the last update he got
+async fn send_current_state(game: &Game<TarotGameState, GameEvent>) {
+    let updates = {
+        let state = game.state_handle().lock().await;
+        next_updates(&state, &state)
+    };
+    for (pid, update) in updates {
+        game.send(pid, &Message::GameStateSnapshot(update)).await;
+    }
+}
+
+/// Changes of the view of `state` for the players and the spectators, since the last update
+/// they got from the `current` game state
+fn next_updates(current: &TarotGameState, state: &TarotGameState) -> Vec<(Uuid, StateUpdate)> {
+    state
+        .viewers()
+        .into_iter()
+        .map(|pid| (pid, current.next_update(pid, state.view(pid))))
+        .collect()
+}
+
+/// Saves the game, so that it can be played again after a restart of the server. A game which
+/// is not in progress has nothing to resume, its file is deleted.
+async fn persist(game: &Game<TarotGameState, GameEvent>) {
+    if let Some(store) = STORE.as_ref() {
+        let saved = {
+            let state = game.state_handle().lock().await;
+            if state.is_in_progress() {
+                Some(serde_json::to_vec(&*state))
+            } else {
+                None
+            }
+        };
+        let result = match saved {
+            Some(Ok(json)) => store.save(game.id, json).await,
+            Some(Err(e)) => Err(e.into()),
+            None => store.delete(game.id).await,
+        };
+        if let Err(e) = result {
+            error!("could not save game {}: {}", game.id, e);
+        }
+    }
+}
+
+/// Puts back in play the game saved before a restart where the player had a seat, if he is
+/// alone in a new game : the other players join it with its join code
+async fn restore_saved(game: &Arc<Game<TarotGameState, GameEvent>>, player_id: Uuid) {
+    let store = match STORE.as_ref() {
+        Some(store) => store,
+        None => return,
+    };
+    {
+        let state = game.state_handle();
+        let mut state = state.lock().await;
+        if state.is_in_progress() || state.get_players().keys().any(|pid| *pid != player_id) {
+            return;
+        }
+        let player_info = match state.get_players().get(&player_id) {
+            Some(player) => player.player.clone(),
+            None => return,
+        };
+        match store.claim(player_id).await {
+            Some(saved) => *state = saved,
+            None => return,
+        }
+        state.add_player(player_info);
+    }
+    info!(
+        "saved game of player {} resumed in game {}",
+        player_id, game.id
+    );
+    persist(game).await;
+    send_current_state(game).await;
+    schedule_timeout(game).await;
+}
+/// Archives the deal ended by the event, so that it can be replayed later
+fn archive_deal(game_id: Uuid, state: &TarotGameState, event: &GameEvent) {
+    if let (Some(archives), PlayEvent::DealSummary(summary)) = (ARCHIVES.as_ref(), &event.event) {
+        if let Some(archive) = state.deal_archive(game_id, summary.clone()) {
+            if let Err(e) = archives.save(&archive) {
+                error!(
+                    "could not archive deal {} of game {}: {}",
+                    archive.deal, game_id, e
+                );
+            }
+        }
+    }
+}
+
+/// Acts for the player who must act once his time runs out, when the game has time controls.
+/// Each game has a single timer, armed again only when the deadline changes with the turn.
+async fn schedule_timeout(game: &Arc<Game<TarotGameState, GameEvent>>) {
+    let deadline = game.state_handle().lock().await.clock_deadline();
+    arm_timer(game.clone(), deadline);
+}
+
+fn arm_timer(game: Arc<Game<TarotGameState, GameEvent>>, deadline: Option<u64>) {
+    let mut timers = TIMERS.lock().unwrap();
+    if timers.get(&game.id).map(|(armed, _)| *armed) == deadline {
+        return;
+    }
+    let game_id = game.id;
+    let previous = match deadline {
+        Some(deadline) => {
+            timers.insert(game_id, (deadline, tokio::spawn(run_timer(game, deadline))))
+        }
+        None => timers.remove(&game_id),
+    };
+    if let Some((_, task)) = previous {
+        task.abort();
+    }
+}
+
+async fn run_timer(game: Arc<Game<TarotGameState, GameEvent>>, deadline: u64) {
+    let wait = deadline.saturating_sub(now_millis());
+    tokio::time::sleep(Duration::from_millis(wait)).await;
+    {
+        let mut timers = TIMERS.lock().unwrap();
+        if timers.get(&game.id).map(|(armed, _)| *armed) != Some(deadline) {
+            // the turn changed meanwhile, the new timer takes over
+            return;
+        }
+        timers.remove(&game.id);
+    }
+    if play_timeout(&game).await {
+        persist(&game).await;
+    }
+    let deadline = game.state_handle().lock().await.clock_deadline();
+    arm_timer(game, deadline);
+}
+
+/// Passes or plays for the player who ran out of time, returns false if nobody did
+async fn play_timeout(game: &Game<TarotGameState, GameEvent>) -> bool {
+    let mut listener = TarotEventsListener {
+        game_id: game.id,
+        events_states: vec![],
+    };
+    let game_state = game.state_handle();
+    let mut game_state = game_state.lock().await;
+    let mut game_manager = TarotGameManager::new(&mut game_state);
+    game_manager.register_listener(&mut listener);
+    let result = game_manager.play_timeout(now_millis());
+    drop(game_manager);
+    drop(game_state);
+    match result {
+        Ok(true) => {
+            for (event, state) in listener.events_states {
+                let event = state.log_event(event);
+                archive_deal(game.id, &state, &event);
+                send_state(&game, &state).await;
+                game.broadcast(&Message::PlayEvent(event)).await;
+            }
+            send_current_state(&game).await;
+            true
+        }
+        Ok(false) => false,
+        Err(e) => {
+            error!("could not act for the player out of time: {:?}", e);
+            false
+        }
+    }
+}
+
 pub async fn on_player_play(
-    game: Arc<Game<TarotGameState, PlayEvent>>,
+    game: Arc<Game<TarotGameState, GameEvent>>,
     player_id: Uuid,
     cmd: PlayCommand,
 ) -> Result<(), ProtocolError> {
@@ -162,64 +409,277 @@ pub async fn on_player_play(
         // game.broadcast(&Message::PlayEvent(PlayEvent::Play ( player_id, cmd.card ))).await; // uncomment to show played cards
         for (event, state) in listener.events_states {
             // println!("new state event {:?}!", event);
-            game.broadcast_state(&state).await;
+            let event = state.log_event(event);
+            archive_deal(game.id, &state, &event);
+            send_state(&game, &state).await;
             game.broadcast(&Message::PlayEvent(event)).await;
         }
-        game.broadcast_current_state().await;
+        send_current_state(&game).await;
     }
     Ok(())
 }
 
 pub async fn on_player_pass(
-    game: Arc<Game<TarotGameState, PlayEvent>>,
+    game: Arc<Game<TarotGameState, GameEvent>>,
     player_id: Uuid,
 ) -> Result<(), ProtocolError> {
-    let game_state = game.state_handle();
+    let mut listener = TarotEventsListener {
+        game_id: game.id,
+        events_states: vec![],
+    };
     {
+        let game_state = game.state_handle();
         let mut game_state = game_state.lock().await;
-        game_state.set_pass(player_id)?;
-    }
-    game.broadcast(&Message::Chat(ChatMessage {
-        player_id,
-        text: format!("pass"),
-    }))
-    .await;
-    game.broadcast_current_state().await;
+        let mut game_manager = TarotGameManager::new(&mut game_state);
+        game_manager.register_listener(&mut listener);
+        game_manager.set_pass(player_id)?;
+    }
+    broadcast_events(&game, listener).await;
     Ok(())
 }
 
 pub async fn on_player_call_king(
-    game: Arc<Game<TarotGameState, PlayEvent>>,
+    game: Arc<Game<TarotGameState, GameEvent>>,
     player_id: Uuid,
     cmd: CallKingCommand,
 ) -> Result<(), ProtocolError> {
-    game.broadcast(&Message::Chat(ChatMessage {
-        player_id,
-        text: format!("call king: {}", cmd.card.to_string()),
-    }))
-    .await;
-    let game_state = game.state_handle();
+    let mut listener = TarotEventsListener {
+        game_id: game.id,
+        events_states: vec![],
+    };
     {
+        let game_state = game.state_handle();
         let mut game_state = game_state.lock().await;
-        game_state.call_king(player_id, cmd.card);
+        let mut game_manager = TarotGameManager::new(&mut game_state);
+        game_manager.register_listener(&mut listener);
+        game_manager.call_king(player_id, cmd.card)?;
     }
-    game.broadcast_current_state().await;
+    broadcast_events(&game, listener).await;
     Ok(())
 }
 
 pub async fn on_player_make_dog(
-    game: Arc<Game<TarotGameState, PlayEvent>>,
+    game: Arc<Game<TarotGameState, GameEvent>>,
     player_id: Uuid,
     cmd: MakeDogCommand,
 ) -> Result<(), ProtocolError> {
+    let mut listener = TarotEventsListener {
+        game_id: game.id,
+        events_states: vec![],
+    };
     let game_state = game.state_handle();
     let mut game_state = game_state.lock().await;
-    if let Err(e) = game_state.make_dog(player_id, cmd.cards, cmd.slam) {
-        drop(game_state);
+    let mut game_manager = TarotGameManager::new(&mut game_state);
+    game_manager.register_listener(&mut listener);
+    let dog_result = game_manager.make_dog(player_id, cmd.cards, cmd.slam);
+    drop(game_manager);
+    drop(game_state);
+    if let Err(e) = dog_result {
         game.send(player_id, &Message::Error(e.into())).await;
     } else {
-        drop(game_state);
-        game.broadcast_current_state().await;
+        broadcast_events(&game, listener).await;
+    }
+    Ok(())
+}
+
+pub async fn on_player_replace_absent(
+    game: Arc<Game<TarotGameState, GameEvent>>,
+    player_id: Uuid,
+    cmd: ReplaceAbsentCommand,
+) -> Result<(), ProtocolError> {
+    let game_state = game.state_handle();
+    let token = {
+        let mut game_state = game_state.lock().await;
+        game_state.replace_absent(player_id, cmd.pos)?
+    };
+    // the players learn from the new state that a bot is coming
+    send_current_state(&game).await;
+    if let Some(token) = token {
+        bots::take_seat(game.join_code(), token).await;
+    }
+    Ok(())
+}
+
+pub async fn on_player_take_seat(
+    game: Arc<Game<TarotGameState, GameEvent>>,
+    player_id: Uuid,
+    cmd: TakeSeatCommand,
+) -> Result<(), ProtocolError> {
+    let game_state = game.state_handle();
+    {
+        let mut game_state = game_state.lock().await;
+        game_state.take_seat(player_id, cmd.token)?;
+    }
+    // the bot gets the hand of the absent player
+    send_current_state(&game).await;
+    Ok(())
+}
+
+pub async fn on_player_set_bot_delay(
+    game: Arc<Game<TarotGameState, GameEvent>>,
+    player_id: Uuid,
+    cmd: SetBotDelayCommand,
+) -> Result<(), ProtocolError> {
+    let game_state = game.state_handle();
+    {
+        let mut game_state = game_state.lock().await;
+        game_state.set_bot_delay(player_id, cmd.delay)?;
+    }
+    // bots already in the game pick up the new delay from the state
+    send_current_state(&game).await;
+    Ok(())
+}
+
+pub async fn on_player_set_hands(
+    game: Arc<Game<TarotGameState, GameEvent>>,
+    player_id: Uuid,
+    cmd: SetHandsCommand,
+) -> Result<(), ProtocolError> {
+    let game_state = game.state_handle();
+    {
+        let mut game_state = game_state.lock().await;
+        game_state.set_hands(player_id, cmd.hands, cmd.dog)?;
     }
+    // the players get their new hands when the deal has not started
+    send_current_state(&game).await;
     Ok(())
 }
+
+pub async fn on_player_entropy(
+    game: Arc<Game<TarotGameState, GameEvent>>,
+    player_id: Uuid,
+    cmd: EntropyCommand,
+) -> Result<(), ProtocolError> {
+    let game_state = game.state_handle();
+    {
+        let mut game_state = game_state.lock().await;
+        game_state.add_entropy(player_id, cmd.entropy)?;
+    }
+    // the players see who gave his entropy
+    send_current_state(&game).await;
+    Ok(())
+}
+
+pub async fn on_player_resync(
+    game: Arc<Game<TarotGameState, GameEvent>>,
+    player_id: Uuid,
+) -> Result<(), ProtocolError> {
+    let game_state = game.state_handle();
+    let update = game_state.lock().await.resync(player_id);
+    game.send(player_id, &Message::GameStateSnapshot(update))
+        .await;
+    Ok(())
+}
+
+pub async fn on_player_replay(
+    game: Arc<Game<TarotGameState, GameEvent>>,
+    player_id: Uuid,
+    cmd: ReplayCommand,
+) -> Result<(), ProtocolError> {
+    let game_state = game.state_handle();
+    let events = game_state.lock().await.events_since(cmd.since);
+    // the events the player missed while he was disconnected
+    for event in events {
+        game.send(player_id, &Message::PlayEvent(event)).await;
+    }
+    Ok(())
+}
+
+pub fn on_player_hello(player_id: Uuid, cmd: HelloCommand) -> Result<(), ProtocolError> {
+    check_protocol_version(cmd.version).map_err(|e| {
+        warn!(
+            "player {} uses protocol version {}, server version is {}",
+            player_id, cmd.version, PROTOCOL_VERSION
+        );
+        e.into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tarot_protocol::{GameView, SnapshotTracker, TarotVariant, Turn, VariantSettings};
+    use tarotgame::bid;
+    use webgame_protocol::PlayerInfo;
+
+    fn player(nickname: &str) -> PlayerInfo {
+        PlayerInfo {
+            id: Uuid::new_v4(),
+            nickname: String::from(nickname),
+        }
+    }
+
+    /// Id of the player who must act
+    fn next_player_id(state: &TarotGameState) -> Uuid {
+        let pos = state.get_deal().next_player();
+        state
+            .get_players()
+            .values()
+            .find(|player| player.pos == pos)
+            .map(|player| player.player.id)
+            .unwrap()
+    }
+
+    fn update_of(updates: Vec<(Uuid, StateUpdate)>, pid: Uuid) -> StateUpdate {
+        updates
+            .into_iter()
+            .find(|(id, _)| *id == pid)
+            .map(|(_, update)| update)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_spectator_updates() {
+        let mut state = TarotGameState::default();
+        state.set_variant(TarotVariant {
+            parameters: VariantSettings::new(4),
+        });
+        for n in 0..4 {
+            let info = player(&format!("player{}", n));
+            state.add_player(info.clone());
+            state.set_player_ready(info.id);
+        }
+        let watcher = player("watcher");
+        state.add_player(watcher.clone());
+        assert!(state.is_spectator(watcher.id));
+
+        let taker = next_player_id(&state);
+        state
+            .set_bid(taker, bid::Target::GardeContre, false)
+            .unwrap();
+        while let Turn::Bidding(_) = state.get_turn() {
+            let pid = next_player_id(&state);
+            state.set_pass(pid).unwrap();
+        }
+        let updates = next_updates(&state, &state);
+        assert_eq!(5, updates.len());
+        let mut tracker = SnapshotTracker::new();
+        assert!(tracker.apply_view(update_of(updates, watcher.id)).is_some());
+
+        // a card is played : the spectator gets the changes, as the players do
+        let mut listener = TarotEventsListener {
+            game_id: Uuid::new_v4(),
+            events_states: vec![],
+        };
+        let pid = next_player_id(&state);
+        let hand = state.get_deal().hands()[state.get_deal().next_player().to_n()];
+        let mut game_manager = TarotGameManager::new(&mut state);
+        game_manager.register_listener(&mut listener);
+        let played = hand
+            .list()
+            .into_iter()
+            .any(|card| game_manager.set_play(pid, card).is_ok());
+        drop(game_manager);
+        assert!(played);
+        for (_event, emitted) in &listener.events_states {
+            let update = update_of(next_updates(&state, emitted), watcher.id);
+            assert!(tracker.apply_view(update).is_some());
+        }
+        let update = update_of(next_updates(&state, &state), watcher.id);
+        assert!(matches!(update, StateUpdate::Patch { .. }));
+        assert_eq!(
+            Some(GameView::Spectator(state.spectator_snapshot())),
+            tracker.apply_view(update)
+        );
+    }
+}
@@ -1,4 +1,9 @@
+mod archive;
+mod bots;
 mod dispatcher;
+mod store;
+
+use log::{error, info};
 
 pub(crate) use webgame_server;
 pub(crate) use webgame_protocol as protocol;
@@ -18,11 +23,23 @@ pub async fn main() {
     // let name = format!("{}", env!("CARGO_PKG_NAME"));
     let name = env!("CARGO_PKG_NAME");
 
+    if let Some(store) = store::STORE.as_ref() {
+        // The saved games are put back in play when one of their players comes back
+        match store.load_saved().await {
+            Ok(count) => info!("{} saved games wait for their players", count),
+            Err(e) => error!("could not read the games store: {}", e),
+        }
+    }
+
+    if let Some(archives) = archive::ARCHIVES.as_ref() {
+        tokio::spawn(archive::serve(archives));
+    }
+
     webgame_server::launcher::launch(
         name, version, author,
         dispatcher::on_gameplay,
         dispatcher::on_player_set_role,
-        tarot_bot::socket_listener::start
+        bots::start
         ).await;
 }
 
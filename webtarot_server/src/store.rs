@@ -0,0 +1,197 @@
+//! Games saved on disk, to survive a restart of the server.
+//!
+//! Each game is a JSON file named after its id, in the directory given by `WEBTAROT_STORE`.
+//! The files are replaced atomically : a crash while saving leaves the previous state.
+//!
+//! The games saved before a restart are loaded at boot and wait for one of their players : the
+//! first one to come back gets the game put back in play in his new game, the others join it
+//! with its join code and get their seats back.
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use log::error;
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::tarot_protocol::TarotGameState;
+
+pub const STORE_ENV: &str = "WEBTAROT_STORE";
+
+lazy_static! {
+    /// Store of the server, games are not saved when `WEBTAROT_STORE` is not set
+    pub static ref STORE: Option<GameStore> = GameStore::from_env();
+}
+
+pub struct GameStore {
+    dir: PathBuf,
+    /// Games loaded at boot, waiting for one of their players
+    saved: Mutex<Vec<(Uuid, TarotGameState)>>,
+}
+
+impl GameStore {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        GameStore {
+            dir: dir.as_ref().to_path_buf(),
+            saved: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        env::var(STORE_ENV).ok().map(GameStore::new)
+    }
+
+    fn path(&self, game_id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.json", game_id))
+    }
+
+    pub async fn save(&self, game_id: Uuid, json: Vec<u8>) -> io::Result<()> {
+        let tmp = self.dir.join(format!("{}.json.tmp", game_id));
+        fs::write(&tmp, json).await?;
+        fs::rename(tmp, self.path(game_id)).await
+    }
+
+    pub async fn delete(&self, game_id: Uuid) -> io::Result<()> {
+        match fs::remove_file(self.path(game_id)).await {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Loads the games saved before the restart, returns how many wait for their players
+    pub async fn load_saved(&self) -> io::Result<usize> {
+        fs::create_dir_all(&self.dir).await?;
+        let games = self.load_all().await?;
+        let mut saved = self.saved.lock().unwrap();
+        *saved = games;
+        Ok(saved.len())
+    }
+
+    /// Takes the saved game where `player_id` had a seat. Its file is deleted : the game is
+    /// saved again under the id of the game it is put in.
+    pub async fn claim(&self, player_id: Uuid) -> Option<TarotGameState> {
+        let (game_id, state) = {
+            let mut saved = self.saved.lock().unwrap();
+            let idx = saved
+                .iter()
+                .position(|(_, state)| state.is_absent(player_id))?;
+            saved.remove(idx)
+        };
+        if let Err(e) = self.delete(game_id).await {
+            error!("could not delete saved game {}: {}", game_id, e);
+        }
+        Some(state)
+    }
+
+    /// Saved games, as they must be played again after a restart. The games which were not in
+    /// progress have nothing to resume, their files are deleted.
+    async fn load_all(&self) -> io::Result<Vec<(Uuid, TarotGameState)>> {
+        let mut games = vec![];
+        let mut entries = fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let game_id = match path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| Uuid::parse_str(stem).ok())
+            {
+                Some(game_id) => game_id,
+                None => continue,
+            };
+            let loaded = fs::read(&path).await.and_then(|json| {
+                serde_json::from_slice::<TarotGameState>(&json).map_err(io::Error::from)
+            });
+            match loaded {
+                Ok(state) if state.is_in_progress() => games.push((game_id, state.restored())),
+                Ok(_) => self.delete(game_id).await?,
+                Err(e) => error!("could not load saved game {}: {}", path.display(), e),
+            }
+        }
+        Ok(games)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::GameState;
+    use webgame_protocol::PlayerInfo;
+
+    fn player(nickname: &str) -> PlayerInfo {
+        PlayerInfo {
+            id: Uuid::new_v4(),
+            nickname: String::from(nickname),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restart() {
+        let dir = env::temp_dir().join(format!("webtarot-store-{}", Uuid::new_v4()));
+        let store = GameStore::new(&dir);
+        assert_eq!(0, store.load_saved().await.unwrap());
+
+        // a game in progress, and a game nobody started
+        let mut state = TarotGameState::default();
+        let players: Vec<PlayerInfo> = (0..5).map(|n| player(&format!("player{}", n))).collect();
+        for info in &players {
+            state.add_player(info.clone());
+            state.set_player_ready(info.id);
+        }
+        assert!(state.is_in_progress());
+        let game_id = Uuid::new_v4();
+        store
+            .save(game_id, serde_json::to_vec(&state).unwrap())
+            .await
+            .unwrap();
+        // saving again replaces the game
+        store
+            .save(game_id, serde_json::to_vec(&state).unwrap())
+            .await
+            .unwrap();
+        let mut pregame = TarotGameState::default();
+        pregame.add_player(player("alone"));
+        let pregame_id = Uuid::new_v4();
+        store
+            .save(pregame_id, serde_json::to_vec(&pregame).unwrap())
+            .await
+            .unwrap();
+        fs::write(dir.join("notes.txt"), "not a game").await.unwrap();
+
+        // the server starts over from the same directory
+        let store = GameStore::new(&dir);
+        assert_eq!(1, store.load_saved().await.unwrap());
+        assert!(!store.path(pregame_id).exists());
+        assert!(store.claim(Uuid::new_v4()).await.is_none());
+
+        // the first player back gets the game, the others take their seats in it
+        let mut resumed = store.claim(players[2].id).await.unwrap();
+        assert!(!store.path(game_id).exists());
+        assert!(store.claim(players[3].id).await.is_none());
+        for info in &players {
+            let pos = resumed.add_player(info.clone());
+            assert_eq!(state.get_players()[&info.id].pos, pos);
+            assert_eq!(
+                state.player_snapshot(info.id).deal.hand,
+                resumed.player_snapshot(info.id).deal.hand
+            );
+        }
+        assert_eq!(state.get_turn(), resumed.get_turn());
+
+        // a finished game is deleted
+        let resumed_id = Uuid::new_v4();
+        store
+            .save(resumed_id, serde_json::to_vec(&resumed).unwrap())
+            .await
+            .unwrap();
+        store.delete(resumed_id).await.unwrap();
+        assert!(!store.path(resumed_id).exists());
+        store.delete(resumed_id).await.unwrap();
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}